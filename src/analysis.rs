@@ -0,0 +1,499 @@
+use crate::disassembler::{disassemble_code, Listing, Syntax};
+use crate::emulator::opcode::OpCode::{self, *};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+/// Most entries followed in the jump table of a `JumpRegZero`
+const MAX_JUMP_TABLE: u16 = 128;
+
+/// How control leaves a basic block
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum Flow {
+    /// Runs into the next block, which starts at a jump target
+    Fallthrough(u16),
+    /// `Goto`
+    Jump(u16),
+    /// Skip instruction, which continues at `next` if its condition is false
+    /// and at `skipped` if it's true
+    Skip { next: u16, skipped: u16 },
+    /// `JumpRegZero`, into the entries of the jump table at its target
+    Indexed(Vec<u16>),
+    /// `Return` from a subroutine
+    Return,
+    /// `Exit`, or an instruction that can't be decoded, lies outside of the ROM
+    /// or ends at the top of the address space
+    Halt,
+}
+
+impl Flow {
+    /// Addresses execution can continue at
+    pub fn successors(&self) -> Vec<u16> {
+        match self {
+            Flow::Fallthrough(next) | Flow::Jump(next) => vec![*next],
+            Flow::Skip { next, skipped } => vec![*next, *skipped],
+            Flow::Indexed(targets) => targets.clone(),
+            Flow::Return | Flow::Halt => Vec::new(),
+        }
+    }
+}
+
+/// Instructions that run in sequence, entered only at the first one
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Block {
+    /// Address of the first instruction
+    pub start: u16,
+    /// Instructions with their addresses, including the one that ends the block
+    pub instructions: Vec<(u16, OpCode)>,
+    /// Where execution continues
+    pub flow: Flow,
+}
+
+impl Block {
+    /// Address right after the last instruction
+    pub fn end(&self) -> u16 {
+        match self.instructions.last() {
+            Some((address, opcode)) => address.wrapping_add(opcode.size()),
+            None => self.start,
+        }
+    }
+
+    /// Targets of the subroutines called by the block, in order
+    pub fn calls(&self) -> Vec<u16> {
+        self.instructions
+            .iter()
+            .filter_map(|(_, opcode)| match opcode {
+                Subroutine { target } => Some(*target),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Subroutine, or the code started at the entry point
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Function {
+    /// Address of the first instruction
+    pub entry: u16,
+    /// Starts of the blocks reachable from the entry without following calls
+    pub blocks: BTreeSet<u16>,
+    /// Entries of the functions it calls
+    pub calls: BTreeSet<u16>,
+}
+
+/// Control-flow graph of a ROM, recovered by following every path from the entry point
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct ControlFlowGraph {
+    /// Address the ROM is loaded at
+    pub load_address: u16,
+    /// Address execution starts at
+    pub entry: u16,
+    /// Basic blocks by start address
+    pub blocks: BTreeMap<u16, Block>,
+    /// Entry point and called subroutines by entry address
+    pub functions: BTreeMap<u16, Function>,
+}
+
+/// Flow out of a block ending with `opcode` at `address`, or `None` if execution goes on
+/// to the next instruction.
+/// `decode` is used to find the size of a skipped instruction and the jump table entries.
+fn flow(address: u16, opcode: OpCode, decode: &dyn Fn(u16) -> Option<OpCode>) -> Option<Flow> {
+    let next = address.wrapping_add(opcode.size());
+    match opcode {
+        Goto { target } => Some(Flow::Jump(target)),
+        JumpRegZero { target } => {
+            let mut targets = vec![target];
+            targets.extend(
+                (1..MAX_JUMP_TABLE)
+                    .map(|entry| target.wrapping_add(entry * 2))
+                    .take_while(|&entry| matches!(decode(entry), Some(Goto { .. }))),
+            );
+            Some(Flow::Indexed(targets))
+        }
+        SkipNextIfRegEqualToConst { .. }
+        | SkipNextIfRegNotEqualToConst { .. }
+        | SkipNextIfRegEqualToReg { .. }
+        | SkipNextIfRegNotEqualToReg { .. }
+        | SkipNextIfRegKeyPressed { .. }
+        | SkipNextIfRegKeyNotPressed { .. } => {
+            let size = decode(next).map_or(2, |skipped| skipped.size());
+            Some(Flow::Skip {
+                next,
+                skipped: next.wrapping_add(size),
+            })
+        }
+        Return => Some(Flow::Return),
+        Exit => Some(Flow::Halt),
+        _ => None,
+    }
+}
+
+/// Recover the control-flow graph of `rom` loaded at `load_address`, starting at `entry`.
+///
+/// Only instructions reachable from the entry point are decoded, so sprites and other data
+/// aren't mistaken for code.
+/// `JumpRegZero` is assumed to index a table of `Goto`s at its target, which are followed
+/// as long as they decode as such.
+pub fn analyze(rom: &[u8], load_address: u16, entry: u16) -> ControlFlowGraph {
+    let decode = |address: u16| -> Option<OpCode> {
+        let offset = (address as usize).checked_sub(load_address as usize)?;
+        let opcode = OpCode::try_from(rom.get(offset..)?).ok()?;
+        // Keep the addresses after the instruction within 16 bits
+        if address as usize + opcode.size() as usize > 0xFFFF {
+            return None;
+        }
+        Some(opcode)
+    };
+
+    let mut instructions = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    let mut entries = BTreeSet::new();
+    leaders.insert(entry);
+    entries.insert(entry);
+    let mut work = vec![entry];
+    while let Some(address) = work.pop() {
+        if instructions.contains_key(&address) {
+            continue;
+        }
+        let opcode = match decode(address) {
+            Some(opcode) => opcode,
+            None => continue,
+        };
+        instructions.insert(address, opcode);
+        if let Subroutine { target } = opcode {
+            leaders.insert(target);
+            entries.insert(target);
+            work.push(target);
+        }
+        match flow(address, opcode, &decode) {
+            Some(flow) => {
+                for successor in flow.successors() {
+                    leaders.insert(successor);
+                    work.push(successor);
+                }
+            }
+            None => work.push(address.wrapping_add(opcode.size())),
+        }
+    }
+
+    let mut blocks = BTreeMap::new();
+    for &start in &leaders {
+        if !instructions.contains_key(&start) {
+            continue;
+        }
+        let mut block = Block {
+            start,
+            instructions: Vec::new(),
+            flow: Flow::Halt,
+        };
+        let mut address = start;
+        while let Some(&opcode) = instructions.get(&address) {
+            block.instructions.push((address, opcode));
+            if let Some(flow) = flow(address, opcode, &decode) {
+                block.flow = flow;
+                break;
+            }
+            address = address.wrapping_add(opcode.size());
+            if leaders.contains(&address) && instructions.contains_key(&address) {
+                block.flow = Flow::Fallthrough(address);
+                break;
+            }
+        }
+        blocks.insert(start, block);
+    }
+
+    let mut functions = BTreeMap::new();
+    for &entry in entries.iter().filter(|entry| blocks.contains_key(entry)) {
+        let mut function = Function {
+            entry,
+            blocks: BTreeSet::new(),
+            calls: BTreeSet::new(),
+        };
+        let mut work = vec![entry];
+        while let Some(start) = work.pop() {
+            let block: &Block = match blocks.get(&start) {
+                Some(block) if function.blocks.insert(start) => block,
+                _ => continue,
+            };
+            function.calls.extend(block.calls());
+            work.extend(block.flow.successors());
+        }
+        functions.insert(entry, function);
+    }
+
+    ControlFlowGraph {
+        load_address,
+        entry,
+        blocks,
+        functions,
+    }
+}
+
+impl ControlFlowGraph {
+    /// Name of the function at `entry`: `main` for the entry point, `sub_NNN` for subroutines
+    pub fn function_name(&self, entry: u16) -> String {
+        if entry == self.entry {
+            "main".to_string()
+        } else {
+            format!("sub_{:03X}", entry)
+        }
+    }
+
+    /// Block containing the instruction at `address`
+    pub fn block_at(&self, address: u16) -> Option<&Block> {
+        self.blocks
+            .values()
+            .find(|block| block.instructions.iter().any(|&(at, _)| at == address))
+    }
+
+    /// Addresses of all the reachable instructions
+    pub fn instructions(&self) -> BTreeSet<u16> {
+        self.blocks
+            .values()
+            .flat_map(|block| block.instructions.iter().map(|&(address, _)| address))
+            .collect()
+    }
+
+    /// Check if the byte at `address` belongs to a reachable instruction, rather than data
+    pub fn is_code(&self, address: u16) -> bool {
+        self.blocks.values().any(|block| {
+            block.instructions.iter().any(|&(at, opcode)| {
+                (at as usize..at as usize + opcode.size() as usize).contains(&(address as usize))
+            })
+        })
+    }
+
+    /// Entries of the functions called by each function
+    pub fn call_graph(&self) -> BTreeMap<u16, BTreeSet<u16>> {
+        self.functions
+            .values()
+            .map(|function| (function.entry, function.calls.clone()))
+            .collect()
+    }
+
+    /// Disassemble `rom`, the ROM the graph was recovered from, decoding only reachable code
+    pub fn listing(&self, rom: &[u8], syntax: Syntax) -> Listing {
+        disassemble_code(rom, self.load_address, syntax, &self.instructions())
+    }
+
+    /// Graphviz DOT of the basic blocks, with calls as dashed edges to the called functions
+    pub fn dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            if self.functions.contains_key(&block.start) {
+                write!(label, "{}:\\l", self.function_name(block.start)).unwrap();
+            }
+            for (address, opcode) in &block.instructions {
+                write!(label, "{:04X}  {}\\l", address, opcode).unwrap();
+            }
+            writeln!(dot, "    b{:03X} [label=\"{}\"];", block.start, label).unwrap();
+        }
+        for block in self.blocks.values() {
+            let edges = match &block.flow {
+                Flow::Skip { next, skipped } => vec![(*next, ""), (*skipped, " [label=skip]")],
+                flow => flow.successors().into_iter().map(|s| (s, "")).collect(),
+            };
+            for (successor, attributes) in edges {
+                if self.blocks.contains_key(&successor) {
+                    writeln!(
+                        dot,
+                        "    b{:03X} -> b{:03X}{};",
+                        block.start, successor, attributes
+                    )
+                    .unwrap();
+                }
+            }
+            for call in block.calls() {
+                if self.blocks.contains_key(&call) {
+                    writeln!(
+                        dot,
+                        "    b{:03X} -> b{:03X} [style=dashed];",
+                        block.start, call
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Graphviz DOT of the call graph
+    pub fn call_graph_dot(&self) -> String {
+        let mut dot = String::from("digraph calls {\n");
+        for entry in self.functions.keys() {
+            writeln!(
+                dot,
+                "    f{:03X} [label=\"{}\"];",
+                entry,
+                self.function_name(*entry)
+            )
+            .unwrap();
+        }
+        for (entry, calls) in self.call_graph() {
+            for call in calls
+                .iter()
+                .filter(|call| self.functions.contains_key(call))
+            {
+                writeln!(dot, "    f{:03X} -> f{:03X};", entry, call).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::{analyze, Flow};
+    use crate::disassembler::Syntax;
+    use crate::emulator::opcode::OpCode::*;
+
+    /// Program with a counting loop, a subroutine called twice and a sprite
+    const ROM: [u8; 20] = [
+        0x60, 0x00, // 200: LD V0, 0
+        0x22, 0x0C, // 202: CALL sub_20C
+        0x70, 0x01, // 204: ADD V0, 1
+        0x30, 0x05, // 206: SE V0, 5
+        0x12, 0x02, // 208: JP 0x202
+        0x12, 0x0A, // 20A: JP 0x20A
+        0xA2, 0x12, // 20C: LD I, 0x212
+        0xD0, 0x11, // 20E: DRW V0, V1, 1
+        0x00, 0xEE, // 210: RET
+        0xFF, 0x00, // 212: sprite, decodes as LD HF, VF
+    ];
+
+    /// Test splitting the program into basic blocks
+    #[test]
+    fn test_blocks() {
+        let cfg = analyze(&ROM, 0x200, 0x200);
+        let starts: Vec<u16> = cfg.blocks.keys().copied().collect();
+        assert_eq!(starts, vec![0x200, 0x202, 0x208, 0x20A, 0x20C]);
+        assert_eq!(cfg.blocks[&0x200].flow, Flow::Fallthrough(0x202));
+        assert_eq!(
+            cfg.blocks[&0x202].instructions,
+            vec![
+                (0x202, Subroutine { target: 0x20C }),
+                (
+                    0x204,
+                    RegAddConst {
+                        register: 0,
+                        constant: 1
+                    }
+                ),
+                (
+                    0x206,
+                    SkipNextIfRegEqualToConst {
+                        register: 0,
+                        constant: 5
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            cfg.blocks[&0x202].flow,
+            Flow::Skip {
+                next: 0x208,
+                skipped: 0x20A
+            }
+        );
+        assert_eq!(cfg.blocks[&0x208].flow, Flow::Jump(0x202));
+        assert_eq!(cfg.blocks[&0x20A].flow, Flow::Jump(0x20A));
+        assert_eq!(cfg.blocks[&0x20C].flow, Flow::Return);
+        assert_eq!(cfg.blocks[&0x20C].end(), 0x212);
+        assert_eq!(cfg.block_at(0x204).map(|block| block.start), Some(0x202));
+    }
+
+    /// Test telling code from data
+    #[test]
+    fn test_code_map() {
+        let cfg = analyze(&ROM, 0x200, 0x200);
+        assert!(cfg.is_code(0x211));
+        assert!(!cfg.is_code(0x212));
+        assert!(!cfg.instructions().contains(&0x212));
+        let listing = cfg.listing(&ROM, Syntax::Cowgod);
+        let last = listing.lines.last().unwrap();
+        assert_eq!((last.address, last.opcode), (0x212, None));
+        assert!(listing
+            .to_string()
+            .ends_with("data_212:\n0212  FF 00        DB 0xFF, 0x00\n"));
+    }
+
+    /// Test finding functions and the call graph
+    #[test]
+    fn test_functions() {
+        let cfg = analyze(&ROM, 0x200, 0x200);
+        let main = &cfg.functions[&0x200];
+        let blocks: Vec<u16> = main.blocks.iter().copied().collect();
+        assert_eq!(blocks, vec![0x200, 0x202, 0x208, 0x20A]);
+        assert_eq!(
+            cfg.call_graph()[&0x200].iter().collect::<Vec<_>>(),
+            vec![&0x20C]
+        );
+        assert!(cfg.functions[&0x20C].calls.is_empty());
+        assert_eq!(cfg.function_name(0x20C), "sub_20C");
+        assert_eq!(
+            cfg.call_graph_dot(),
+            "digraph calls {\n    f200 [label=\"main\"];\n    f20C [label=\"sub_20C\"];\n    f200 -> f20C;\n}\n"
+        );
+    }
+
+    /// Test following a jump table
+    #[test]
+    fn test_jump_table() {
+        let rom = [
+            0xC0, 0x02, // 200: RND V0, 2
+            0x80, 0x0E, // 202: SHL V0, V0
+            0xB2, 0x08, // 204: JP V0, 0x208
+            0x00, 0xFD, // 206: EXIT
+            0x12, 0x0E, // 208: JP 0x20E
+            0x12, 0x06, // 20A: JP 0x206
+            0x3C, 0x42, // 20C: data
+            0x00, 0xE0, // 20E: CLS
+            0x12, 0x06, // 210: JP 0x206
+        ];
+        let cfg = analyze(&rom, 0x200, 0x200);
+        assert_eq!(cfg.blocks[&0x200].flow, Flow::Indexed(vec![0x208, 0x20A]));
+        assert_eq!(cfg.blocks[&0x206].flow, Flow::Halt);
+        assert!(cfg.blocks.contains_key(&0x20E));
+        assert!(!cfg.is_code(0x20C));
+    }
+
+    /// Test skipping over a 4 bytes long instruction and running off the ROM
+    #[test]
+    fn test_skip_long() {
+        let rom = [0xE1, 0x9E, 0xF0, 0x00, 0x12, 0x34, 0x00];
+        let cfg = analyze(&rom, 0x200, 0x200);
+        assert_eq!(
+            cfg.blocks[&0x200].flow,
+            Flow::Skip {
+                next: 0x202,
+                skipped: 0x206
+            }
+        );
+        assert_eq!(cfg.blocks[&0x202].flow, Flow::Halt);
+        assert!(!cfg.blocks.contains_key(&0x206));
+    }
+
+    /// Test exporting the graph
+    #[test]
+    fn test_dot() {
+        let rom = [0x22, 0x06, 0x3F, 0x00, 0x00, 0xFD, 0x00, 0xEE];
+        let cfg = analyze(&rom, 0x200, 0x200);
+        assert_eq!(
+            cfg.dot(),
+            "\
+digraph cfg {
+    node [shape=box, fontname=monospace];
+    b200 [label=\"main:\\l0200  CALL 0x206\\l0202  SE VF, 0x00\\l\"];
+    b204 [label=\"0204  EXIT\\l\"];
+    b206 [label=\"sub_206:\\l0206  RET\\l\"];
+    b200 -> b204;
+    b200 -> b206 [label=skip];
+    b200 -> b206 [style=dashed];
+}
+"
+        );
+    }
+}
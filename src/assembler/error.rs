@@ -0,0 +1,102 @@
+use crate::emulator::opcode::InvalidOperand;
+use std::error::Error;
+use std::fmt;
+
+/// Position in the source: file name and line number, starting from 1
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Location {
+    /// Name of the source file
+    pub file: String,
+    /// Line number, starting from 1
+    pub line: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+/// Problem found in the source
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum ErrorKind {
+    /// Source file couldn't be read
+    Io(String),
+    /// Line isn't a label, an instruction or a directive
+    Syntax(String),
+    /// Mnemonic or directive doesn't exist
+    UnknownMnemonic(String),
+    /// Operands don't fit any form of the mnemonic
+    InvalidOperands(String),
+    /// Operand is neither a number nor a label name
+    InvalidNumber(String),
+    /// Label is used but never defined
+    UndefinedLabel(String),
+    /// Label is defined twice
+    DuplicateLabel(String),
+    /// Value doesn't fit its operand
+    OutOfRange(i64),
+    /// Opcode can't be encoded
+    InvalidOperand(InvalidOperand),
+    /// Program doesn't fit the address space
+    ProgramTooLarge,
+    /// Token isn't the expected one
+    Expected { expected: String, found: String },
+    /// Block is closed without being opened, or never closed
+    Unbalanced(String),
+    /// Address is written twice
+    Overlap(u16),
+    /// Feature exists in the language, but has no opcode on this machine
+    Unsupported(String),
+    /// Program has no `main` label to start from
+    MissingMain,
+    /// Expression divides by zero
+    DivisionByZero,
+    /// File includes itself, or macro expands itself, directly or not
+    Recursive(String),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Io(error) => write!(f, "can't read source: {}", error),
+            ErrorKind::Syntax(text) => write!(f, "syntax error: `{}`", text),
+            ErrorKind::UnknownMnemonic(mnemonic) => write!(f, "unknown mnemonic `{}`", mnemonic),
+            ErrorKind::InvalidOperands(mnemonic) => {
+                write!(f, "invalid operands for `{}`", mnemonic)
+            }
+            ErrorKind::InvalidNumber(text) => write!(f, "invalid number `{}`", text),
+            ErrorKind::UndefinedLabel(label) => write!(f, "undefined label `{}`", label),
+            ErrorKind::DuplicateLabel(label) => write!(f, "label `{}` is already defined", label),
+            ErrorKind::OutOfRange(value) => write!(f, "value {} is out of range", value),
+            ErrorKind::InvalidOperand(error) => write!(f, "{}", error),
+            ErrorKind::ProgramTooLarge => write!(f, "program doesn't fit in memory"),
+            ErrorKind::Expected { expected, found } => {
+                write!(f, "expected {}, found `{}`", expected, found)
+            }
+            ErrorKind::Unbalanced(token) => write!(f, "unbalanced `{}`", token),
+            ErrorKind::Overlap(address) => write!(f, "address {:#05X} is written twice", address),
+            ErrorKind::Unsupported(feature) => write!(f, "`{}` is not supported", feature),
+            ErrorKind::MissingMain => write!(f, "program has no `main` label"),
+            ErrorKind::DivisionByZero => write!(f, "division by zero"),
+            ErrorKind::Recursive(name) => write!(f, "`{}` includes or expands itself", name),
+        }
+    }
+}
+
+/// Error raised by invalid source, with the position it was found at
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct AssemblyError {
+    /// Where the error is
+    pub location: Location,
+    /// What the error is
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.kind)
+    }
+}
+
+impl Error for AssemblyError {}
@@ -0,0 +1,195 @@
+use crate::assembler::error::ErrorKind;
+use crate::assembler::parser::parse_number;
+
+/// Piece of an expression
+#[derive(Eq, PartialEq, Clone, Debug)]
+enum Token {
+    /// Number or symbol name, `$` is the current address
+    Operand(String),
+    /// Operator or parenthesis
+    Operator(&'static str),
+}
+
+/// Operators, longest first so that `<<` isn't read as two `<`
+const OPERATORS: [&str; 13] = [
+    "<<", ">>", "+", "-", "*", "/", "%", "&", "|", "^", "~", "(", ")",
+];
+
+/// Binary operators from the loosest to the tightest binding, like in C
+const PRECEDENCE: [&[&str]; 6] = [
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// Split `text` into operands and operators
+fn tokenize(text: &str) -> Result<Vec<Token>, ErrorKind> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        if let Some(operator) = OPERATORS.iter().find(|o| rest.starts_with(*o)) {
+            tokens.push(Token::Operator(operator));
+            rest = &rest[operator.len()..];
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$'))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(ErrorKind::Syntax(text.to_string()));
+            }
+            tokens.push(Token::Operand(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent evaluator over the tokens of an expression
+struct Evaluator<'a> {
+    text: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+    symbol: &'a dyn Fn(&str) -> Option<i64>,
+}
+
+impl<'a> Evaluator<'a> {
+    /// Error for a malformed expression
+    fn syntax(&self) -> ErrorKind {
+        ErrorKind::Syntax(self.text.to_string())
+    }
+
+    /// Read the next token
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Evaluate the binary operators of `level` and tighter ones
+    fn binary(&mut self, level: usize) -> Result<i64, ErrorKind> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Operator(operator)) = self.tokens.get(self.position).cloned() {
+            if !PRECEDENCE[level].contains(&operator) {
+                break;
+            }
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            left = match operator {
+                "|" => left | right,
+                "^" => left ^ right,
+                "&" => left & right,
+                "<<" => left.checked_shl(right as u32).unwrap_or(0),
+                ">>" => left.checked_shr(right as u32).unwrap_or(0),
+                "+" => left.wrapping_add(right),
+                "-" => left.wrapping_sub(right),
+                "*" => left.wrapping_mul(right),
+                _ if right == 0 => return Err(ErrorKind::DivisionByZero),
+                "/" => left.checked_div(right).ok_or(ErrorKind::OutOfRange(left))?,
+                _ => left.checked_rem(right).ok_or(ErrorKind::OutOfRange(left))?,
+            };
+        }
+        Ok(left)
+    }
+
+    /// Evaluate a unary operator, a parenthesized expression or an operand
+    fn unary(&mut self) -> Result<i64, ErrorKind> {
+        match self.next() {
+            Some(Token::Operator("-")) => Ok(self.unary()?.wrapping_neg()),
+            Some(Token::Operator("+")) => self.unary(),
+            Some(Token::Operator("~")) => Ok(!self.unary()?),
+            Some(Token::Operator("(")) => {
+                let value = self.binary(0)?;
+                match self.next() {
+                    Some(Token::Operator(")")) => Ok(value),
+                    _ => Err(self.syntax()),
+                }
+            }
+            Some(Token::Operand(operand)) => {
+                if operand.starts_with(|c: char| c.is_ascii_digit()) {
+                    parse_number(&operand)
+                } else {
+                    (self.symbol)(&operand).ok_or(ErrorKind::UndefinedLabel(operand))
+                }
+            }
+            _ => Err(self.syntax()),
+        }
+    }
+}
+
+/// Evaluate the expression `text`, looking up symbol names and `$` with `symbol`.
+///
+/// Supports numbers, symbols, parentheses, unary `- + ~` and binary
+/// `* / % + - << >> & ^ |` with C precedence.
+pub fn evaluate(text: &str, symbol: &dyn Fn(&str) -> Option<i64>) -> Result<i64, ErrorKind> {
+    let mut evaluator = Evaluator {
+        text,
+        tokens: tokenize(text)?,
+        position: 0,
+        symbol,
+    };
+    let value = evaluator.binary(0)?;
+    if evaluator.position != evaluator.tokens.len() {
+        return Err(evaluator.syntax());
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::error::ErrorKind;
+    use crate::assembler::expression::evaluate;
+
+    /// Evaluate `text` with `start` at `0x200` and `$` at `0x210`
+    fn eval(text: &str) -> Result<i64, ErrorKind> {
+        evaluate(text, &|name| match name {
+            "start" => Some(0x200),
+            "$" => Some(0x210),
+            _ => None,
+        })
+    }
+
+    /// Test operators and their precedence
+    #[test]
+    fn test_operators() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("1 << 2 + 1"), Ok(8));
+        assert_eq!(eval("0xF0 | 0x0F & 0x3"), Ok(0xF3));
+        assert_eq!(eval("7 / 2 - 7 % 2"), Ok(2));
+        assert_eq!(eval("-2 * -3"), Ok(6));
+        assert_eq!(eval("~0 & 0xFF"), Ok(0xFF));
+        assert_eq!(eval("0b1010 ^ 0b0110 >> 1"), Ok(0b1001));
+    }
+
+    /// Test symbols and the current address
+    #[test]
+    fn test_symbols() {
+        assert_eq!(eval("$ - start"), Ok(0x10));
+        assert_eq!(eval("start >> 8"), Ok(2));
+        assert_eq!(
+            eval("end - start"),
+            Err(ErrorKind::UndefinedLabel("end".to_string()))
+        );
+    }
+
+    /// Test malformed expressions
+    #[test]
+    fn test_errors() {
+        assert_eq!(eval("1 +"), Err(ErrorKind::Syntax("1 +".to_string())));
+        assert_eq!(eval("(1"), Err(ErrorKind::Syntax("(1".to_string())));
+        assert_eq!(eval("1 2"), Err(ErrorKind::Syntax("1 2".to_string())));
+        assert_eq!(eval("1 / (2 - 2)"), Err(ErrorKind::DivisionByZero));
+        assert_eq!(
+            eval("0xZ"),
+            Err(ErrorKind::InvalidNumber("0xZ".to_string()))
+        );
+        assert_eq!(eval("1 ? 2"), Err(ErrorKind::Syntax("1 ? 2".to_string())));
+    }
+}
@@ -0,0 +1,345 @@
+use crate::assembler::error::ErrorKind;
+use crate::emulator::opcode::OpCode::{self, *};
+
+/// Mnemonics of the instructions, as used by the disassembler's Cowgod syntax
+pub const MNEMONICS: [&str; 31] = [
+    "SYS", "CLS", "RET", "SCD", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL", "SE", "SNE",
+    "SAVE", "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN", "SHL", "RND", "DRW",
+    "SKP", "SKNP", "PLANE", "AUDIO", "PITCH",
+];
+
+/// Operand of an instruction
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum Operand {
+    /// `VX`
+    Register(u8),
+    /// `I`
+    Index,
+    /// `[I]`, memory at `I`
+    IndexMemory,
+    /// `DT`
+    DelayTimer,
+    /// `ST`
+    SoundTimer,
+    /// `K`, the awaited key
+    Key,
+    /// `F`, the small font
+    Font,
+    /// `HF`, the large font
+    LargeFont,
+    /// `B`, binary-coded decimal digits
+    Bcd,
+    /// `R`, the user flags
+    Flags,
+    /// `LONG` followed by a 16-bit address
+    Long(String),
+    /// Number or label
+    Value(String),
+}
+
+impl Operand {
+    /// Classify an operand.
+    /// Keywords and register names take precedence over labels with the same name.
+    pub fn parse(text: &str) -> Operand {
+        let upper = text.to_ascii_uppercase();
+        match upper.as_str() {
+            "I" => return Operand::Index,
+            "[I]" => return Operand::IndexMemory,
+            "DT" => return Operand::DelayTimer,
+            "ST" => return Operand::SoundTimer,
+            "K" => return Operand::Key,
+            "F" => return Operand::Font,
+            "HF" => return Operand::LargeFont,
+            "B" => return Operand::Bcd,
+            "R" => return Operand::Flags,
+            _ => {}
+        }
+        if let Some(register) = upper.strip_prefix('V') {
+            if register.len() == 1 {
+                if let Ok(register) = u8::from_str_radix(register, 16) {
+                    return Operand::Register(register);
+                }
+            }
+        }
+        if upper.starts_with("LONG ") {
+            return Operand::Long(text[5..].trim().to_string());
+        }
+        Operand::Value(text.to_string())
+    }
+}
+
+/// Convert `value` into a field of `bits` bits
+fn unsigned(value: i64, bits: u32) -> Result<u16, ErrorKind> {
+    if (0..1 << bits).contains(&value) {
+        Ok(value as u16)
+    } else {
+        Err(ErrorKind::OutOfRange(value))
+    }
+}
+
+/// Convert `value` into a byte, negative values are stored in two's complement
+fn byte(value: i64) -> Result<u8, ErrorKind> {
+    if (-128..=255).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(ErrorKind::OutOfRange(value))
+    }
+}
+
+/// Build the opcode for `mnemonic` with `operands`, using `value` to evaluate numbers and labels
+pub fn instruction(
+    mnemonic: &str,
+    operands: &[Operand],
+    value: &dyn Fn(&str) -> Result<i64, ErrorKind>,
+) -> Result<OpCode, ErrorKind> {
+    use Operand::*;
+    let address = |text: &str| unsigned(value(text)?, 12);
+    let nibble = |text: &str| Ok(unsigned(value(text)?, 4)? as u8);
+    let constant = |text: &str| byte(value(text)?);
+    let upper = mnemonic.to_ascii_uppercase();
+    let opcode = match (upper.as_str(), operands) {
+        ("SYS", [Value(target)]) => _NativeCall {
+            target: address(target)?,
+        },
+        ("CLS", []) => ClearScreen,
+        ("RET", []) => Return,
+        ("SCD", [Value(rows)]) => ScrollDown {
+            rows: nibble(rows)?,
+        },
+        ("SCR", []) => ScrollRight,
+        ("SCL", []) => ScrollLeft,
+        ("EXIT", []) => Exit,
+        ("LOW", []) => LowRes,
+        ("HIGH", []) => HighRes,
+        ("JP", [Value(target)]) => Goto {
+            target: address(target)?,
+        },
+        ("JP", [Register(0), Value(target)]) => JumpRegZero {
+            target: address(target)?,
+        },
+        ("CALL", [Value(target)]) => Subroutine {
+            target: address(target)?,
+        },
+        ("SE", [Register(register), Value(value)]) => SkipNextIfRegEqualToConst {
+            register: *register,
+            constant: constant(value)?,
+        },
+        ("SNE", [Register(register), Value(value)]) => SkipNextIfRegNotEqualToConst {
+            register: *register,
+            constant: constant(value)?,
+        },
+        ("SE", [Register(x), Register(y)]) => SkipNextIfRegEqualToReg {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("SAVE", [Register(x), Register(y)]) => RegRangeDump {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("LOAD", [Register(x), Register(y)]) => RegRangeLoad {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("LD", [Register(register), Value(value)]) => RegSetConst {
+            register: *register,
+            constant: constant(value)?,
+        },
+        ("ADD", [Register(register), Value(value)]) => RegAddConst {
+            register: *register,
+            constant: constant(value)?,
+        },
+        ("LD", [Register(x), Register(y)]) => RegMov {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("OR", [Register(x), Register(y)]) => RegBitwiseOr {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("AND", [Register(x), Register(y)]) => RegBitwiseAnd {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("XOR", [Register(x), Register(y)]) => RegBitwiseXor {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("ADD", [Register(x), Register(y)]) => RegAdd {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("SUB", [Register(x), Register(y)]) => RegSub {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("SHR", [Register(x)]) => RegRightShift {
+            register_x: *x,
+            register_y: *x,
+        },
+        ("SHR", [Register(x), Register(y)]) => RegRightShift {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("SUBN", [Register(x), Register(y)]) => RegReverseSub {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("SHL", [Register(x)]) => RegLeftShift {
+            register_x: *x,
+            register_y: *x,
+        },
+        ("SHL", [Register(x), Register(y)]) => RegLeftShift {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("SNE", [Register(x), Register(y)]) => SkipNextIfRegNotEqualToReg {
+            register_x: *x,
+            register_y: *y,
+        },
+        ("LD", [Index, Value(target)]) => Mem {
+            target: address(target)?,
+        },
+        ("RND", [Register(register), Value(value)]) => RandToReg {
+            register: *register,
+            constant: constant(value)?,
+        },
+        ("DRW", [Register(x), Register(y), Value(height)]) => DisplaySprite {
+            coord_x: *x,
+            coord_y: *y,
+            height: nibble(height)?,
+        },
+        ("SKP", [Register(register)]) => SkipNextIfRegKeyPressed {
+            register: *register,
+        },
+        ("SKNP", [Register(register)]) => SkipNextIfRegKeyNotPressed {
+            register: *register,
+        },
+        ("LD", [Index, Long(target)]) => MemLong {
+            target: unsigned(value(target)?, 16)?,
+        },
+        ("PLANE", [Value(planes)]) => SelectPlanes {
+            planes: nibble(planes)?,
+        },
+        ("AUDIO", []) => LoadAudioPattern,
+        ("LD", [Register(register), DelayTimer]) => SetRegToDelayTimer {
+            register: *register,
+        },
+        ("LD", [Register(register), Key]) => SetRegToKeyPressed {
+            register: *register,
+        },
+        ("LD", [DelayTimer, Register(register)]) => SetDelayTimerToReg {
+            register: *register,
+        },
+        ("LD", [SoundTimer, Register(register)]) => SetSoundTimerToReg {
+            register: *register,
+        },
+        ("ADD", [Index, Register(register)]) => MemAddReg {
+            register: *register,
+        },
+        ("LD", [Font, Register(register)]) => MemMoveToRegChar {
+            register: *register,
+        },
+        ("LD", [LargeFont, Register(register)]) => MemMoveToRegLargeChar {
+            register: *register,
+        },
+        ("PITCH", [Register(register)]) => SetPitchToReg {
+            register: *register,
+        },
+        ("LD", [Bcd, Register(register)]) => StoreBCD {
+            register: *register,
+        },
+        ("LD", [IndexMemory, Register(register)]) => RegDump {
+            register: *register,
+        },
+        ("LD", [Register(register), IndexMemory]) => RegLoad {
+            register: *register,
+        },
+        ("LD", [Flags, Register(register)]) => StoreFlags {
+            register: *register,
+        },
+        ("LD", [Register(register), Flags]) => LoadFlags {
+            register: *register,
+        },
+        (mnemonic, _) if MNEMONICS.contains(&mnemonic) => {
+            return Err(ErrorKind::InvalidOperands(mnemonic.to_string()))
+        }
+        _ => return Err(ErrorKind::UnknownMnemonic(mnemonic.to_string())),
+    };
+    Ok(opcode)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::error::ErrorKind;
+    use crate::assembler::instruction::{instruction, Operand};
+    use crate::assembler::parser::parse_number;
+    use crate::emulator::opcode::OpCode::{self, *};
+
+    /// Assemble `mnemonic` with `operands`, where every value is a number
+    fn assemble(mnemonic: &str, operands: &[&str]) -> Result<OpCode, ErrorKind> {
+        let operands: Vec<Operand> = operands.iter().map(|text| Operand::parse(text)).collect();
+        instruction(mnemonic, &operands, &parse_number)
+    }
+
+    /// Test operand classification
+    #[test]
+    fn test_operands() {
+        assert_eq!(Operand::parse("vA"), Operand::Register(0xA));
+        assert_eq!(Operand::parse("[i]"), Operand::IndexMemory);
+        assert_eq!(Operand::parse("hf"), Operand::LargeFont);
+        assert_eq!(
+            Operand::parse("LONG 0x1234"),
+            Operand::Long("0x1234".to_string())
+        );
+        assert_eq!(Operand::parse("VX"), Operand::Value("VX".to_string()));
+        assert_eq!(Operand::parse("vf0"), Operand::Value("vf0".to_string()));
+    }
+
+    /// Test the optional shift operand and mnemonic case
+    #[test]
+    fn test_forms() {
+        assert_eq!(
+            assemble("shr", &["V3"]),
+            Ok(RegRightShift {
+                register_x: 3,
+                register_y: 3
+            })
+        );
+        assert_eq!(
+            assemble("ADD", &["V3", "-1"]),
+            Ok(RegAddConst {
+                register: 3,
+                constant: 0xFF
+            })
+        );
+    }
+
+    /// Test that bad mnemonics and operands are rejected
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            assemble("MOV", &["V0", "V1"]),
+            Err(ErrorKind::UnknownMnemonic("MOV".to_string()))
+        );
+        assert_eq!(
+            assemble("LD", &["DT", "K"]),
+            Err(ErrorKind::InvalidOperands("LD".to_string()))
+        );
+        assert_eq!(
+            assemble("JP", &["V1", "0x200"]),
+            Err(ErrorKind::InvalidOperands("JP".to_string()))
+        );
+        assert_eq!(
+            assemble("JP", &["0x1000"]),
+            Err(ErrorKind::OutOfRange(0x1000))
+        );
+        assert_eq!(
+            assemble("LD", &["V0", "256"]),
+            Err(ErrorKind::OutOfRange(256))
+        );
+        assert_eq!(
+            assemble("DRW", &["V0", "V1", "16"]),
+            Err(ErrorKind::OutOfRange(16))
+        );
+    }
+}
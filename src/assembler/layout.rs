@@ -0,0 +1,418 @@
+use crate::assembler::error::{AssemblyError, ErrorKind, Location};
+use crate::assembler::expression::evaluate;
+use crate::assembler::parser::{is_identifier, parse_line, Statement};
+use crate::assembler::size;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Deepest nesting of includes and macro expansions
+const MAX_DEPTH: usize = 64;
+
+/// Source of the files named by `INCLUDE`
+pub trait Loader {
+    /// Contents of the file `name`, or the reason it can't be read
+    fn load(&self, name: &str) -> Result<String, String>;
+}
+
+/// Loads included files from a directory
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct FileLoader {
+    /// Directory the names are relative to
+    pub directory: PathBuf,
+}
+
+impl Loader for FileLoader {
+    fn load(&self, name: &str) -> Result<String, String> {
+        fs::read_to_string(self.directory.join(name)).map_err(|error| error.to_string())
+    }
+}
+
+/// Loads included files from memory, by name
+impl Loader for BTreeMap<String, String> {
+    fn load(&self, name: &str) -> Result<String, String> {
+        self.get(name)
+            .cloned()
+            .ok_or_else(|| format!("no file named `{}`", name))
+    }
+}
+
+/// Statement placed at its address, encoded once every label is known
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Item {
+    /// Where the statement comes from
+    pub location: Location,
+    /// Address of the first generated byte
+    pub address: usize,
+    /// The statement itself
+    pub statement: Statement,
+}
+
+/// Macro defined by `name: MACRO parameter, ...` up to `ENDM`
+#[derive(Eq, PartialEq, Clone, Debug)]
+struct Macro {
+    /// Names replaced by the arguments
+    parameters: Vec<String>,
+    /// Lines between `MACRO` and `ENDM`
+    body: Vec<String>,
+}
+
+/// Open `IF` block
+#[derive(Eq, PartialEq, Clone, Debug)]
+struct Condition {
+    /// Lines of the current branch are assembled
+    active: bool,
+    /// A branch was assembled already, or none will be because an outer block is inactive
+    taken: bool,
+    /// `ELSE` was seen
+    in_else: bool,
+    /// Where the block starts
+    location: Location,
+}
+
+/// First pass of the assembler.
+///
+/// Expands includes, macros and conditional blocks, defines labels and constants,
+/// and places the remaining statements at their addresses.
+pub struct Layout<'a> {
+    loader: &'a dyn Loader,
+    /// Address of the next statement
+    pub address: usize,
+    /// Values of the labels and constants
+    pub symbols: BTreeMap<String, i64>,
+    /// Addresses of the labels
+    pub labels: BTreeMap<String, u16>,
+    /// Values of the `EQU` constants
+    pub constants: BTreeMap<String, i64>,
+    /// Statements that generate code, in source order
+    pub items: Vec<Item>,
+    macros: BTreeMap<String, Macro>,
+    stack: Vec<String>,
+    expansions: usize,
+}
+
+/// Replace the `parameters` in `text` with the `arguments`, and `\@` with `id`
+fn substitute(text: &str, parameters: &[String], arguments: &[String], id: usize) -> String {
+    let text = text.replace("\\@", &id.to_string());
+    let mut result = String::with_capacity(text.len());
+    let mut word = String::new();
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+    for c in text.chars().chain(std::iter::once('\n')) {
+        if is_word(c) {
+            word.push(c);
+            continue;
+        }
+        match parameters.iter().position(|parameter| *parameter == word) {
+            Some(index) => result.push_str(&arguments[index]),
+            None => result.push_str(&word),
+        }
+        word.clear();
+        result.push(c);
+    }
+    result.pop();
+    result
+}
+
+impl<'a> Layout<'a> {
+    /// Make a layout starting at `address`, reading includes with `loader`
+    pub fn new(loader: &'a dyn Loader, address: usize) -> Self {
+        Self {
+            loader,
+            address,
+            symbols: BTreeMap::new(),
+            labels: BTreeMap::new(),
+            constants: BTreeMap::new(),
+            items: Vec::new(),
+            macros: BTreeMap::new(),
+            stack: Vec::new(),
+            expansions: 0,
+        }
+    }
+
+    /// Lay out the file `file` with contents `source`
+    pub fn file(&mut self, file: &str, source: &str) -> Result<(), AssemblyError> {
+        let lines: Vec<(Location, String)> = source
+            .lines()
+            .enumerate()
+            .map(|(index, text)| {
+                let location = Location {
+                    file: file.to_string(),
+                    line: index + 1,
+                };
+                (location, text.to_string())
+            })
+            .collect();
+        self.nested(file, &lines)
+    }
+
+    /// Lay out `lines` of the file or macro `name`, guarding against recursion
+    fn nested(&mut self, name: &str, lines: &[(Location, String)]) -> Result<(), AssemblyError> {
+        self.stack.push(name.to_string());
+        let result = self.lines(lines);
+        self.stack.pop();
+        result
+    }
+
+    /// Value of the expression `text` with the symbols defined so far
+    fn evaluate(&self, text: &str) -> Result<i64, ErrorKind> {
+        evaluate(text, &|name| match name {
+            "$" => Some(self.address as i64),
+            _ => self.symbols.get(name).copied(),
+        })
+    }
+
+    /// Value of the only operand of `statement`
+    fn operand(&self, statement: &Statement) -> Result<i64, ErrorKind> {
+        match statement.operands.as_slice() {
+            [operand] => self.evaluate(operand),
+            _ => Err(ErrorKind::InvalidOperands(
+                statement.mnemonic.clone().unwrap_or_default(),
+            )),
+        }
+    }
+
+    /// Define symbol `name` with `value`
+    fn define(&mut self, name: &str, value: i64) -> Result<(), ErrorKind> {
+        if self.symbols.insert(name.to_string(), value).is_some() {
+            return Err(ErrorKind::DuplicateLabel(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Define the label of `statement`, if it has one, at the current address
+    fn define_label(&mut self, statement: &Statement) -> Result<(), ErrorKind> {
+        if let Some(label) = &statement.label {
+            if self.address > 0xFFFF {
+                return Err(ErrorKind::ProgramTooLarge);
+            }
+            self.define(label, self.address as i64)?;
+            self.labels.insert(label.clone(), self.address as u16);
+        }
+        Ok(())
+    }
+
+    /// Lay out `lines`, which must close every block they open
+    fn lines(&mut self, lines: &[(Location, String)]) -> Result<(), AssemblyError> {
+        let mut conditions: Vec<Condition> = Vec::new();
+        let mut definition: Option<(Location, String, Macro)> = None;
+        for (location, text) in lines {
+            let error = |kind| AssemblyError {
+                location: location.clone(),
+                kind,
+            };
+            let parsed = parse_line(text);
+            let directive = parsed
+                .as_ref()
+                .ok()
+                .and_then(|statement| statement.mnemonic.as_ref())
+                .map(|mnemonic| mnemonic.to_ascii_uppercase());
+            if let Some((_, name, body)) = &mut definition {
+                match directive.as_deref() {
+                    Some("ENDM") => {
+                        let body = body.clone();
+                        self.macros.insert(name.clone(), body);
+                        definition = None;
+                    }
+                    Some("MACRO") => return Err(error(ErrorKind::Unbalanced("MACRO".to_string()))),
+                    _ => body.body.push(text.clone()),
+                }
+                continue;
+            }
+            let active = conditions.iter().all(|condition| condition.active);
+            match (directive.as_deref(), &parsed) {
+                (Some("IF"), Ok(statement)) => {
+                    let value = active && self.operand(statement).map_err(error)? != 0;
+                    conditions.push(Condition {
+                        active: value,
+                        taken: value || !active,
+                        in_else: false,
+                        location: location.clone(),
+                    });
+                    continue;
+                }
+                (Some("ELSE"), _) => {
+                    match conditions.last_mut() {
+                        Some(condition) if !condition.in_else => {
+                            condition.active = !condition.taken;
+                            condition.taken = true;
+                            condition.in_else = true;
+                        }
+                        _ => return Err(error(ErrorKind::Unbalanced("ELSE".to_string()))),
+                    }
+                    continue;
+                }
+                (Some("ENDIF"), _) => {
+                    if conditions.pop().is_none() {
+                        return Err(error(ErrorKind::Unbalanced("ENDIF".to_string())));
+                    }
+                    continue;
+                }
+                _ if !active => continue,
+                _ => {}
+            }
+            let statement = parsed.map_err(error)?;
+            if directive.as_deref() == Some("MACRO") {
+                let name = match &statement.label {
+                    Some(name) => name.clone(),
+                    None => return Err(error(ErrorKind::Syntax(text.trim().to_string()))),
+                };
+                if let Some(parameter) = statement.operands.iter().find(|o| !is_identifier(o)) {
+                    return Err(error(ErrorKind::Syntax(parameter.clone())));
+                }
+                let body = Macro {
+                    parameters: statement.operands,
+                    body: Vec::new(),
+                };
+                definition = Some((location.clone(), name, body));
+                continue;
+            }
+            self.statement(location, statement, directive.as_deref())?;
+        }
+        if let Some(condition) = conditions.pop() {
+            return Err(AssemblyError {
+                location: condition.location,
+                kind: ErrorKind::Unbalanced("IF".to_string()),
+            });
+        }
+        if let Some((location, _, _)) = definition {
+            return Err(AssemblyError {
+                location,
+                kind: ErrorKind::Unbalanced("MACRO".to_string()),
+            });
+        }
+        Ok(())
+    }
+
+    /// Lay out a statement that isn't part of a conditional block or macro definition
+    fn statement(
+        &mut self,
+        location: &Location,
+        statement: Statement,
+        directive: Option<&str>,
+    ) -> Result<(), AssemblyError> {
+        let error = |kind| AssemblyError {
+            location: location.clone(),
+            kind,
+        };
+        if directive == Some("EQU") {
+            return self.constant(&statement).map_err(error);
+        }
+        self.define_label(&statement).map_err(error)?;
+        let mnemonic = statement.mnemonic.clone().unwrap_or_default();
+        if directive == Some("INCLUDE") {
+            let name = match statement.operands.as_slice() {
+                [name] if name.len() >= 2 && name.starts_with('"') && name.ends_with('"') => {
+                    name[1..name.len() - 1].to_string()
+                }
+                _ => return Err(error(ErrorKind::InvalidOperands(mnemonic))),
+            };
+            self.enter(&name).map_err(error)?;
+            let source = self
+                .loader
+                .load(&name)
+                .map_err(|e| error(ErrorKind::Io(e)))?;
+            return self.file(&name, &source);
+        }
+        if let Some(definition) = self.macros.get(&mnemonic).cloned() {
+            if statement.operands.len() != definition.parameters.len() {
+                return Err(error(ErrorKind::InvalidOperands(mnemonic)));
+            }
+            self.enter(&mnemonic).map_err(error)?;
+            return self.expand(location, &mnemonic, &definition, &statement.operands);
+        }
+        self.directive(location, statement, directive)
+            .map_err(error)
+    }
+
+    /// Define the `EQU` constant of `statement`
+    fn constant(&mut self, statement: &Statement) -> Result<(), ErrorKind> {
+        let name = match &statement.label {
+            Some(name) => name.clone(),
+            None => return Err(ErrorKind::Syntax("EQU".to_string())),
+        };
+        let value = self.operand(statement)?;
+        self.define(&name, value)?;
+        self.constants.insert(name, value);
+        Ok(())
+    }
+
+    /// Lay out `ORG`, `ALIGN`, `ENDM` outside of a definition, or a statement generating code
+    fn directive(
+        &mut self,
+        location: &Location,
+        statement: Statement,
+        directive: Option<&str>,
+    ) -> Result<(), ErrorKind> {
+        match directive {
+            Some("ENDM") => Err(ErrorKind::Unbalanced("ENDM".to_string())),
+            Some("ORG") => {
+                let address = self.operand(&statement)?;
+                if !(0..=0x10000).contains(&address) {
+                    return Err(ErrorKind::OutOfRange(address));
+                }
+                self.address = address as usize;
+                Ok(())
+            }
+            Some("ALIGN") => {
+                let alignment = self.operand(&statement)?;
+                if alignment <= 0 {
+                    return Err(ErrorKind::OutOfRange(alignment));
+                }
+                let alignment = alignment as usize;
+                self.address = self.address.div_ceil(alignment) * alignment;
+                self.check_size()
+            }
+            _ => {
+                let size = size(&statement)?;
+                if size > 0 {
+                    self.items.push(Item {
+                        location: location.clone(),
+                        address: self.address,
+                        statement,
+                    });
+                }
+                self.address += size;
+                self.check_size()
+            }
+        }
+    }
+
+    /// Check that `name` can be entered without recursing too deep or into itself
+    fn enter(&self, name: &str) -> Result<(), ErrorKind> {
+        if self.stack.len() >= MAX_DEPTH || self.stack.iter().any(|entered| entered == name) {
+            return Err(ErrorKind::Recursive(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Check that the program still fits the address space
+    fn check_size(&self) -> Result<(), ErrorKind> {
+        if self.address > 0x10000 {
+            Err(ErrorKind::ProgramTooLarge)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Lay out the body of macro `name` with `arguments`, invoked at `location`.
+    ///
+    /// The lines of the body are attributed to the invocation, so errors point to it.
+    fn expand(
+        &mut self,
+        location: &Location,
+        name: &str,
+        definition: &Macro,
+        arguments: &[String],
+    ) -> Result<(), AssemblyError> {
+        self.expansions += 1;
+        let body: Vec<(Location, String)> = definition
+            .body
+            .iter()
+            .map(|text| {
+                let text = substitute(text, &definition.parameters, arguments, self.expansions);
+                (location.clone(), text)
+            })
+            .collect();
+        self.nested(name, &body)
+    }
+}
@@ -0,0 +1,544 @@
+use crate::assembler::error::{AssemblyError, ErrorKind, Location};
+use crate::assembler::expression::evaluate;
+use crate::assembler::instruction::{instruction, Operand};
+use crate::assembler::layout::{FileLoader, Layout, Loader};
+use crate::assembler::parser::Statement;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Contains assembly errors
+pub mod error;
+/// Contains constant expressions
+pub mod expression;
+/// Contains instruction mnemonics
+pub mod instruction;
+/// Contains the first pass: includes, macros and conditional assembly
+pub mod layout;
+/// Contains source line parsing
+pub mod parser;
+
+/// Assembled program
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Program {
+    /// ROM bytes, to be loaded at the load address
+    pub rom: Vec<u8>,
+    /// Addresses of the labels
+    pub labels: BTreeMap<String, u16>,
+    /// Values of the `EQU` constants
+    pub constants: BTreeMap<String, i64>,
+}
+
+/// Two-pass assembler for the Cowgod mnemonics printed by the disassembler.
+///
+/// Source consists of lines of the form `[label:] [mnemonic [operand, ...]] [; comment]`.
+/// Mnemonics, register names and keywords are case-insensitive, labels are not.
+/// Numbers are decimal, `0x` hexadecimal or `0b` binary, and labels may be used before
+/// they are defined.
+/// Operands are expressions over numbers, labels, constants and `$`, the address of the
+/// current statement, see [`expression::evaluate`].
+///
+/// Directives:
+/// - `DB` and `DW` emit their operands as bytes and big-endian words
+/// - `name: EQU expression` defines a constant, which must be computable where it's defined
+/// - `ORG address` continues at `address`, and `ALIGN n` at the next multiple of `n`,
+///   gaps are filled with zeros
+/// - `INCLUDE "file"` assembles another source file in place
+/// - `IF expression`, `ELSE` and `ENDIF` assemble lines only if the expression isn't zero
+/// - `name: MACRO parameter, ...` up to `ENDM` defines a macro, invoked as `name argument, ...`,
+///   in its body the parameters are replaced by the arguments and `\@` by a number unique to
+///   each expansion, for local labels
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Assembler {
+    /// Address the ROM is loaded at, which labels are relative to
+    pub load_address: u16,
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new(0x200)
+    }
+}
+
+/// Error at `location`
+fn error_at(location: &Location, kind: ErrorKind) -> AssemblyError {
+    AssemblyError {
+        location: location.clone(),
+        kind,
+    }
+}
+
+/// Size in bytes of the code generated by `statement`
+fn size(statement: &Statement) -> Result<usize, ErrorKind> {
+    let mnemonic = match &statement.mnemonic {
+        Some(mnemonic) => mnemonic.to_ascii_uppercase(),
+        None => return Ok(0),
+    };
+    match mnemonic.as_str() {
+        "DB" => Ok(statement.operands.len()),
+        "DW" => Ok(statement.operands.len() * 2),
+        _ => {
+            let operands: Vec<Operand> = statement
+                .operands
+                .iter()
+                .map(|o| Operand::parse(o))
+                .collect();
+            let opcode = instruction(&mnemonic, &operands, &|_| Ok(0))?;
+            Ok(opcode.size() as usize)
+        }
+    }
+}
+
+/// Code generated by `statement`, looking up symbols with `symbol`
+fn emit(statement: &Statement, symbol: &dyn Fn(&str) -> Option<i64>) -> Result<Vec<u8>, ErrorKind> {
+    let mnemonic = match &statement.mnemonic {
+        Some(mnemonic) => mnemonic.to_ascii_uppercase(),
+        None => return Ok(Vec::new()),
+    };
+    let value = |text: &str| evaluate(text, symbol);
+    let mut bytes = Vec::new();
+    match mnemonic.as_str() {
+        "DB" => {
+            for operand in &statement.operands {
+                match value(operand)? {
+                    byte @ -128..=255 => bytes.push(byte as u8),
+                    byte => return Err(ErrorKind::OutOfRange(byte)),
+                }
+            }
+        }
+        "DW" => {
+            for operand in &statement.operands {
+                match value(operand)? {
+                    word @ -32768..=65535 => bytes.extend_from_slice(&(word as u16).to_be_bytes()),
+                    word => return Err(ErrorKind::OutOfRange(word)),
+                }
+            }
+        }
+        _ => {
+            let operands: Vec<Operand> = statement
+                .operands
+                .iter()
+                .map(|o| Operand::parse(o))
+                .collect();
+            let opcode = instruction(&mnemonic, &operands, &value)?;
+            let encoded = opcode.encode().map_err(ErrorKind::InvalidOperand)?;
+            bytes.extend_from_slice(encoded.as_slice());
+        }
+    }
+    Ok(bytes)
+}
+
+impl Assembler {
+    /// Make an assembler for ROMs loaded at `load_address`
+    pub fn new(load_address: u16) -> Self {
+        Self { load_address }
+    }
+
+    /// Assemble `source`, naming it `file` in errors, without includes
+    pub fn assemble(&self, file: &str, source: &str) -> Result<Program, AssemblyError> {
+        self.assemble_with(file, source, &BTreeMap::new())
+    }
+
+    /// Assemble `source`, naming it `file` in errors and reading includes with `loader`.
+    ///
+    /// The first pass lays out the statements and finds the addresses of the labels,
+    /// the second one generates the code.
+    pub fn assemble_with(
+        &self,
+        file: &str,
+        source: &str,
+        loader: &dyn Loader,
+    ) -> Result<Program, AssemblyError> {
+        let load_address = self.load_address as usize;
+        let mut layout = Layout::new(loader, load_address);
+        layout.file(file, source)?;
+
+        let mut rom = Vec::new();
+        let mut written = Vec::new();
+        for item in &layout.items {
+            let symbol = |name: &str| match name {
+                "$" => Some(item.address as i64),
+                _ => layout.symbols.get(name).copied(),
+            };
+            let bytes =
+                emit(&item.statement, &symbol).map_err(|kind| error_at(&item.location, kind))?;
+            if item.address < load_address {
+                return Err(error_at(
+                    &item.location,
+                    ErrorKind::OutOfRange(item.address as i64),
+                ));
+            }
+            let offset = item.address - load_address;
+            let end = offset + bytes.len();
+            if rom.len() < end {
+                rom.resize(end, 0);
+                written.resize(end, false);
+            }
+            if let Some(overlap) = written[offset..end].iter().position(|&w| w) {
+                let address = (item.address + overlap) as u16;
+                return Err(error_at(&item.location, ErrorKind::Overlap(address)));
+            }
+            rom[offset..end].copy_from_slice(&bytes);
+            written[offset..end].iter_mut().for_each(|w| *w = true);
+        }
+        Ok(Program {
+            rom,
+            labels: layout.labels,
+            constants: layout.constants,
+        })
+    }
+
+    /// Read and assemble the source file at `path`, with includes relative to its directory
+    pub fn assemble_file(&self, path: &Path) -> Result<Program, AssemblyError> {
+        let file = path.display().to_string();
+        let source = fs::read_to_string(path).map_err(|error| AssemblyError {
+            location: Location {
+                file: file.clone(),
+                line: 0,
+            },
+            kind: ErrorKind::Io(error.to_string()),
+        })?;
+        let loader = FileLoader {
+            directory: path.parent().unwrap_or_else(|| Path::new("")).to_path_buf(),
+        };
+        self.assemble_with(&file, &source, &loader)
+    }
+}
+
+/// Assemble `source` for a ROM loaded at `0x200`
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblyError> {
+    Assembler::default()
+        .assemble("<source>", source)
+        .map(|program| program.rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::error::{AssemblyError, ErrorKind, Location};
+    use crate::assembler::{assemble, Assembler};
+    use crate::disassembler::{format_opcode, Syntax};
+    use crate::emulator::opcode::OpCode;
+    use crate::emulator::Emulator;
+    use std::collections::BTreeMap;
+    use std::convert::TryFrom;
+
+    /// Error of `kind` at `line` of the default source
+    fn error(line: usize, kind: ErrorKind) -> AssemblyError {
+        error_in("<source>", line, kind)
+    }
+
+    /// Error of `kind` at `line` of `file`
+    fn error_in(file: &str, line: usize, kind: ErrorKind) -> AssemblyError {
+        AssemblyError {
+            location: Location {
+                file: file.to_string(),
+                line,
+            },
+            kind,
+        }
+    }
+
+    /// Files `main.asm`, including `sprites.asm`, which includes `macros.asm`
+    fn files() -> BTreeMap<String, String> {
+        let main = "
+            start: LD I, face
+                   DRW V0, V1, FACE_HEIGHT
+                   INCLUDE \"sprites.asm\"
+            ";
+        let sprites = "
+            INCLUDE \"macros.asm\"
+                   ORG 0x300
+            face:  DB 0x3C, 0x42
+            face_end:
+            FACE_HEIGHT: EQU face_end - face
+            ";
+        let mut files = BTreeMap::new();
+        files.insert("main.asm".to_string(), main.to_string());
+        files.insert("sprites.asm".to_string(), sprites.to_string());
+        files.insert("macros.asm".to_string(), "; nothing yet".to_string());
+        files
+    }
+
+    /// Test assembling a program with labels and forward references
+    #[test]
+    fn test_assemble() {
+        let source = "
+            ; Count V0 up to 10, storing it at `counter`
+            start:  LD V0, 0
+            loop:   ADD V0, 0x01
+                    SE V0, 10
+                    JP loop
+                    LD I, counter
+                    LD [I], V0
+            end:    JP end
+            counter: DB 0b0
+        ";
+        let rom = assemble(source).unwrap();
+        assert_eq!(
+            rom,
+            vec![
+                0x60, 0x00, 0x70, 0x01, 0x30, 0x0A, 0x12, 0x02, 0xA2, 0x0E, 0xF0, 0x55, 0x12, 0x0C,
+                0x00
+            ]
+        );
+        let mut e = Emulator::default();
+        e.load_rom(&rom).unwrap();
+        e.run_cycles(100).unwrap();
+        assert_eq!(e.memory[0x20E], 10);
+    }
+
+    /// Test that the labels are reported and relative to the load address
+    #[test]
+    fn test_labels() {
+        let program = Assembler::new(0x600)
+            .assemble(
+                "test.asm",
+                "CLS\nmain: JP main\nLD I, LONG data\ndata: DW 0x1234, -1",
+            )
+            .unwrap();
+        assert_eq!(program.labels["main"], 0x602);
+        assert_eq!(program.labels["data"], 0x608);
+        assert_eq!(
+            program.rom,
+            vec![0x00, 0xE0, 0x16, 0x02, 0xF0, 0x00, 0x06, 0x08, 0x12, 0x34, 0xFF, 0xFF]
+        );
+    }
+
+    /// Test `EQU`, `ORG`, `ALIGN` and expressions
+    #[test]
+    fn test_directives() {
+        let program = Assembler::default()
+            .assemble(
+                "test.asm",
+                "
+                WIDTH:  EQU 64
+                HALF:   EQU WIDTH / 2
+                start:  LD V0, HALF - 1
+                        LD I, table + 2 * 2
+                        ALIGN 8
+                table:  DW start, $, (table - start) << 4
+                        ORG 0x220
+                        JP start | 0x10
+                ",
+            )
+            .unwrap();
+        assert_eq!(program.constants["WIDTH"], 64);
+        assert_eq!(program.constants["HALF"], 32);
+        assert_eq!(program.labels["table"], 0x208);
+        let mut expected = vec![0x60, 0x1F, 0xA2, 0x0C, 0, 0, 0, 0];
+        expected.extend_from_slice(&[0x02, 0x00, 0x02, 0x08, 0x00, 0x80]);
+        expected.resize(0x20, 0);
+        expected.extend_from_slice(&[0x12, 0x10]);
+        assert_eq!(program.rom, expected);
+    }
+
+    /// Test macros with parameters and local labels
+    #[test]
+    fn test_macros() {
+        let source = "
+            wait: MACRO register, ticks
+                  LD register, ticks
+                  LD DT, register
+            w\\@:   LD register, DT
+                  SE register, 0
+                  JP w\\@
+                  ENDM
+                  wait V1, 2
+                  wait V2, 3 + 1
+            ";
+        assert_eq!(
+            assemble(source),
+            Ok(vec![
+                0x61, 0x02, 0xF1, 0x15, 0xF1, 0x07, 0x31, 0x00, 0x12, 0x04, 0x62, 0x04, 0xF2, 0x15,
+                0xF2, 0x07, 0x32, 0x00, 0x12, 0x0E
+            ])
+        );
+    }
+
+    /// Test conditional assembly, including nested blocks
+    #[test]
+    fn test_conditional() {
+        let source = "
+            DEBUG: EQU 0
+            IF DEBUG
+                CLS
+                IF 1
+                    UNKNOWN
+                ENDIF
+            ELSE
+                IF DEBUG | 1
+                    RET
+                ELSE
+                    UNKNOWN
+                ENDIF
+            ENDIF
+            ";
+        assert_eq!(assemble(source), Ok(vec![0x00, 0xEE]));
+        assert_eq!(
+            assemble(&source.replace("EQU 0", "EQU 1")),
+            Err(error(6, ErrorKind::UnknownMnemonic("UNKNOWN".to_string())))
+        );
+    }
+
+    /// Test including files, with errors pointing into them
+    #[test]
+    fn test_include() {
+        let mut files = files();
+        let assembler = Assembler::default();
+        let program = assembler
+            .assemble_with("main.asm", &files["main.asm"], &files)
+            .unwrap();
+        assert_eq!(program.labels["start"], 0x200);
+        assert_eq!(program.constants["FACE_HEIGHT"], 2);
+        let mut expected = vec![0xA3, 0x00, 0xD0, 0x12];
+        expected.resize(0x100, 0);
+        expected.extend_from_slice(&[0x3C, 0x42]);
+        assert_eq!(program.rom, expected);
+
+        files.insert("macros.asm".to_string(), "\n  JP nowhere".to_string());
+        assert_eq!(
+            assembler.assemble_with("main.asm", &files["main.asm"], &files),
+            Err(error_in(
+                "macros.asm",
+                2,
+                ErrorKind::UndefinedLabel("nowhere".to_string())
+            ))
+        );
+        files.insert(
+            "macros.asm".to_string(),
+            "INCLUDE \"sprites.asm\"".to_string(),
+        );
+        assert_eq!(
+            assembler.assemble_with("main.asm", &files["main.asm"], &files),
+            Err(error_in(
+                "macros.asm",
+                1,
+                ErrorKind::Recursive("sprites.asm".to_string())
+            ))
+        );
+        files.remove("macros.asm");
+        assert_eq!(
+            assembler.assemble_with("main.asm", &files["main.asm"], &files),
+            Err(error_in(
+                "sprites.asm",
+                2,
+                ErrorKind::Io("no file named `macros.asm`".to_string())
+            ))
+        );
+    }
+
+    /// Test errors in directives
+    #[test]
+    fn test_directive_errors() {
+        assert_eq!(
+            assemble("CLS\nIF 1\nCLS"),
+            Err(error(2, ErrorKind::Unbalanced("IF".to_string())))
+        );
+        assert_eq!(
+            assemble("ELSE"),
+            Err(error(1, ErrorKind::Unbalanced("ELSE".to_string())))
+        );
+        assert_eq!(
+            assemble("m: MACRO\nCLS"),
+            Err(error(1, ErrorKind::Unbalanced("MACRO".to_string())))
+        );
+        assert_eq!(
+            assemble("m: MACRO\nm\nENDM\nm"),
+            Err(error(4, ErrorKind::Recursive("m".to_string())))
+        );
+        assert_eq!(
+            assemble("set: MACRO value\nLD V0, value\nENDM\nCLS\nset 0x100"),
+            Err(error(5, ErrorKind::OutOfRange(0x100)))
+        );
+        assert_eq!(
+            assemble("twice: MACRO\nhere: CLS\nENDM\ntwice\ntwice"),
+            Err(error(5, ErrorKind::DuplicateLabel("here".to_string())))
+        );
+        assert_eq!(
+            assemble("m: MACRO a\nENDM\nm"),
+            Err(error(3, ErrorKind::InvalidOperands("m".to_string())))
+        );
+        assert_eq!(
+            assemble("X: EQU later\nlater: CLS"),
+            Err(error(1, ErrorKind::UndefinedLabel("later".to_string())))
+        );
+        assert_eq!(
+            assemble("CLS\nCLS\nORG 0x202\nRET"),
+            Err(error(4, ErrorKind::Overlap(0x202)))
+        );
+        assert_eq!(
+            assemble("ORG 0x100\nCLS"),
+            Err(error(2, ErrorKind::OutOfRange(0x100)))
+        );
+        assert_eq!(assemble("ALIGN 0"), Err(error(1, ErrorKind::OutOfRange(0))));
+    }
+
+    /// Test that the disassembler's output assembles back into every opcode
+    #[test]
+    fn test_disassembly_round_trip() {
+        for code in 0..=0xFFFFu16 {
+            let bytes = [(code >> 8) as u8, code as u8];
+            let opcode = match OpCode::try_from((bytes[0], bytes[1])) {
+                Ok(opcode) => opcode,
+                Err(_) => continue,
+            };
+            let source = format_opcode(opcode, Syntax::Cowgod);
+            assert_eq!(
+                assemble(&source),
+                Ok(opcode.encode().unwrap().as_slice().to_vec()),
+                "{}",
+                source
+            );
+        }
+        let long = OpCode::MemLong { target: 0xABCD };
+        assert_eq!(
+            assemble(&format_opcode(long, Syntax::Cowgod)),
+            Ok(vec![0xF0, 0x00, 0xAB, 0xCD])
+        );
+    }
+
+    /// Test that errors point to their line
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            assemble("CLS\nMOV V0, V1"),
+            Err(error(2, ErrorKind::UnknownMnemonic("MOV".to_string())))
+        );
+        assert_eq!(
+            assemble("CLS\n\nJP nowhere"),
+            Err(error(3, ErrorKind::UndefinedLabel("nowhere".to_string())))
+        );
+        assert_eq!(
+            assemble("a: CLS\na: RET"),
+            Err(error(2, ErrorKind::DuplicateLabel("a".to_string())))
+        );
+        assert_eq!(
+            assemble("DB 256"),
+            Err(error(1, ErrorKind::OutOfRange(256)))
+        );
+        assert_eq!(
+            assemble("LD V0, 0x"),
+            Err(error(1, ErrorKind::InvalidNumber("0x".to_string())))
+        );
+        for operator in ["/", "%"].iter() {
+            assert_eq!(
+                assemble(&format!("LD V0, (1 << 63) {} -1", operator)),
+                Err(error(1, ErrorKind::OutOfRange(i64::MIN)))
+            );
+        }
+        assert_eq!(
+            assemble("SYS 0x0E0").unwrap_err().to_string(),
+            "<source>:1: operand `target` out of range in _NativeCall { target: 224 }"
+        );
+        assert_eq!(
+            Assembler::new(0xFFFE).assemble("big.asm", "CLS\nCLS"),
+            Err(AssemblyError {
+                location: Location {
+                    file: "big.asm".to_string(),
+                    line: 2
+                },
+                kind: ErrorKind::ProgramTooLarge
+            })
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use crate::assembler::error::ErrorKind;
+
+/// Contents of a source line
+#[derive(Eq, PartialEq, Clone, Debug, Default)]
+pub struct Statement {
+    /// Label defined at the start of the line
+    pub label: Option<String>,
+    /// Mnemonic or directive, as written
+    pub mnemonic: Option<String>,
+    /// Comma separated operands, trimmed
+    pub operands: Vec<String>,
+}
+
+/// Check if `text` can name a label: a letter or `_` followed by letters, digits, `_` and `.`
+pub fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Parse a source line of the form `[label:] [mnemonic [operand, ...]] [; comment]`.
+/// Semicolons and colons inside double quotes are part of the operands.
+pub fn parse_line(text: &str) -> Result<Statement, ErrorKind> {
+    let mut quoted = false;
+    let comment = text.find(|c| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        c == ';' && !quoted
+    });
+    let code = match comment {
+        Some(comment) => &text[..comment],
+        None => text,
+    };
+    let mut rest = code.trim();
+    let mut statement = Statement::default();
+    if let Some(colon) = rest.find(':') {
+        let label = rest[..colon].trim();
+        if !label.contains(|c: char| c.is_whitespace() || c == '"') {
+            if !is_identifier(label) {
+                return Err(ErrorKind::Syntax(code.trim().to_string()));
+            }
+            statement.label = Some(label.to_string());
+            rest = rest[colon + 1..].trim();
+        }
+    }
+    if rest.is_empty() {
+        return Ok(statement);
+    }
+    let (mnemonic, operands) = match rest.find(char::is_whitespace) {
+        Some(space) => (&rest[..space], rest[space..].trim()),
+        None => (rest, ""),
+    };
+    statement.mnemonic = Some(mnemonic.to_string());
+    if !operands.is_empty() {
+        for operand in operands.split(',') {
+            let operand = operand.trim();
+            if operand.is_empty() {
+                return Err(ErrorKind::Syntax(code.trim().to_string()));
+            }
+            statement.operands.push(operand.to_string());
+        }
+    }
+    Ok(statement)
+}
+
+/// Parse a decimal, `0x` hexadecimal or `0b` binary literal, optionally negative
+pub fn parse_number(text: &str) -> Result<i64, ErrorKind> {
+    let invalid = || ErrorKind::InvalidNumber(text.to_string());
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let lower = digits.to_ascii_lowercase();
+    let (radix, body) = if let Some(hex) = lower.strip_prefix("0x") {
+        (16, hex)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        (2, binary)
+    } else {
+        (10, lower.as_str())
+    };
+    if body.is_empty() || !body.chars().all(|c| c.is_digit(radix)) {
+        return Err(invalid());
+    }
+    let value = i64::from_str_radix(body, radix).map_err(|_| invalid())?;
+    Ok(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::error::ErrorKind;
+    use crate::assembler::parser::{parse_line, parse_number, Statement};
+
+    /// Test splitting lines into labels, mnemonics and operands
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(parse_line("  ; comment"), Ok(Statement::default()));
+        assert_eq!(
+            parse_line("loop: ADD V0, 0x01 ; count"),
+            Ok(Statement {
+                label: Some("loop".to_string()),
+                mnemonic: Some("ADD".to_string()),
+                operands: vec!["V0".to_string(), "0x01".to_string()],
+            })
+        );
+        assert_eq!(
+            parse_line("end:"),
+            Ok(Statement {
+                label: Some("end".to_string()),
+                ..Statement::default()
+            })
+        );
+        assert_eq!(
+            parse_line("LD I, LONG sprite"),
+            Ok(Statement {
+                label: None,
+                mnemonic: Some("LD".to_string()),
+                operands: vec!["I".to_string(), "LONG sprite".to_string()],
+            })
+        );
+        assert_eq!(
+            parse_line("1abel: CLS"),
+            Err(ErrorKind::Syntax("1abel: CLS".to_string()))
+        );
+        assert_eq!(
+            parse_line("INCLUDE \"a:b;c.asm\" ; comment"),
+            Ok(Statement {
+                label: None,
+                mnemonic: Some("INCLUDE".to_string()),
+                operands: vec!["\"a:b;c.asm\"".to_string()],
+            })
+        );
+        assert_eq!(
+            parse_line("DB 1,,2"),
+            Err(ErrorKind::Syntax("DB 1,,2".to_string()))
+        );
+    }
+
+    /// Test parsing literals
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("42"), Ok(42));
+        assert_eq!(parse_number("0x2A"), Ok(42));
+        assert_eq!(parse_number("0b101010"), Ok(42));
+        assert_eq!(parse_number("-1"), Ok(-1));
+        assert_eq!(
+            parse_number("0xG"),
+            Err(ErrorKind::InvalidNumber("0xG".to_string()))
+        );
+        assert_eq!(
+            parse_number("--1"),
+            Err(ErrorKind::InvalidNumber("--1".to_string()))
+        );
+    }
+}
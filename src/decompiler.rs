@@ -0,0 +1,821 @@
+use crate::analysis::{analyze, ControlFlowGraph};
+use crate::emulator::opcode::OpCode::{self, *};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Condition under which a skip instruction skips
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Condition {
+    /// `vX == NN`, or `vX != NN` if `equal` is false
+    Constant {
+        register: u8,
+        constant: u8,
+        equal: bool,
+    },
+    /// `vX == vY`, or `vX != vY` if `equal` is false
+    Register {
+        register_x: u8,
+        register_y: u8,
+        equal: bool,
+    },
+    /// Key `vX` is pressed, or isn't if `pressed` is false
+    Key { register: u8, pressed: bool },
+}
+
+impl Condition {
+    /// Condition of a skip instruction, `None` for other opcodes
+    pub fn of_skip(opcode: OpCode) -> Option<Condition> {
+        let condition = match opcode {
+            SkipNextIfRegEqualToConst { register, constant } => Condition::Constant {
+                register,
+                constant,
+                equal: true,
+            },
+            SkipNextIfRegNotEqualToConst { register, constant } => Condition::Constant {
+                register,
+                constant,
+                equal: false,
+            },
+            SkipNextIfRegEqualToReg {
+                register_x,
+                register_y,
+            } => Condition::Register {
+                register_x,
+                register_y,
+                equal: true,
+            },
+            SkipNextIfRegNotEqualToReg {
+                register_x,
+                register_y,
+            } => Condition::Register {
+                register_x,
+                register_y,
+                equal: false,
+            },
+            SkipNextIfRegKeyPressed { register } => Condition::Key {
+                register,
+                pressed: true,
+            },
+            SkipNextIfRegKeyNotPressed { register } => Condition::Key {
+                register,
+                pressed: false,
+            },
+            _ => return None,
+        };
+        Some(condition)
+    }
+
+    /// The opposite condition
+    pub fn negate(self) -> Condition {
+        match self {
+            Condition::Constant {
+                register,
+                constant,
+                equal,
+            } => Condition::Constant {
+                register,
+                constant,
+                equal: !equal,
+            },
+            Condition::Register {
+                register_x,
+                register_y,
+                equal,
+            } => Condition::Register {
+                register_x,
+                register_y,
+                equal: !equal,
+            },
+            Condition::Key { register, pressed } => Condition::Key {
+                register,
+                pressed: !pressed,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    /// Formats the condition like `v0 == 5`, `v1 != v2` or `!key_pressed(v3)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = |equal: bool| if equal { "==" } else { "!=" };
+        match *self {
+            Condition::Constant {
+                register,
+                constant,
+                equal,
+            } => write!(f, "v{:x} {} {}", register, operator(equal), constant),
+            Condition::Register {
+                register_x,
+                register_y,
+                equal,
+            } => write!(f, "v{:x} {} v{:x}", register_x, operator(equal), register_y),
+            Condition::Key { register, pressed } => {
+                let not = if pressed { "" } else { "!" };
+                write!(f, "{}key_pressed(v{:x})", not, register)
+            }
+        }
+    }
+}
+
+/// Statement of the structured pseudo-code
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum Statement {
+    /// Assignment or call, like `v3 += 1` or `draw(v0, v1, 5)`
+    Simple(String),
+    /// Runs `then` if the condition holds, `otherwise` if it doesn't
+    If {
+        condition: Condition,
+        then: Vec<Statement>,
+        otherwise: Vec<Statement>,
+    },
+    /// Runs the body forever, until a `break` or `return`
+    Loop(Vec<Statement>),
+    /// Runs the body, then again as long as the condition holds
+    DoWhile {
+        body: Vec<Statement>,
+        condition: Condition,
+    },
+    /// Leaves the innermost loop
+    Break,
+    /// Starts the next iteration of the innermost loop
+    Continue,
+    /// Returns from the function
+    Return,
+    /// Jump that doesn't fit the structure, to the label at the address
+    Goto(u16),
+    /// Target of a `Goto`
+    Label(u16),
+}
+
+impl Statement {
+    /// Write the statement at `indent` levels of indentation
+    fn write(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let pad = "    ".repeat(indent);
+        let block = |f: &mut fmt::Formatter<'_>, body: &[Statement]| -> fmt::Result {
+            body.iter()
+                .try_for_each(|statement| statement.write(f, indent + 1))
+        };
+        match self {
+            Statement::Simple(text) => writeln!(f, "{}{}", pad, text),
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                writeln!(f, "{}if {} {{", pad, condition)?;
+                block(f, then)?;
+                if !otherwise.is_empty() {
+                    writeln!(f, "{}}} else {{", pad)?;
+                    block(f, otherwise)?;
+                }
+                writeln!(f, "{}}}", pad)
+            }
+            Statement::Loop(body) => {
+                writeln!(f, "{}loop {{", pad)?;
+                block(f, body)?;
+                writeln!(f, "{}}}", pad)
+            }
+            Statement::DoWhile { body, condition } => {
+                writeln!(f, "{}do {{", pad)?;
+                block(f, body)?;
+                writeln!(f, "{}}} while {}", pad, condition)
+            }
+            Statement::Break => writeln!(f, "{}break", pad),
+            Statement::Continue => writeln!(f, "{}continue", pad),
+            Statement::Return => writeln!(f, "{}return", pad),
+            Statement::Goto(target) => writeln!(f, "{}goto label_{:03X}", pad, target),
+            Statement::Label(address) => writeln!(f, "{}label_{:03X}:", pad, address),
+        }
+    }
+}
+
+/// Decompiled function
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Function {
+    /// Address of the first instruction
+    pub entry: u16,
+    /// Name, `main` for the entry point and `sub_NNN` for subroutines
+    pub name: String,
+    /// Statements of the function
+    pub body: Vec<Statement>,
+}
+
+impl fmt::Display for Function {
+    /// Formats the function like `fn sub_20C() { ... }`, one statement per line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "fn {}() {{", self.name)?;
+        for statement in &self.body {
+            statement.write(f, 1)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// Pseudo-code of a whole ROM
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct PseudoCode {
+    /// Functions in entry address order
+    pub functions: Vec<Function>,
+}
+
+impl fmt::Display for PseudoCode {
+    /// Formats the functions separated by blank lines
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
+
+/// Loop being structured
+struct Context {
+    /// Address of the first instruction
+    header: u16,
+    /// Addresses a jump to means `continue`
+    continues: Vec<u16>,
+    /// Address right after the loop, a jump to it means `break`
+    exit: u16,
+}
+
+/// Turns the instructions of a function into structured statements
+struct Structurer<'a> {
+    cfg: &'a ControlFlowGraph,
+    /// Instructions of the function
+    code: BTreeMap<u16, OpCode>,
+    /// Targets of the function's jumps, which can't be folded into a structure
+    targets: BTreeSet<u16>,
+    /// Addresses that need a label
+    labels: BTreeSet<u16>,
+    /// Targets of the emitted `Goto`s
+    gotos: BTreeSet<u16>,
+    /// Addresses a label was emitted at
+    placed: BTreeSet<u16>,
+    /// Instructions turned into statements
+    emitted: BTreeSet<u16>,
+    loops: Vec<Context>,
+}
+
+/// Name of register `register`, like `v3`
+fn v(register: u8) -> String {
+    format!("v{:x}", register)
+}
+
+impl<'a> Structurer<'a> {
+    /// Make a structurer for the function at `entry`
+    fn new(cfg: &'a ControlFlowGraph, entry: u16) -> Self {
+        let code: BTreeMap<u16, OpCode> = cfg.functions[&entry]
+            .blocks
+            .iter()
+            .flat_map(|start| cfg.blocks[start].instructions.iter().copied())
+            .collect();
+        let targets = code
+            .values()
+            .filter_map(|opcode| match opcode {
+                Goto { target } => Some(*target),
+                _ => None,
+            })
+            .collect();
+        Self {
+            cfg,
+            code,
+            targets,
+            labels: BTreeSet::new(),
+            gotos: BTreeSet::new(),
+            placed: BTreeSet::new(),
+            emitted: BTreeSet::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    /// Statements of the function starting at `entry`, with labels for its `goto`s.
+    ///
+    /// Each pass labels the targets of the previous one's `goto`s. A `goto` that ends up
+    /// in the middle of a structure keeps it from being built in the next pass.
+    fn statements(&mut self, entry: u16) -> Vec<Statement> {
+        loop {
+            self.gotos.clear();
+            self.placed.clear();
+            self.emitted.clear();
+            let body = self.function(entry);
+            let unplaced: Vec<u16> = self.gotos.difference(&self.placed).copied().collect();
+            if unplaced.is_empty() && self.labels == self.gotos {
+                return body;
+            }
+            self.targets.extend(unplaced);
+            self.labels = self.gotos.clone();
+        }
+    }
+
+    /// Statements of the whole function starting at `entry`.
+    ///
+    /// Code that isn't reached by falling through from the entry, like code placed before it,
+    /// follows the rest under its label.
+    fn function(&mut self, entry: u16) -> Vec<Statement> {
+        let end = match self.code.iter().next_back() {
+            Some((address, opcode)) => address + opcode.size(),
+            None => return Vec::new(),
+        };
+        let mut body = self.region(entry, end);
+        while let Some(&start) = self.code.keys().find(|a| !self.emitted.contains(a)) {
+            let stop = self.emitted.range(start..).next().copied().unwrap_or(end);
+            body.extend(self.region(start, stop));
+        }
+        if body.last() == Some(&Statement::Return) {
+            body.pop();
+        }
+        body
+    }
+
+    /// Address of the last jump back to `header` from before `end`, if `header` starts a loop
+    fn back_edge(&self, header: u16, end: u16) -> Option<u16> {
+        self.code
+            .range(header..end)
+            .filter(|(_, opcode)| **opcode == Goto { target: header })
+            .map(|(&address, _)| address)
+            .next_back()
+    }
+
+    /// Statement for a jump to `target`: `break`, `continue`, `goto`,
+    /// or a plain jump if it leaves the function
+    fn jump(&mut self, target: u16) -> Statement {
+        if let Some(context) = self.loops.last() {
+            if context.continues.contains(&target) {
+                return Statement::Continue;
+            }
+            if context.exit == target {
+                return Statement::Break;
+            }
+        }
+        if !self.code.contains_key(&target) {
+            return Statement::Simple(format!("jump(0x{:03X})", target));
+        }
+        self.gotos.insert(target);
+        Statement::Goto(target)
+    }
+
+    /// Statements of the instructions from `start` up to `end`
+    fn region(&mut self, start: u16, end: u16) -> Vec<Statement> {
+        let mut body = Vec::new();
+        let mut address = start;
+        while address < end {
+            let (at, opcode) = match self.code.range(address..end).next() {
+                Some((&at, &opcode)) => (at, opcode),
+                None => break,
+            };
+            address = at;
+            // The header of a loop being structured starts its body, its label goes before the loop
+            let in_loop = self.loops.iter().any(|context| context.header == address);
+            if self.labels.contains(&address) && !in_loop {
+                self.placed.insert(address);
+                body.push(Statement::Label(address));
+            }
+            if let Some(source) = self.back_edge(address, end).filter(|_| !in_loop) {
+                address = self.structure_loop(address, source, &mut body);
+                continue;
+            }
+            self.emitted.insert(address);
+            let next = address + opcode.size();
+            if let Some(condition) = Condition::of_skip(opcode) {
+                address = self.structure_skip(condition, next, end, &mut body);
+                continue;
+            }
+            match opcode {
+                Goto { target } => body.push(self.jump(target)),
+                Return => body.push(Statement::Return),
+                StoreBCD { register }
+                    if self.code.get(&next) == Some(&RegLoad { register: 2 })
+                        && !self.targets.contains(&next) =>
+                {
+                    self.emitted.insert(next);
+                    let digits = format!("v0, v1, v2 = digits({})", v(register));
+                    body.push(Statement::Simple(digits));
+                    address = next + 2;
+                    continue;
+                }
+                _ => body.push(Statement::Simple(self.simple(address, opcode))),
+            }
+            address = next;
+        }
+        // A skip at the end can leave the region
+        if address > end {
+            let jump = self.jump(address);
+            body.push(jump);
+        }
+        body
+    }
+
+    /// Structure the loop from `header` to the jump back at `source`,
+    /// returning the address after it.
+    ///
+    /// A skip right before the jump back becomes the condition of a `do ... while`,
+    /// unless other jumps lead to either of them,
+    /// and waiting for a key with an empty one becomes a call.
+    fn structure_loop(&mut self, header: u16, source: u16, body: &mut Vec<Statement>) -> u16 {
+        let exit = source + 2;
+        self.emitted.insert(source);
+        let check = source.wrapping_sub(2);
+        let condition = match self.code.get(&check) {
+            Some(&opcode)
+                if check >= header
+                    && !self.targets.contains(&source)
+                    && (check == header || !self.targets.contains(&check)) =>
+            {
+                Condition::of_skip(opcode)
+            }
+            _ => None,
+        };
+        let condition = match condition {
+            Some(condition) => condition.negate(),
+            None => {
+                self.loops.push(Context {
+                    header,
+                    continues: vec![header, source],
+                    exit,
+                });
+                let mut statements = self.region(header, source);
+                self.loops.pop();
+                // Jumps from outside of the loop to the jump back need its label
+                if self.labels.contains(&source) {
+                    self.placed.insert(source);
+                    statements.push(Statement::Label(source));
+                    statements.push(Statement::Continue);
+                }
+                body.push(Statement::Loop(statements));
+                return exit;
+            }
+        };
+        self.emitted.insert(check);
+        self.loops.push(Context {
+            header,
+            continues: vec![check],
+            exit,
+        });
+        let statements = self.region(header, check);
+        self.loops.pop();
+        let statement = match condition {
+            Condition::Key { register, pressed } if statements.is_empty() => {
+                let wait = if pressed { "released" } else { "pressed" };
+                Statement::Simple(format!("wait_key_{}({})", wait, v(register)))
+            }
+            _ => Statement::DoWhile {
+                body: statements,
+                condition,
+            },
+        };
+        body.push(statement);
+        exit
+    }
+
+    /// Structure a skip with `condition`, followed by the instruction at `next`,
+    /// returning the address to continue at.
+    ///
+    /// A skip over a forward jump within the region becomes an `if` around the code jumped
+    /// over, with an `else` if that code ends by jumping further.
+    /// Otherwise the skipped instruction runs if the condition doesn't hold.
+    fn structure_skip(
+        &mut self,
+        condition: Condition,
+        next: u16,
+        end: u16,
+        body: &mut Vec<Statement>,
+    ) -> u16 {
+        let skipped = next.saturating_add(self.code.get(&next).map_or(2, |opcode| opcode.size()));
+        let target = match self.code.get(&next) {
+            Some(&Goto { target })
+                if target > skipped && target <= end && !self.targets.contains(&next) =>
+            {
+                target
+            }
+            _ => {
+                let then = self.region(next, skipped);
+                body.push(Statement::If {
+                    condition: condition.negate(),
+                    then,
+                    otherwise: Vec::new(),
+                });
+                return skipped;
+            }
+        };
+        self.emitted.insert(next);
+        let last = self.code.range(skipped..target).next_back();
+        let (then, otherwise, after) = match last {
+            Some((&at, &Goto { target: after }))
+                if at + 2 == target
+                    && after > target
+                    && after <= end
+                    && !self.targets.contains(&at) =>
+            {
+                let then = self.region(skipped, at);
+                self.emitted.insert(at);
+                let otherwise = self.region(target, after);
+                (then, otherwise, after)
+            }
+            _ => (self.region(skipped, target), Vec::new(), target),
+        };
+        body.push(Statement::If {
+            condition,
+            then,
+            otherwise,
+        });
+        after
+    }
+
+    /// Name of the data `I` is set to at `address`: `sprite_0xNNN` if it's drawn before
+    /// `I` is used otherwise in the same block, `data_0xNNN` if not
+    fn data_name(&self, address: u16, target: u16) -> String {
+        let block = self.cfg.block_at(address);
+        let drawn = block.and_then(|block| {
+            block
+                .instructions
+                .iter()
+                .skip_while(|&&(at, _)| at <= address)
+                .find_map(|&(_, opcode)| match opcode {
+                    DisplaySprite { .. } => Some(true),
+                    Mem { .. }
+                    | MemLong { .. }
+                    | MemAddReg { .. }
+                    | MemMoveToRegChar { .. }
+                    | MemMoveToRegLargeChar { .. }
+                    | StoreBCD { .. }
+                    | RegDump { .. }
+                    | RegLoad { .. }
+                    | RegRangeDump { .. }
+                    | RegRangeLoad { .. }
+                    | LoadAudioPattern => Some(false),
+                    _ => None,
+                })
+        });
+        let prefix = if drawn == Some(true) {
+            "sprite"
+        } else {
+            "data"
+        };
+        format!("{}_0x{:03X}", prefix, target)
+    }
+
+    /// Text of a statement that doesn't affect control flow, `opcode` being at `address`
+    fn simple(&self, address: u16, opcode: OpCode) -> String {
+        let shift = |x: u8, y: u8, operator: &str| {
+            if x == y {
+                format!("{} {}= 1", v(x), operator)
+            } else {
+                format!("{} = {} {} 1", v(x), v(y), operator)
+            }
+        };
+        match opcode {
+            _NativeCall { target } => format!("native(0x{:03X})", target),
+            ClearScreen => "clear()".to_string(),
+            ScrollDown { rows } => format!("scroll_down({})", rows),
+            ScrollRight => "scroll_right()".to_string(),
+            ScrollLeft => "scroll_left()".to_string(),
+            Exit => "exit()".to_string(),
+            LowRes => "lores()".to_string(),
+            HighRes => "hires()".to_string(),
+            Subroutine { target } => format!("{}()", self.cfg.function_name(target)),
+            JumpRegZero { target } => format!("jump(0x{:03X} + v0)", target),
+            RegRangeDump {
+                register_x,
+                register_y,
+            } => format!("save({}, {})", v(register_x), v(register_y)),
+            RegRangeLoad {
+                register_x,
+                register_y,
+            } => format!("load({}, {})", v(register_x), v(register_y)),
+            RegSetConst { register, constant } => format!("{} = {}", v(register), constant),
+            RegAddConst { register, constant } => format!("{} += {}", v(register), constant),
+            RegMov {
+                register_x,
+                register_y,
+            } => format!("{} = {}", v(register_x), v(register_y)),
+            RegBitwiseOr {
+                register_x,
+                register_y,
+            } => format!("{} |= {}", v(register_x), v(register_y)),
+            RegBitwiseAnd {
+                register_x,
+                register_y,
+            } => format!("{} &= {}", v(register_x), v(register_y)),
+            RegBitwiseXor {
+                register_x,
+                register_y,
+            } => format!("{} ^= {}", v(register_x), v(register_y)),
+            RegAdd {
+                register_x,
+                register_y,
+            } => format!("{} += {}", v(register_x), v(register_y)),
+            RegSub {
+                register_x,
+                register_y,
+            } => format!("{} -= {}", v(register_x), v(register_y)),
+            RegRightShift {
+                register_x,
+                register_y,
+            } => shift(register_x, register_y, ">>"),
+            RegReverseSub {
+                register_x,
+                register_y,
+            } => format!("{} = {} - {}", v(register_x), v(register_y), v(register_x)),
+            RegLeftShift {
+                register_x,
+                register_y,
+            } => shift(register_x, register_y, "<<"),
+            Mem { target } | MemLong { target } => {
+                format!("i = {}", self.data_name(address, target))
+            }
+            RandToReg { register, constant } => {
+                format!("{} = random() & 0x{:02X}", v(register), constant)
+            }
+            DisplaySprite {
+                coord_x,
+                coord_y,
+                height,
+            } => format!("draw({}, {}, {})", v(coord_x), v(coord_y), height),
+            SelectPlanes { planes } => format!("plane({})", planes),
+            LoadAudioPattern => "audio()".to_string(),
+            SetRegToDelayTimer { register } => format!("{} = delay", v(register)),
+            SetRegToKeyPressed { register } => format!("{} = wait_key()", v(register)),
+            SetDelayTimerToReg { register } => format!("delay = {}", v(register)),
+            SetSoundTimerToReg { register } => format!("sound = {}", v(register)),
+            MemAddReg { register } => format!("i += {}", v(register)),
+            MemMoveToRegChar { register } => format!("i = font({})", v(register)),
+            MemMoveToRegLargeChar { register } => format!("i = big_font({})", v(register)),
+            SetPitchToReg { register } => format!("pitch = {}", v(register)),
+            StoreBCD { register } => format!("bcd({})", v(register)),
+            RegDump { register } => format!("save(v0, {})", v(register)),
+            RegLoad { register } => format!("load(v0, {})", v(register)),
+            StoreFlags { register } => format!("save_flags(v0, {})", v(register)),
+            LoadFlags { register } => format!("load_flags(v0, {})", v(register)),
+            // Control flow is structured by the callers
+            Return
+            | Goto { .. }
+            | SkipNextIfRegEqualToConst { .. }
+            | SkipNextIfRegNotEqualToConst { .. }
+            | SkipNextIfRegEqualToReg { .. }
+            | SkipNextIfRegNotEqualToReg { .. }
+            | SkipNextIfRegKeyPressed { .. }
+            | SkipNextIfRegKeyNotPressed { .. } => format!("{}", opcode),
+        }
+    }
+}
+
+/// Decompile every function of `cfg` into structured pseudo-code.
+///
+/// Skips over forward jumps become `if` blocks, jumps back become loops, and jumps that
+/// don't fit the structure are left as `goto`s.
+/// Recognises storing and loading the digits of a number, and loops waiting for a key.
+pub fn decompile(cfg: &ControlFlowGraph) -> PseudoCode {
+    let functions = cfg
+        .functions
+        .keys()
+        .map(|&entry| {
+            let mut structurer = Structurer::new(cfg, entry);
+            Function {
+                entry,
+                name: cfg.function_name(entry),
+                body: structurer.statements(entry),
+            }
+        })
+        .collect();
+    PseudoCode { functions }
+}
+
+/// Decompile `rom` loaded and started at `load_address`
+pub fn decompile_rom(rom: &[u8], load_address: u16) -> PseudoCode {
+    decompile(&analyze(rom, load_address, load_address))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decompiler::{decompile_rom, Condition, Statement};
+
+    /// Test structuring loops, conditions and calls, and recognising idioms
+    #[test]
+    fn test_decompile() {
+        let rom = [
+            0x00, 0xE0, // 200: CLS
+            0x60, 0x00, // 202: LD V0, 0
+            0x61, 0x00, // 204: LD V1, 0
+            0xA2, 0x2A, // 206: LD I, 0x22A
+            0xD0, 0x15, // 208: DRW V0, V1, 5
+            0x70, 0x08, // 20A: ADD V0, 8
+            0x30, 0x40, // 20C: SE V0, 64
+            0x12, 0x06, // 20E: JP 0x206
+            0x22, 0x22, // 210: CALL 0x222
+            0xE1, 0x9E, // 212: SKP V1
+            0x12, 0x12, // 214: JP 0x212
+            0x31, 0x05, // 216: SE V1, 5
+            0x12, 0x1E, // 218: JP 0x21E
+            0x62, 0x00, // 21A: LD V2, 0
+            0x12, 0x20, // 21C: JP 0x220
+            0x62, 0x01, // 21E: LD V2, 1
+            0x12, 0x20, // 220: JP 0x220
+            0xA2, 0x30, // 222: LD I, 0x230
+            0xF2, 0x33, // 224: LD B, V2
+            0xF2, 0x65, // 226: LD V2, [I]
+            0x00, 0xEE, // 228: RET
+            0xF0, 0x90, 0x90, 0x90, 0xF0, // 22A: sprite
+        ];
+        assert_eq!(
+            decompile_rom(&rom, 0x200).to_string(),
+            "\
+fn main() {
+    clear()
+    v0 = 0
+    v1 = 0
+    do {
+        i = sprite_0x22A
+        draw(v0, v1, 5)
+        v0 += 8
+    } while v0 != 64
+    sub_222()
+    wait_key_pressed(v1)
+    if v1 == 5 {
+        v2 = 0
+    } else {
+        v2 = 1
+    }
+    loop {
+    }
+}
+
+fn sub_222() {
+    i = data_0x230
+    v0, v1, v2 = digits(v2)
+}
+"
+        );
+    }
+
+    /// Test leaving loops with `break`
+    #[test]
+    fn test_break() {
+        let rom = [
+            0x70, 0x01, // 200: ADD V0, 1
+            0x40, 0x05, // 202: SNE V0, 5
+            0x12, 0x0A, // 204: JP 0x20A
+            0x61, 0x00, // 206: LD V1, 0
+            0x12, 0x00, // 208: JP 0x200
+            0x00, 0xFD, // 20A: EXIT
+        ];
+        let code = decompile_rom(&rom, 0x200);
+        assert_eq!(
+            code.functions[0].body,
+            vec![
+                Statement::Loop(vec![
+                    Statement::Simple("v0 += 1".to_string()),
+                    Statement::If {
+                        condition: Condition::Constant {
+                            register: 0,
+                            constant: 5,
+                            equal: true
+                        },
+                        then: vec![Statement::Break],
+                        otherwise: Vec::new(),
+                    },
+                    Statement::Simple("v1 = 0".to_string()),
+                ]),
+                Statement::Simple("exit()".to_string()),
+            ]
+        );
+    }
+
+    /// Test that jumps that don't fit the structure become `goto`s to labels
+    #[test]
+    fn test_goto() {
+        let rom = [
+            0x60, 0x00, // 200: LD V0, 0
+            0x12, 0x06, // 202: JP 0x206
+            0x70, 0x01, // 204: ADD V0, 1
+            0x30, 0x01, // 206: SE V0, 1
+            0x12, 0x04, // 208: JP 0x204
+            0xE3, 0xA1, // 20A: SKNP V3
+            0x00, 0xEE, // 20C: RET
+            0x00, 0xE0, // 20E: CLS
+        ];
+        assert_eq!(
+            decompile_rom(&rom, 0x200).to_string(),
+            "\
+fn main() {
+    v0 = 0
+    goto label_206
+    loop {
+        v0 += 1
+        label_206:
+        if v0 != 1 {
+            continue
+        }
+        break
+    }
+    if key_pressed(v3) {
+        return
+    }
+    clear()
+}
+"
+        );
+    }
+}
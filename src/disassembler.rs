@@ -0,0 +1,655 @@
+use crate::emulator::opcode::OpCode::{self, *};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Assembly language flavour of the mnemonics
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default)]
+pub enum Syntax {
+    /// Mnemonics of Cowgod's Chip-8 Technical Reference, like `ADD V3, 0x01`
+    #[default]
+    Cowgod,
+    /// Octo assembly language, like `v3 += 0x01`
+    Octo,
+}
+
+/// Render `opcode` as a mnemonic in `syntax`, with numeric addresses
+pub fn format_opcode(opcode: OpCode, syntax: Syntax) -> String {
+    render(opcode, syntax, &|target| format!("0x{:03X}", target))
+}
+
+/// Render `opcode` as a mnemonic in `syntax`, naming jump, call and `I` targets with `address`
+fn render(opcode: OpCode, syntax: Syntax, address: &dyn Fn(u16) -> String) -> String {
+    match syntax {
+        Syntax::Cowgod => cowgod(opcode, address),
+        Syntax::Octo => octo(opcode, address),
+    }
+}
+
+fn cowgod(opcode: OpCode, address: &dyn Fn(u16) -> String) -> String {
+    let v = |register: u8| format!("V{:X}", register);
+    match opcode {
+        _NativeCall { target } => format!("SYS 0x{:03X}", target),
+        ClearScreen => "CLS".to_string(),
+        Return => "RET".to_string(),
+        ScrollDown { rows } => format!("SCD {}", rows),
+        ScrollRight => "SCR".to_string(),
+        ScrollLeft => "SCL".to_string(),
+        Exit => "EXIT".to_string(),
+        LowRes => "LOW".to_string(),
+        HighRes => "HIGH".to_string(),
+        Goto { target } => format!("JP {}", address(target)),
+        Subroutine { target } => format!("CALL {}", address(target)),
+        SkipNextIfRegEqualToConst { register, constant } => {
+            format!("SE {}, 0x{:02X}", v(register), constant)
+        }
+        SkipNextIfRegNotEqualToConst { register, constant } => {
+            format!("SNE {}, 0x{:02X}", v(register), constant)
+        }
+        SkipNextIfRegEqualToReg {
+            register_x,
+            register_y,
+        } => format!("SE {}, {}", v(register_x), v(register_y)),
+        RegRangeDump {
+            register_x,
+            register_y,
+        } => format!("SAVE {}, {}", v(register_x), v(register_y)),
+        RegRangeLoad {
+            register_x,
+            register_y,
+        } => format!("LOAD {}, {}", v(register_x), v(register_y)),
+        RegSetConst { register, constant } => format!("LD {}, 0x{:02X}", v(register), constant),
+        RegAddConst { register, constant } => format!("ADD {}, 0x{:02X}", v(register), constant),
+        RegMov {
+            register_x,
+            register_y,
+        } => format!("LD {}, {}", v(register_x), v(register_y)),
+        RegBitwiseOr {
+            register_x,
+            register_y,
+        } => format!("OR {}, {}", v(register_x), v(register_y)),
+        RegBitwiseAnd {
+            register_x,
+            register_y,
+        } => format!("AND {}, {}", v(register_x), v(register_y)),
+        RegBitwiseXor {
+            register_x,
+            register_y,
+        } => format!("XOR {}, {}", v(register_x), v(register_y)),
+        RegAdd {
+            register_x,
+            register_y,
+        } => format!("ADD {}, {}", v(register_x), v(register_y)),
+        RegSub {
+            register_x,
+            register_y,
+        } => format!("SUB {}, {}", v(register_x), v(register_y)),
+        RegRightShift {
+            register_x,
+            register_y,
+        } => format!("SHR {}, {}", v(register_x), v(register_y)),
+        RegReverseSub {
+            register_x,
+            register_y,
+        } => format!("SUBN {}, {}", v(register_x), v(register_y)),
+        RegLeftShift {
+            register_x,
+            register_y,
+        } => format!("SHL {}, {}", v(register_x), v(register_y)),
+        SkipNextIfRegNotEqualToReg {
+            register_x,
+            register_y,
+        } => format!("SNE {}, {}", v(register_x), v(register_y)),
+        Mem { target } => format!("LD I, {}", address(target)),
+        JumpRegZero { target } => format!("JP V0, {}", address(target)),
+        RandToReg { register, constant } => format!("RND {}, 0x{:02X}", v(register), constant),
+        DisplaySprite {
+            coord_x,
+            coord_y,
+            height,
+        } => format!("DRW {}, {}, {}", v(coord_x), v(coord_y), height),
+        SkipNextIfRegKeyPressed { register } => format!("SKP {}", v(register)),
+        SkipNextIfRegKeyNotPressed { register } => format!("SKNP {}", v(register)),
+        MemLong { target } => format!("LD I, LONG 0x{:04X}", target),
+        SelectPlanes { planes } => format!("PLANE {}", planes),
+        LoadAudioPattern => "AUDIO".to_string(),
+        SetRegToDelayTimer { register } => format!("LD {}, DT", v(register)),
+        SetRegToKeyPressed { register } => format!("LD {}, K", v(register)),
+        SetDelayTimerToReg { register } => format!("LD DT, {}", v(register)),
+        SetSoundTimerToReg { register } => format!("LD ST, {}", v(register)),
+        MemAddReg { register } => format!("ADD I, {}", v(register)),
+        MemMoveToRegChar { register } => format!("LD F, {}", v(register)),
+        MemMoveToRegLargeChar { register } => format!("LD HF, {}", v(register)),
+        SetPitchToReg { register } => format!("PITCH {}", v(register)),
+        StoreBCD { register } => format!("LD B, {}", v(register)),
+        RegDump { register } => format!("LD [I], {}", v(register)),
+        RegLoad { register } => format!("LD {}, [I]", v(register)),
+        StoreFlags { register } => format!("LD R, {}", v(register)),
+        LoadFlags { register } => format!("LD {}, R", v(register)),
+    }
+}
+
+fn octo(opcode: OpCode, address: &dyn Fn(u16) -> String) -> String {
+    let v = |register: u8| format!("v{:x}", register);
+    match opcode {
+        // Octo has no mnemonic for machine code calls, so the raw bytes are emitted
+        _NativeCall { target } => format!("0x{:02X} 0x{:02X}", target >> 8, target & 0xFF),
+        ClearScreen => "clear".to_string(),
+        Return => "return".to_string(),
+        ScrollDown { rows } => format!("scroll-down {}", rows),
+        ScrollRight => "scroll-right".to_string(),
+        ScrollLeft => "scroll-left".to_string(),
+        Exit => "exit".to_string(),
+        LowRes => "lores".to_string(),
+        HighRes => "hires".to_string(),
+        Goto { target } => format!("jump {}", address(target)),
+        Subroutine { target } => format!(":call {}", address(target)),
+        // Octo conditions say when the next instruction runs, so they are the negated skips
+        SkipNextIfRegEqualToConst { register, constant } => {
+            format!("if {} != 0x{:02X} then", v(register), constant)
+        }
+        SkipNextIfRegNotEqualToConst { register, constant } => {
+            format!("if {} == 0x{:02X} then", v(register), constant)
+        }
+        SkipNextIfRegEqualToReg {
+            register_x,
+            register_y,
+        } => format!("if {} != {} then", v(register_x), v(register_y)),
+        RegRangeDump {
+            register_x,
+            register_y,
+        } => format!("save {} - {}", v(register_x), v(register_y)),
+        RegRangeLoad {
+            register_x,
+            register_y,
+        } => format!("load {} - {}", v(register_x), v(register_y)),
+        RegSetConst { register, constant } => format!("{} := 0x{:02X}", v(register), constant),
+        RegAddConst { register, constant } => format!("{} += 0x{:02X}", v(register), constant),
+        RegMov {
+            register_x,
+            register_y,
+        } => format!("{} := {}", v(register_x), v(register_y)),
+        RegBitwiseOr {
+            register_x,
+            register_y,
+        } => format!("{} |= {}", v(register_x), v(register_y)),
+        RegBitwiseAnd {
+            register_x,
+            register_y,
+        } => format!("{} &= {}", v(register_x), v(register_y)),
+        RegBitwiseXor {
+            register_x,
+            register_y,
+        } => format!("{} ^= {}", v(register_x), v(register_y)),
+        RegAdd {
+            register_x,
+            register_y,
+        } => format!("{} += {}", v(register_x), v(register_y)),
+        RegSub {
+            register_x,
+            register_y,
+        } => format!("{} -= {}", v(register_x), v(register_y)),
+        RegRightShift {
+            register_x,
+            register_y,
+        } => format!("{} >>= {}", v(register_x), v(register_y)),
+        RegReverseSub {
+            register_x,
+            register_y,
+        } => format!("{} =- {}", v(register_x), v(register_y)),
+        RegLeftShift {
+            register_x,
+            register_y,
+        } => format!("{} <<= {}", v(register_x), v(register_y)),
+        SkipNextIfRegNotEqualToReg {
+            register_x,
+            register_y,
+        } => format!("if {} == {} then", v(register_x), v(register_y)),
+        Mem { target } => format!("i := {}", address(target)),
+        JumpRegZero { target } => format!("jump0 {}", address(target)),
+        RandToReg { register, constant } => {
+            format!("{} := random 0x{:02X}", v(register), constant)
+        }
+        DisplaySprite {
+            coord_x,
+            coord_y,
+            height,
+        } => format!("sprite {} {} {}", v(coord_x), v(coord_y), height),
+        SkipNextIfRegKeyPressed { register } => format!("if {} -key then", v(register)),
+        SkipNextIfRegKeyNotPressed { register } => format!("if {} key then", v(register)),
+        MemLong { target } => format!("i := long 0x{:04X}", target),
+        SelectPlanes { planes } => format!("plane {}", planes),
+        LoadAudioPattern => "audio".to_string(),
+        SetRegToDelayTimer { register } => format!("{} := delay", v(register)),
+        SetRegToKeyPressed { register } => format!("{} := key", v(register)),
+        SetDelayTimerToReg { register } => format!("delay := {}", v(register)),
+        SetSoundTimerToReg { register } => format!("buzzer := {}", v(register)),
+        MemAddReg { register } => format!("i += {}", v(register)),
+        MemMoveToRegChar { register } => format!("i := hex {}", v(register)),
+        MemMoveToRegLargeChar { register } => format!("i := bighex {}", v(register)),
+        SetPitchToReg { register } => format!("pitch := {}", v(register)),
+        StoreBCD { register } => format!("bcd {}", v(register)),
+        RegDump { register } => format!("save {}", v(register)),
+        RegLoad { register } => format!("load {}", v(register)),
+        StoreFlags { register } => format!("saveflags {}", v(register)),
+        LoadFlags { register } => format!("loadflags {}", v(register)),
+    }
+}
+
+impl fmt::Display for OpCode {
+    /// Formats the opcode as a Cowgod-style mnemonic, like `ADD V3, 0x01`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_opcode(*self, Syntax::Cowgod))
+    }
+}
+
+/// Disassembled opcode, or bytes that don't form one
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Line {
+    /// Address of the first byte
+    pub address: u16,
+    /// Raw bytes
+    pub bytes: Vec<u8>,
+    /// Decoded opcode, `None` for data
+    pub opcode: Option<OpCode>,
+}
+
+/// Disassembly of a whole ROM
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Listing {
+    /// Syntax of the mnemonics
+    pub syntax: Syntax,
+    /// Lines in address order
+    pub lines: Vec<Line>,
+    /// Generated names of the jump, call and data targets inside the ROM, by address
+    pub labels: BTreeMap<u16, String>,
+}
+
+impl Listing {
+    /// Name of the label at `address`, if there is one
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    /// Mnemonic of `line`, with jump, call and `I` targets replaced by their labels
+    pub fn text(&self, line: &Line) -> String {
+        match line.opcode {
+            Some(opcode) => render(opcode, self.syntax, &|target| {
+                self.label(target)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("0x{:03X}", target))
+            }),
+            None => {
+                let bytes: Vec<String> =
+                    line.bytes.iter().map(|b| format!("0x{:02X}", b)).collect();
+                match self.syntax {
+                    Syntax::Cowgod => format!("DB {}", bytes.join(", ")),
+                    Syntax::Octo => bytes.join(" "),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Listing {
+    /// Formats the listing one line per opcode: address, raw bytes and mnemonic,
+    /// with label definitions on lines of their own
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            if let Some(label) = self.label(line.address) {
+                match self.syntax {
+                    Syntax::Cowgod => writeln!(f, "{}:", label)?,
+                    Syntax::Octo => writeln!(f, ": {}", label)?,
+                }
+            }
+            let bytes: Vec<String> = line.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            writeln!(
+                f,
+                "{:04X}  {:<11}  {}",
+                line.address,
+                bytes.join(" "),
+                self.text(line)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode the opcode at the start of `bytes`, returning it with its size
+fn decode(bytes: &[u8]) -> Option<(OpCode, usize)> {
+    let opcode = OpCode::try_from(bytes).ok()?;
+    Some((opcode, opcode.size() as usize))
+}
+
+/// Disassemble `rom` loaded at `load_address`, decoding it linearly from the start.
+///
+/// Targets of `Goto` and `JumpRegZero` inside the ROM get `label_NNN` labels,
+/// targets of `Subroutine` get `sub_NNN` ones.
+/// Decoding restarts at every label, so code reached by odd jumps is decoded too.
+/// Bytes that don't form an opcode become data lines.
+pub fn disassemble(rom: &[u8], load_address: u16, syntax: Syntax) -> Listing {
+    let address = |offset: usize| load_address.wrapping_add(offset as u16);
+    let end = load_address as usize + rom.len();
+    let mut labels = BTreeMap::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let size = match decode(&rom[offset..]) {
+            Some((opcode, size)) => {
+                let label = match opcode {
+                    Goto { target } | JumpRegZero { target } => Some(("label", target)),
+                    Subroutine { target } => Some(("sub", target)),
+                    _ => None,
+                };
+                if let Some((prefix, target)) = label {
+                    if (load_address as usize..end).contains(&(target as usize))
+                        && (prefix == "sub" || !labels.contains_key(&target))
+                    {
+                        labels.insert(target, format!("{}_{:03X}", prefix, target));
+                    }
+                }
+                size
+            }
+            None => 2,
+        };
+        offset += size;
+    }
+
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let (opcode, mut size) = match decode(&rom[offset..]) {
+            Some((opcode, size)) => (Some(opcode), size),
+            None => (None, 2),
+        };
+        size = size.min(rom.len() - offset);
+        if let Some(label) = (1..size).find(|i| labels.contains_key(&address(offset + i))) {
+            lines.push(Line {
+                address: address(offset),
+                bytes: rom[offset..offset + label].to_vec(),
+                opcode: None,
+            });
+            offset += label;
+            continue;
+        }
+        lines.push(Line {
+            address: address(offset),
+            bytes: rom[offset..offset + size].to_vec(),
+            opcode,
+        });
+        offset += size;
+    }
+
+    Listing {
+        syntax,
+        lines,
+        labels,
+    }
+}
+
+/// Disassemble `rom` loaded at `load_address`, decoding opcodes only at the addresses in `code`,
+/// like the instructions found by [`crate::analysis::analyze`].
+///
+/// Everything else becomes data lines of up to 8 bytes. Besides the jump and call targets,
+/// addresses inside the ROM that `I` is set to get `data_NNN` labels.
+pub fn disassemble_code(
+    rom: &[u8],
+    load_address: u16,
+    syntax: Syntax,
+    code: &BTreeSet<u16>,
+) -> Listing {
+    let address = |offset: usize| load_address.wrapping_add(offset as u16);
+    let in_rom = |target: u16| {
+        (load_address as usize..load_address as usize + rom.len()).contains(&(target as usize))
+    };
+    let opcodes: BTreeMap<u16, OpCode> = code
+        .iter()
+        .filter(|&&at| in_rom(at))
+        .filter_map(|&at| {
+            let opcode = OpCode::try_from(&rom[(at - load_address) as usize..]).ok()?;
+            Some((at, opcode))
+        })
+        .collect();
+
+    let mut labels = BTreeMap::new();
+    for prefix in ["data", "label", "sub"].iter() {
+        for opcode in opcodes.values() {
+            let label = match *opcode {
+                Mem { target } => ("data", target),
+                Goto { target } | JumpRegZero { target } => ("label", target),
+                Subroutine { target } => ("sub", target),
+                _ => continue,
+            };
+            if label.0 == *prefix && in_rom(label.1) {
+                labels.insert(label.1, format!("{}_{:03X}", prefix, label.1));
+            }
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        if let Some(&opcode) = opcodes.get(&address(offset)) {
+            let size = opcode.size() as usize;
+            lines.push(Line {
+                address: address(offset),
+                bytes: rom[offset..offset + size].to_vec(),
+                opcode: Some(opcode),
+            });
+            offset += size;
+            continue;
+        }
+        let mut size = 1;
+        while size < 8
+            && offset + size < rom.len()
+            && !opcodes.contains_key(&address(offset + size))
+            && !labels.contains_key(&address(offset + size))
+        {
+            size += 1;
+        }
+        lines.push(Line {
+            address: address(offset),
+            bytes: rom[offset..offset + size].to_vec(),
+            opcode: None,
+        });
+        offset += size;
+    }
+
+    Listing {
+        syntax,
+        lines,
+        labels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disassembler::{disassemble, disassemble_code, format_opcode, Line, Syntax};
+    use crate::emulator::opcode::OpCode::{self, *};
+    use std::convert::TryFrom;
+
+    /// Assert that `bytes` render as `cowgod` and `octo`
+    fn assert_mnemonic(bytes: &[u8], cowgod: &str, octo: &str) {
+        let opcode = OpCode::try_from(bytes).unwrap();
+        assert_eq!(format_opcode(opcode, Syntax::Cowgod), cowgod);
+        assert_eq!(format_opcode(opcode, Syntax::Octo), octo);
+    }
+
+    /// Test mnemonics of every opcode
+    #[test]
+    fn test_mnemonics() {
+        assert_mnemonic(&[0x01, 0x23], "SYS 0x123", "0x01 0x23");
+        assert_mnemonic(&[0x00, 0xE0], "CLS", "clear");
+        assert_mnemonic(&[0x00, 0xEE], "RET", "return");
+        assert_mnemonic(&[0x00, 0xC4], "SCD 4", "scroll-down 4");
+        assert_mnemonic(&[0x00, 0xFB], "SCR", "scroll-right");
+        assert_mnemonic(&[0x00, 0xFC], "SCL", "scroll-left");
+        assert_mnemonic(&[0x00, 0xFD], "EXIT", "exit");
+        assert_mnemonic(&[0x00, 0xFE], "LOW", "lores");
+        assert_mnemonic(&[0x00, 0xFF], "HIGH", "hires");
+        assert_mnemonic(&[0x12, 0x34], "JP 0x234", "jump 0x234");
+        assert_mnemonic(&[0x23, 0x45], "CALL 0x345", ":call 0x345");
+        assert_mnemonic(&[0x3A, 0x12], "SE VA, 0x12", "if va != 0x12 then");
+        assert_mnemonic(&[0x4A, 0x12], "SNE VA, 0x12", "if va == 0x12 then");
+        assert_mnemonic(&[0x51, 0x20], "SE V1, V2", "if v1 != v2 then");
+        assert_mnemonic(&[0x51, 0x22], "SAVE V1, V2", "save v1 - v2");
+        assert_mnemonic(&[0x51, 0x23], "LOAD V1, V2", "load v1 - v2");
+        assert_mnemonic(&[0x63, 0xFF], "LD V3, 0xFF", "v3 := 0xFF");
+        assert_mnemonic(&[0x73, 0x01], "ADD V3, 0x01", "v3 += 0x01");
+        assert_mnemonic(&[0x81, 0x20], "LD V1, V2", "v1 := v2");
+        assert_mnemonic(&[0x81, 0x21], "OR V1, V2", "v1 |= v2");
+        assert_mnemonic(&[0x81, 0x22], "AND V1, V2", "v1 &= v2");
+        assert_mnemonic(&[0x81, 0x23], "XOR V1, V2", "v1 ^= v2");
+        assert_mnemonic(&[0x81, 0x24], "ADD V1, V2", "v1 += v2");
+        assert_mnemonic(&[0x81, 0x25], "SUB V1, V2", "v1 -= v2");
+        assert_mnemonic(&[0x81, 0x26], "SHR V1, V2", "v1 >>= v2");
+        assert_mnemonic(&[0x81, 0x27], "SUBN V1, V2", "v1 =- v2");
+        assert_mnemonic(&[0x81, 0x2E], "SHL V1, V2", "v1 <<= v2");
+        assert_mnemonic(&[0x9E, 0xF0], "SNE VE, VF", "if ve == vf then");
+        assert_mnemonic(&[0xA2, 0x0A], "LD I, 0x20A", "i := 0x20A");
+        assert_mnemonic(&[0xB3, 0x00], "JP V0, 0x300", "jump0 0x300");
+        assert_mnemonic(&[0xC0, 0x0F], "RND V0, 0x0F", "v0 := random 0x0F");
+        assert_mnemonic(&[0xD1, 0x25], "DRW V1, V2, 5", "sprite v1 v2 5");
+        assert_mnemonic(&[0xE4, 0x9E], "SKP V4", "if v4 -key then");
+        assert_mnemonic(&[0xE4, 0xA1], "SKNP V4", "if v4 key then");
+        assert_mnemonic(
+            &[0xF0, 0x00, 0x12, 0x34],
+            "LD I, LONG 0x1234",
+            "i := long 0x1234",
+        );
+        assert_mnemonic(&[0xF3, 0x01], "PLANE 3", "plane 3");
+        assert_mnemonic(&[0xF0, 0x02], "AUDIO", "audio");
+        assert_mnemonic(&[0xF5, 0x07], "LD V5, DT", "v5 := delay");
+        assert_mnemonic(&[0xF5, 0x0A], "LD V5, K", "v5 := key");
+        assert_mnemonic(&[0xF5, 0x15], "LD DT, V5", "delay := v5");
+        assert_mnemonic(&[0xF5, 0x18], "LD ST, V5", "buzzer := v5");
+        assert_mnemonic(&[0xF5, 0x1E], "ADD I, V5", "i += v5");
+        assert_mnemonic(&[0xF5, 0x29], "LD F, V5", "i := hex v5");
+        assert_mnemonic(&[0xF5, 0x30], "LD HF, V5", "i := bighex v5");
+        assert_mnemonic(&[0xF5, 0x3A], "PITCH V5", "pitch := v5");
+        assert_mnemonic(&[0xF5, 0x33], "LD B, V5", "bcd v5");
+        assert_mnemonic(&[0xF5, 0x55], "LD [I], V5", "save v5");
+        assert_mnemonic(&[0xF5, 0x65], "LD V5, [I]", "load v5");
+        assert_mnemonic(&[0xF5, 0x75], "LD R, V5", "saveflags v5");
+        assert_mnemonic(&[0xF5, 0x85], "LD V5, R", "loadflags v5");
+    }
+
+    /// Test that `Display` uses the Cowgod syntax
+    #[test]
+    fn test_display() {
+        let opcode = RegAddConst {
+            register: 3,
+            constant: 1,
+        };
+        assert_eq!(opcode.to_string(), "ADD V3, 0x01");
+    }
+
+    /// Test disassembling a ROM with labels
+    #[test]
+    fn test_disassemble() {
+        let rom = [
+            0x22, 0x06, // CALL sub_206
+            0x12, 0x02, // JP label_202
+            0x00, 0x00, // SYS 0x000
+            0x60, 0x01, // LD V0, 0x01
+            0x00, 0xEE, // RET
+        ];
+        let listing = disassemble(&rom, 0x200, Syntax::Cowgod);
+        assert_eq!(listing.label(0x202), Some("label_202"));
+        assert_eq!(listing.label(0x206), Some("sub_206"));
+        assert_eq!(
+            listing.to_string(),
+            "\
+0200  22 06        CALL sub_206
+label_202:
+0202  12 02        JP label_202
+0204  00 00        SYS 0x000
+sub_206:
+0206  60 01        LD V0, 0x01
+0208  00 EE        RET
+"
+        );
+        let listing = disassemble(&rom, 0x200, Syntax::Octo);
+        assert_eq!(
+            listing.to_string(),
+            "\
+0200  22 06        :call sub_206
+: label_202
+0202  12 02        jump label_202
+0204  00 00        0x00 0x00
+: sub_206
+0206  60 01        v0 := 0x01
+0208  00 EE        return
+"
+        );
+    }
+
+    /// Test that only the given addresses are decoded as code
+    #[test]
+    fn test_disassemble_code() {
+        let rom = [
+            0xA2, 0x06, // LD I, data_206
+            0xD0, 0x12, // DRW V0, V1, 2
+            0x12, 0x04, // JP label_204
+            0x3C, 0x42, // sprite
+            0x12, 0x00, // JP 0x200, never run
+        ];
+        let code = [0x200, 0x202, 0x204].iter().copied().collect();
+        let listing = disassemble_code(&rom, 0x200, Syntax::Cowgod, &code);
+        assert_eq!(
+            listing.to_string(),
+            "\
+0200  A2 06        LD I, data_206
+0202  D0 12        DRW V0, V1, 2
+label_204:
+0204  12 04        JP label_204
+data_206:
+0206  3C 42 12 00  DB 0x3C, 0x42, 0x12, 0x00
+"
+        );
+    }
+
+    /// Test that targets outside of the ROM are left as addresses
+    #[test]
+    fn test_outside_targets() {
+        let listing = disassemble(&[0x13, 0x00, 0x20, 0x00], 0x200, Syntax::Cowgod);
+        assert!(listing.labels.is_empty());
+        assert_eq!(listing.text(&listing.lines[0]), "JP 0x300");
+        assert_eq!(listing.text(&listing.lines[1]), "CALL 0x000");
+    }
+
+    /// Test data lines, long opcodes and labels inside opcodes
+    #[test]
+    fn test_data() {
+        let rom = [
+            0x12, 0x05, // JP label_205
+            0xF0, 0x00, 0x12, // LD I, LONG 0x12.. split by the label
+            0x34, 0x56, // SE V4, 0x56 at label_205
+            0x5A, 0xB1, // Unknown opcode
+            0xFF, // Trailing byte
+        ];
+        let listing = disassemble(&rom, 0x200, Syntax::Cowgod);
+        assert_eq!(
+            listing.lines[1],
+            Line {
+                address: 0x202,
+                bytes: vec![0xF0, 0x00, 0x12],
+                opcode: None,
+            }
+        );
+        assert_eq!(
+            listing.lines[2].opcode,
+            Some(SkipNextIfRegEqualToConst {
+                register: 4,
+                constant: 0x56
+            })
+        );
+        assert_eq!(listing.lines[2].address, 0x205);
+        assert_eq!(listing.text(&listing.lines[2]), "SE V4, 0x56");
+        assert_eq!(listing.text(&listing.lines[3]), "DB 0x5A, 0xB1");
+        assert_eq!(listing.text(&listing.lines[4]), "DB 0xFF");
+        assert_eq!(listing.lines.len(), 5);
+
+        let listing = disassemble(&[0xF0, 0x00, 0x12, 0x34], 0x200, Syntax::Octo);
+        assert_eq!(listing.text(&listing.lines[0]), "i := long 0x1234");
+    }
+}
@@ -0,0 +1,66 @@
+/// Pitch at which the pattern is played at 4000 samples per second
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// XO-CHIP sound: a pattern of 1-bit samples played in a loop while the sound timer is active
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Audio {
+    /// 128 samples, most significant bit of the first byte first
+    pub pattern: [u8; 16],
+    /// Playback pitch, set by `FX3A`
+    pub pitch: u8,
+}
+
+impl Audio {
+    /// Number of samples in the pattern
+    pub const SAMPLES: usize = 16 * 8;
+
+    /// Samples played per second: `4000 * 2 ^ ((pitch - 64) / 48)`
+    pub fn playback_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - DEFAULT_PITCH as f64) / 48.0)
+    }
+
+    /// Value of the sample at `index`, wrapping around the end of the pattern
+    pub fn sample(&self, index: usize) -> bool {
+        let index = index % Self::SAMPLES;
+        self.pattern[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+}
+
+impl Default for Audio {
+    /// A 500 Hz square wave, which is what programs that don't load a pattern hear
+    fn default() -> Self {
+        Self {
+            pattern: [0xF0; 16],
+            pitch: DEFAULT_PITCH,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::audio::Audio;
+
+    /// Test the playback rate at a few pitches
+    #[test]
+    fn test_playback_rate() {
+        let mut a = Audio::default();
+        assert!((a.playback_rate() - 4000.0).abs() < 1e-9);
+        a.pitch = 64 + 48;
+        assert!((a.playback_rate() - 8000.0).abs() < 1e-9);
+        a.pitch = 64 - 48;
+        assert!((a.playback_rate() - 2000.0).abs() < 1e-9);
+    }
+
+    /// Test reading samples
+    #[test]
+    fn test_sample() {
+        let mut a = Audio {
+            pattern: [0; 16],
+            ..Audio::default()
+        };
+        a.pattern[1] = 0b0100_0000;
+        assert!(a.sample(9));
+        assert!(!a.sample(8));
+        assert!(a.sample(9 + Audio::SAMPLES));
+    }
+}
@@ -1,5 +1,9 @@
+use crate::emulator::cycle::CpuState;
+use crate::emulator::error::EmulatorError;
 use crate::emulator::opcode::OpCode;
+use crate::emulator::quirks::IndexIncrement;
 use crate::emulator::Emulator;
+use std::ops::Range;
 use OpCode::*;
 
 impl Emulator {
@@ -7,13 +11,23 @@ impl Emulator {
     ///
     /// # Parameters:
     /// - 'opcode` - opcode to execute
-    pub fn execute_opcode(&mut self, opcode: OpCode) {
+    ///
+    /// # Errors:
+    /// Fails if the opcode can't be executed in the current machine state.
+    /// The machine may be partially modified in that case.
+    pub fn execute_opcode(&mut self, opcode: OpCode) -> Result<(), EmulatorError> {
         match opcode {
-            _NativeCall { target: _ } => panic!("Called a _NativeCall OpCode, which is deprecated"),
-            ClearScreen => todo!(),
-            Return => self.ret(),
+            _NativeCall { target } => return Err(EmulatorError::NativeCall { target }),
+            ClearScreen => self.display.clear_planes(),
+            Return => self.ret()?,
+            ScrollDown { rows } => self.display.scroll_down(rows as usize),
+            ScrollRight => self.display.scroll_right(4),
+            ScrollLeft => self.display.scroll_left(4),
+            Exit => self.state = CpuState::Halted,
+            LowRes => self.display.set_hires(false),
+            HighRes => self.display.set_hires(true),
             Goto { target } => self.goto(target),
-            Subroutine { target } => self.subroutine(target),
+            Subroutine { target } => self.subroutine(target)?,
             SkipNextIfRegEqualToConst { register, constant } => {
                 if self.get_reg(register) == constant {
                     self.skip()
@@ -34,10 +48,34 @@ impl Emulator {
                     self.skip()
                 }
             }
+            RegRangeDump {
+                register_x,
+                register_y,
+            } => {
+                let registers = Self::register_range(register_x, register_y);
+                let len = registers.len();
+                let mut values = [0; 16];
+                for (value, register) in values.iter_mut().zip(registers) {
+                    *value = self.get_reg(register);
+                }
+                self.write_memory(self.index_register, &values[..len])?;
+            }
+            RegRangeLoad {
+                register_x,
+                register_y,
+            } => {
+                let registers = Self::register_range(register_x, register_y);
+                let len = registers.len();
+                let mut values = [0; 16];
+                values[..len].copy_from_slice(self.read_memory(self.index_register, len)?);
+                for (register, value) in registers.zip(values.iter()) {
+                    self.set_reg(register, *value);
+                }
+            }
             RegSetConst { register, constant } => self.set_reg(register, constant),
             RegAddConst { register, constant } => {
                 let rx = self.get_reg(register);
-                self.set_reg(register, rx + constant);
+                self.set_reg(register, rx.wrapping_add(constant));
             }
             RegMov {
                 register_x,
@@ -50,6 +88,7 @@ impl Emulator {
                 let rx = self.get_reg(register_x);
                 let ry = self.get_reg(register_y);
                 self.set_reg(register_x, rx | ry);
+                self.reset_vf_after_logic();
             }
             RegBitwiseAnd {
                 register_x,
@@ -57,7 +96,8 @@ impl Emulator {
             } => {
                 let rx = self.get_reg(register_x);
                 let ry = self.get_reg(register_y);
-                self.set_reg(register_x, rx & ry)
+                self.set_reg(register_x, rx & ry);
+                self.reset_vf_after_logic();
             }
             RegBitwiseXor {
                 register_x,
@@ -65,7 +105,8 @@ impl Emulator {
             } => {
                 let rx = self.get_reg(register_x);
                 let ry = self.get_reg(register_y);
-                self.set_reg(register_x, rx ^ ry)
+                self.set_reg(register_x, rx ^ ry);
+                self.reset_vf_after_logic();
             }
             RegAdd {
                 register_x,
@@ -73,7 +114,9 @@ impl Emulator {
             } => {
                 let rx = self.get_reg(register_x);
                 let ry = self.get_reg(register_y);
-                self.set_reg(register_x, rx + ry)
+                let (result, carry) = rx.overflowing_add(ry);
+                self.set_reg(register_x, result);
+                self.set_reg(0xF, carry as u8);
             }
             RegSub {
                 register_x,
@@ -81,12 +124,16 @@ impl Emulator {
             } => {
                 let rx = self.get_reg(register_x);
                 let ry = self.get_reg(register_y);
-                self.set_reg(register_x, rx - ry);
+                self.set_reg(register_x, rx.wrapping_sub(ry));
+                self.set_reg(0xF, (rx >= ry) as u8);
             }
-            RegRightShift { register } => {
-                let rx = self.get_reg(register);
-                self.set_reg(0xF, rx % (1 << 1));
-                self.set_reg(register, rx >> 1);
+            RegRightShift {
+                register_x,
+                register_y,
+            } => {
+                let value = self.shift_operand(register_x, register_y);
+                self.set_reg(register_x, value >> 1);
+                self.set_reg(0xF, value % (1 << 1));
             }
             RegReverseSub {
                 register_x,
@@ -94,12 +141,16 @@ impl Emulator {
             } => {
                 let rx = self.get_reg(register_x);
                 let ry = self.get_reg(register_y);
-                self.registers[register_x as usize] = ry - rx;
+                self.set_reg(register_x, ry.wrapping_sub(rx));
+                self.set_reg(0xF, (ry >= rx) as u8);
             }
-            RegLeftShift { register } => {
-                let rx = self.get_reg(register);
-                self.set_reg(0xF, rx >> 7);
-                self.set_reg(register, rx << 1);
+            RegLeftShift {
+                register_x,
+                register_y,
+            } => {
+                let value = self.shift_operand(register_x, register_y);
+                self.set_reg(register_x, value << 1);
+                self.set_reg(0xF, value >> 7);
             }
             SkipNextIfRegNotEqualToReg {
                 register_x,
@@ -112,37 +163,140 @@ impl Emulator {
                 }
             }
             Mem { target } => self.index_register = target,
-            JumpRegZero { target } => self.goto(self.get_reg(0) as u16 + target),
+            JumpRegZero { target } => {
+                let register = if self.quirks.jump_uses_vx {
+                    (target >> 8) as u8
+                } else {
+                    0
+                };
+                self.goto(self.get_reg(register) as u16 + target)
+            }
             RandToReg { register, constant } => {
-                self.set_reg(register, self.rng.clone().rand() & constant)
+                let value = self.rng.rand();
+                self.set_reg(register, value & constant)
             }
             DisplaySprite {
-                coord_x: _,
-                coord_y: _,
-                height: _,
-            } => todo!(),
-            SkipNextIfRegKeyPressed { register: _ } => todo!(),
-            SkipNextIfRegKeyNotPressed { register: _ } => todo!(),
+                coord_x,
+                coord_y,
+                height,
+            } => {
+                let x = self.get_reg(coord_x);
+                let y = self.get_reg(coord_y);
+                let wrap = self.quirks.sprite_wrap;
+                let planes = self.display.plane_count();
+                let collision = if height != 0 {
+                    let sprite =
+                        self.memory_range(self.index_register, height as usize * planes)?;
+                    self.display.draw_sprite(x, y, &self.memory[sprite], wrap)
+                } else if self.variant.has_large_sprites() {
+                    let sprite = self.memory_range(self.index_register, 32 * planes)?;
+                    self.display
+                        .draw_large_sprite(x, y, &self.memory[sprite], wrap)
+                } else {
+                    false
+                };
+                self.set_reg(0xF, collision as u8);
+                if self.quirks.display_wait {
+                    self.state = CpuState::WaitingForVBlank;
+                }
+            }
+            SkipNextIfRegKeyPressed { register } => {
+                if self.keypad.is_pressed(self.get_reg(register)) {
+                    self.skip()
+                }
+            }
+            SkipNextIfRegKeyNotPressed { register } => {
+                if !self.keypad.is_pressed(self.get_reg(register)) {
+                    self.skip()
+                }
+            }
+            MemLong { target } => self.index_register = target,
+            SelectPlanes { planes } => self.display.select_planes(planes),
+            LoadAudioPattern => {
+                let pattern = self.memory_range(self.index_register, 16)?;
+                self.audio.pattern.copy_from_slice(&self.memory[pattern]);
+            }
             SetRegToDelayTimer { register } => self.set_reg(register, self.delay_timer),
-            SetRegToKeyPressed { register: _ } => todo!(),
+            SetRegToKeyPressed { register } => self.state = CpuState::WaitingForKey { register },
             SetDelayTimerToReg { register } => self.delay_timer = self.get_reg(register),
             SetSoundTimerToReg { register } => self.sound_timer = self.get_reg(register),
             MemAddReg { register } => {
-                self.index_register += self.get_reg(register) as u16;
+                self.index_register = self
+                    .index_register
+                    .wrapping_add(self.get_reg(register) as u16);
+            }
+            MemMoveToRegChar { register } => {
+                self.index_register = self.char_address(self.get_reg(register))
+            }
+            MemMoveToRegLargeChar { register } => {
+                self.index_register = self.large_char_address(self.get_reg(register))
+            }
+            SetPitchToReg { register } => self.audio.pitch = self.get_reg(register),
+            StoreBCD { register } => {
+                let rx = self.get_reg(register);
+                self.write_memory(self.index_register, &[rx / 100, rx / 10 % 10, rx % 10])?;
             }
-            MemMoveToRegChar { register: _ } => todo!(),
-            StoreBCD { register: _ } => todo!(),
             RegDump { register } => {
-                for i in 0..=register {
-                    self.memory[self.index_register as usize + i as usize] = self.get_reg(i)
-                }
+                let registers = self.registers;
+                self.write_memory(self.index_register, &registers[..=register as usize])?;
+                self.advance_index(register);
             }
             RegLoad { register } => {
-                for i in 0..=register {
-                    self.set_reg(i, self.memory[self.index_register as usize + i as usize])
-                }
+                let count = register as usize + 1;
+                let values = self.read_memory(self.index_register, count)?;
+                let mut registers = self.registers;
+                registers[..count].copy_from_slice(values);
+                self.registers = registers;
+                self.advance_index(register);
+            }
+            StoreFlags { register } => {
+                let count = register as usize + 1;
+                self.rpl_flags[..count].copy_from_slice(&self.registers[..count]);
+            }
+            LoadFlags { register } => {
+                let count = register as usize + 1;
+                self.registers[..count].copy_from_slice(&self.rpl_flags[..count]);
             }
         }
+        Ok(())
+    }
+
+    /// Registers from `VX` to `VY` (including both) for `5XY2`/`5XY3`, descending if `X > Y`
+    fn register_range(register_x: u8, register_y: u8) -> impl ExactSizeIterator<Item = u8> {
+        let count = register_x.max(register_y) - register_x.min(register_y);
+        (0..=count).map(move |i| {
+            if register_x <= register_y {
+                register_x + i
+            } else {
+                register_x - i
+            }
+        })
+    }
+
+    /// Value shifted by `8XY6`/`8XYE`, according to the `shift_uses_vy` quirk
+    fn shift_operand(&self, register_x: u8, register_y: u8) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.get_reg(register_y)
+        } else {
+            self.get_reg(register_x)
+        }
+    }
+
+    /// Reset `VF` after `8XY1`/`8XY2`/`8XY3` if the `logic_resets_vf` quirk is on
+    fn reset_vf_after_logic(&mut self) {
+        if self.quirks.logic_resets_vf {
+            self.set_reg(0xF, 0);
+        }
+    }
+
+    /// Change `I` after `FX55`/`FX65` according to the `index_increment` quirk
+    fn advance_index(&mut self, register: u8) {
+        let increment = match self.quirks.index_increment {
+            IndexIncrement::None => 0,
+            IndexIncrement::ByX => register as u16,
+            IndexIncrement::ByXPlusOne => register as u16 + 1,
+        };
+        self.index_register = self.index_register.wrapping_add(increment);
     }
 
     /// Move program counter to a `dest`
@@ -151,56 +305,108 @@ impl Emulator {
     }
 
     /// Start a subroutine in `dent`
-    pub fn subroutine(&mut self, dest: u16) {
-        self.stack.push(self.program_counter);
+    pub fn subroutine(&mut self, dest: u16) -> Result<(), EmulatorError> {
+        self.stack.push(self.program_counter)?;
         self.goto(dest);
+        Ok(())
     }
 
     /// Return from a subroutine
-    pub fn ret(&mut self) {
-        self.program_counter = self.stack.pop().unwrap();
+    pub fn ret(&mut self) -> Result<(), EmulatorError> {
+        self.program_counter = self.stack.pop()?;
+        Ok(())
     }
 
-    /// Skip next instruction
+    /// Skip next instruction, which is 4 bytes long if it's `MemLong`
     pub fn skip(&mut self) {
-        self.program_counter += 2;
+        let pc = self.program_counter as usize;
+        let size = match self.memory.get(pc..pc + 2) {
+            Some(prefix) => OpCode::encoded_size(prefix[0], prefix[1]),
+            None => 2,
+        };
+        self.program_counter = self.program_counter.wrapping_add(size);
+    }
+
+    /// Get `len` bytes of memory starting at `address`
+    ///
+    /// Fails if they don't fit into memory
+    pub fn read_memory(&self, address: u16, len: usize) -> Result<&[u8], EmulatorError> {
+        let range = self.memory_range(address, len)?;
+        Ok(&self.memory[range])
+    }
+
+    /// Copy `bytes` into memory starting at `address`
+    ///
+    /// Fails if they don't fit into memory, nothing is written in that case
+    pub fn write_memory(&mut self, address: u16, bytes: &[u8]) -> Result<(), EmulatorError> {
+        let range = self.memory_range(address, bytes.len())?;
+        self.memory[range].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Range of `len` bytes of memory starting at `address`, if they fit into memory
+    fn memory_range(&self, address: u16, len: usize) -> Result<Range<usize>, EmulatorError> {
+        let start = address as usize;
+        if start + len > self.memory.len() {
+            Err(EmulatorError::MemoryOutOfBounds {
+                address: start.max(self.memory.len()),
+            })
+        } else {
+            Ok(start..start + len)
+        }
     }
 }
 
 #[cfg(test)]
 pub mod tests {
+    use crate::emulator::cycle::CpuState;
+    use crate::emulator::error::EmulatorError;
     use crate::emulator::opcode::OpCode::*;
+    use crate::emulator::quirks::IndexIncrement;
+    use crate::emulator::random::{RandomSource, RNG};
+    use crate::emulator::variant::Variant;
     use crate::emulator::Emulator;
 
+    /// Test _NativeCall execution
     #[test]
-    #[should_panic]
     fn test_native_call() {
         let mut e = Emulator::default();
-        e.execute_opcode(_NativeCall { target: 0 })
+        assert_eq!(
+            e.execute_opcode(_NativeCall { target: 0x123 }),
+            Err(EmulatorError::NativeCall { target: 0x123 })
+        );
     }
 
     /// Test ClearScreen execution
     #[test]
-    #[should_panic]
     fn test_clear_screen() {
         let mut e = Emulator::default();
-        e.execute_opcode(ClearScreen);
+        e.display.draw_sprite(0, 0, &[0xFF], false);
+        e.execute_opcode(ClearScreen).unwrap();
+        assert!(e.display.pixels().iter().all(|p| *p == 0));
     }
 
     ///Test Return execution
     #[test]
     fn test_return() {
         let mut e = Emulator::default();
-        e.stack.push(5);
-        e.execute_opcode(Return);
+        e.stack.push(5).unwrap();
+        e.execute_opcode(Return).unwrap();
         assert_eq!(e.program_counter, 5);
     }
 
+    /// Test Return execution with an empty stack
+    #[test]
+    fn test_return_underflow() {
+        let mut e = Emulator::default();
+        assert_eq!(e.execute_opcode(Return), Err(EmulatorError::StackUnderflow));
+    }
+
     /// Test Goto execution
     #[test]
     fn test_goto() {
         let mut e = Emulator::default();
-        e.execute_opcode(Goto { target: 4 });
+        e.execute_opcode(Goto { target: 4 }).unwrap();
         assert_eq!(e.program_counter, 4);
     }
 
@@ -208,10 +414,24 @@ pub mod tests {
     #[test]
     fn test_subroutine() {
         let mut e = Emulator::default();
-        e.execute_opcode(Subroutine { target: 4 });
+        e.execute_opcode(Subroutine { target: 4 }).unwrap();
         assert_eq!(e.program_counter, 4);
-        e.execute_opcode(Return);
-        assert_eq!(e.program_counter, 0);
+        e.execute_opcode(Return).unwrap();
+        assert_eq!(e.program_counter, 0x200);
+    }
+
+    /// Test Subroutine execution with a full stack
+    #[test]
+    fn test_subroutine_overflow() {
+        let mut e = Emulator::new(Variant::CosmacVip);
+        for _ in 0..12 {
+            e.execute_opcode(Subroutine { target: 4 }).unwrap();
+        }
+        assert_eq!(
+            e.execute_opcode(Subroutine { target: 4 }),
+            Err(EmulatorError::StackOverflow)
+        );
+        assert_eq!(e.stack.len(), 12);
     }
 
     /// Test SkipNextIfRegEqualToConst execution
@@ -222,13 +442,15 @@ pub mod tests {
         e.execute_opcode(SkipNextIfRegEqualToConst {
             register: 0,
             constant: 4,
-        });
-        assert_eq!(e.program_counter, 2);
+        })
+        .unwrap();
+        assert_eq!(e.program_counter, 0x202);
         e.execute_opcode(SkipNextIfRegEqualToConst {
             register: 0,
             constant: 0,
-        });
-        assert_eq!(e.program_counter, 2);
+        })
+        .unwrap();
+        assert_eq!(e.program_counter, 0x202);
     }
 
     /// Test SkipNextIfReqNotEqualToConst execution
@@ -239,13 +461,15 @@ pub mod tests {
         e.execute_opcode(SkipNextIfRegNotEqualToConst {
             register: 0,
             constant: 0,
-        });
-        assert_eq!(e.program_counter, 2);
+        })
+        .unwrap();
+        assert_eq!(e.program_counter, 0x202);
         e.execute_opcode(SkipNextIfRegNotEqualToConst {
             register: 0,
             constant: 4,
-        });
-        assert_eq!(e.program_counter, 2);
+        })
+        .unwrap();
+        assert_eq!(e.program_counter, 0x202);
     }
 
     /// Test SkipNextIfRegEqualToReg execution
@@ -257,13 +481,15 @@ pub mod tests {
         e.execute_opcode(SkipNextIfRegEqualToReg {
             register_x: 0,
             register_y: 1,
-        });
-        assert_eq!(e.program_counter, 2);
+        })
+        .unwrap();
+        assert_eq!(e.program_counter, 0x202);
         e.set_reg(1, 2);
         e.execute_opcode(SkipNextIfRegEqualToReg {
             register_x: 0,
             register_y: 1,
         })
+        .unwrap()
     }
 
     /// Test RegSetConst execution
@@ -273,7 +499,8 @@ pub mod tests {
         e.execute_opcode(RegSetConst {
             register: 0,
             constant: 64,
-        });
+        })
+        .unwrap();
         assert_eq!(e.get_reg(0), 64);
     }
 
@@ -285,10 +512,30 @@ pub mod tests {
         e.execute_opcode(RegAddConst {
             register: 0,
             constant: 1,
-        });
+        })
+        .unwrap();
         assert_eq!(e.get_reg(0), 2);
     }
 
+    /// Test RegAddConst wrapping for every pair of values
+    #[test]
+    fn test_reg_add_const_exhaustive() {
+        let mut e = Emulator::default();
+        for x in 0..=255u8 {
+            for constant in 0..=255u8 {
+                e.set_reg(0, x);
+                e.set_reg(0xF, 0x42);
+                e.execute_opcode(RegAddConst {
+                    register: 0,
+                    constant,
+                })
+                .unwrap();
+                assert_eq!(e.get_reg(0), x.wrapping_add(constant));
+                assert_eq!(e.get_reg(0xF), 0x42);
+            }
+        }
+    }
+
     /// Test RegMov execution
     #[test]
     fn test_reg_mov() {
@@ -297,7 +544,8 @@ pub mod tests {
         e.execute_opcode(RegMov {
             register_x: 1,
             register_y: 0,
-        });
+        })
+        .unwrap();
         assert_eq!(e.get_reg(0), 3);
     }
 
@@ -310,7 +558,8 @@ pub mod tests {
         e.execute_opcode(RegBitwiseOr {
             register_x: 0,
             register_y: 1,
-        });
+        })
+        .unwrap();
         assert_eq!(e.get_reg(0), 34 | 224);
     }
 
@@ -323,7 +572,8 @@ pub mod tests {
         e.execute_opcode(RegBitwiseAnd {
             register_x: 0,
             register_y: 1,
-        });
+        })
+        .unwrap();
         assert_eq!(e.get_reg(0), 34 & 224);
     }
 
@@ -336,7 +586,8 @@ pub mod tests {
         e.execute_opcode(RegBitwiseXor {
             register_x: 0,
             register_y: 1,
-        });
+        })
+        .unwrap();
         assert_eq!(e.get_reg(0), 0b0111);
     }
 
@@ -349,8 +600,50 @@ pub mod tests {
         e.execute_opcode(RegAdd {
             register_x: 0,
             register_y: 1,
-        });
+        })
+        .unwrap();
         assert_eq!(e.get_reg(0), 11);
+        assert_eq!(e.get_reg(15), 0);
+    }
+
+    /// Test RegAdd result and carry for every pair of values
+    #[test]
+    fn test_reg_add_exhaustive() {
+        let mut e = Emulator::default();
+        for x in 0..=255u8 {
+            for y in 0..=255u8 {
+                e.set_reg(0, x);
+                e.set_reg(1, y);
+                e.execute_opcode(RegAdd {
+                    register_x: 0,
+                    register_y: 1,
+                })
+                .unwrap();
+                assert_eq!(e.get_reg(0), x.wrapping_add(y));
+                assert_eq!(e.get_reg(15), (x as u16 + y as u16 > 255) as u8);
+            }
+        }
+    }
+
+    /// Test that RegAdd writes the carry after the result
+    #[test]
+    fn test_reg_add_vf() {
+        let mut e = Emulator::default();
+        e.set_reg(15, 200);
+        e.set_reg(0, 100);
+        e.execute_opcode(RegAdd {
+            register_x: 15,
+            register_y: 0,
+        })
+        .unwrap();
+        assert_eq!(e.get_reg(15), 1);
+        e.set_reg(15, 1);
+        e.execute_opcode(RegAdd {
+            register_x: 15,
+            register_y: 0,
+        })
+        .unwrap();
+        assert_eq!(e.get_reg(15), 0);
     }
 
     /// Test RegSub execution
@@ -362,8 +655,50 @@ pub mod tests {
         e.execute_opcode(RegSub {
             register_x: 0,
             register_y: 1,
-        });
+        })
+        .unwrap();
         assert_eq!(e.get_reg(0), 1);
+        assert_eq!(e.get_reg(15), 1);
+    }
+
+    /// Test RegSub result and borrow for every pair of values
+    #[test]
+    fn test_reg_sub_exhaustive() {
+        let mut e = Emulator::default();
+        for x in 0..=255u8 {
+            for y in 0..=255u8 {
+                e.set_reg(0, x);
+                e.set_reg(1, y);
+                e.execute_opcode(RegSub {
+                    register_x: 0,
+                    register_y: 1,
+                })
+                .unwrap();
+                assert_eq!(e.get_reg(0), x.wrapping_sub(y));
+                assert_eq!(e.get_reg(15), (x >= y) as u8);
+            }
+        }
+    }
+
+    /// Test that RegSub writes the borrow flag after the result
+    #[test]
+    fn test_reg_sub_vf() {
+        let mut e = Emulator::default();
+        e.set_reg(15, 5);
+        e.set_reg(0, 3);
+        e.execute_opcode(RegSub {
+            register_x: 15,
+            register_y: 0,
+        })
+        .unwrap();
+        assert_eq!(e.get_reg(15), 1);
+        e.set_reg(15, 2);
+        e.execute_opcode(RegSub {
+            register_x: 15,
+            register_y: 0,
+        })
+        .unwrap();
+        assert_eq!(e.get_reg(15), 0);
     }
 
     /// Test RegRightShift execution
@@ -371,11 +706,19 @@ pub mod tests {
     fn test_reg_rshift() {
         let mut e = Emulator::default();
         e.set_reg(0, 0b101);
-        e.execute_opcode(RegRightShift { register: 0 });
+        e.execute_opcode(RegRightShift {
+            register_x: 0,
+            register_y: 0,
+        })
+        .unwrap();
         assert_eq!(e.get_reg(15), 1);
         assert_eq!(e.get_reg(0), 0b10);
         e.set_reg(0, 0b100);
-        e.execute_opcode(RegRightShift { register: 0 });
+        e.execute_opcode(RegRightShift {
+            register_x: 0,
+            register_y: 0,
+        })
+        .unwrap();
         assert_eq!(e.get_reg(15), 0);
         assert_eq!(e.get_reg(0), 0b10);
     }
@@ -389,8 +732,70 @@ pub mod tests {
         e.execute_opcode(RegReverseSub {
             register_x: 0,
             register_y: 1,
-        });
+        })
+        .unwrap();
         assert_eq!(e.get_reg(0), 1);
+        assert_eq!(e.get_reg(15), 1);
+    }
+
+    /// Test RegReverseSub result and borrow for every pair of values
+    #[test]
+    fn test_reg_reverse_sub_exhaustive() {
+        let mut e = Emulator::default();
+        for x in 0..=255u8 {
+            for y in 0..=255u8 {
+                e.set_reg(0, x);
+                e.set_reg(1, y);
+                e.execute_opcode(RegReverseSub {
+                    register_x: 0,
+                    register_y: 1,
+                })
+                .unwrap();
+                assert_eq!(e.get_reg(0), y.wrapping_sub(x));
+                assert_eq!(e.get_reg(15), (y >= x) as u8);
+            }
+        }
+    }
+
+    /// Test that RegReverseSub writes the borrow flag after the result
+    #[test]
+    fn test_reg_reverse_sub_vf() {
+        let mut e = Emulator::default();
+        e.set_reg(15, 3);
+        e.set_reg(0, 5);
+        e.execute_opcode(RegReverseSub {
+            register_x: 15,
+            register_y: 0,
+        })
+        .unwrap();
+        assert_eq!(e.get_reg(15), 1);
+        e.set_reg(15, 6);
+        e.execute_opcode(RegReverseSub {
+            register_x: 15,
+            register_y: 0,
+        })
+        .unwrap();
+        assert_eq!(e.get_reg(15), 0);
+    }
+
+    /// Test that shifts write the shifted out bit after the result
+    #[test]
+    fn test_shift_vf() {
+        let mut e = Emulator::default();
+        e.set_reg(15, 0b10);
+        e.execute_opcode(RegRightShift {
+            register_x: 15,
+            register_y: 15,
+        })
+        .unwrap();
+        assert_eq!(e.get_reg(15), 0);
+        e.set_reg(15, 0b1000_0001);
+        e.execute_opcode(RegLeftShift {
+            register_x: 15,
+            register_y: 15,
+        })
+        .unwrap();
+        assert_eq!(e.get_reg(15), 1);
     }
 
     /// Test RegLeftShift execution
@@ -398,11 +803,19 @@ pub mod tests {
     fn test_reg_lshift() {
         let mut e = Emulator::default();
         e.set_reg(0, 0b00001000);
-        e.execute_opcode(RegLeftShift { register: 0 });
+        e.execute_opcode(RegLeftShift {
+            register_x: 0,
+            register_y: 0,
+        })
+        .unwrap();
         assert_eq!(e.get_reg(15), 0);
         assert_eq!(e.get_reg(0), 0b10000);
         e.set_reg(0, 0b10001001);
-        e.execute_opcode(RegLeftShift { register: 0 });
+        e.execute_opcode(RegLeftShift {
+            register_x: 0,
+            register_y: 0,
+        })
+        .unwrap();
         assert_eq!(e.get_reg(15), 1);
         assert_eq!(e.get_reg(0), 0b10010)
     }
@@ -415,21 +828,23 @@ pub mod tests {
         e.execute_opcode(SkipNextIfRegNotEqualToReg {
             register_x: 0,
             register_y: 1,
-        });
-        assert_eq!(e.program_counter, 2);
+        })
+        .unwrap();
+        assert_eq!(e.program_counter, 0x202);
         e.set_reg(1, 1);
         e.execute_opcode(SkipNextIfRegNotEqualToReg {
             register_x: 0,
             register_y: 1,
-        });
-        assert_eq!(e.program_counter, 2);
+        })
+        .unwrap();
+        assert_eq!(e.program_counter, 0x202);
     }
 
     /// Test Mem execution
     #[test]
     fn test_mem() {
         let mut e = Emulator::default();
-        e.execute_opcode(Mem { target: 37 });
+        e.execute_opcode(Mem { target: 37 }).unwrap();
         assert_eq!(e.index_register, 37);
     }
 
@@ -438,7 +853,7 @@ pub mod tests {
     fn test_jump_reg0() {
         let mut e = Emulator::default();
         e.set_reg(0, 54);
-        e.execute_opcode(JumpRegZero { target: 46 });
+        e.execute_opcode(JumpRegZero { target: 46 }).unwrap();
         assert_eq!(e.program_counter, 100);
     }
 
@@ -446,64 +861,103 @@ pub mod tests {
     #[test]
     fn test_rand2reg() {
         let mut e = Emulator::default();
-        e.execute_opcode(RandToReg {
-            register: 0,
-            constant: 54,
-        });
-        e.execute_opcode(RandToReg {
-            register: 1,
-            constant: 54,
-        });
-        assert_ne!(e.get_reg(0), e.get_reg(1));
+        e.seed_rng(1);
+        let mut rng = RNG::from_seed(1);
+        for register in 0..16 {
+            e.execute_opcode(RandToReg {
+                register,
+                constant: 0xF0,
+            })
+            .unwrap();
+            assert_eq!(e.get_reg(register), rng.rand() & 0xF0);
+        }
+        assert_ne!(e.registers, [e.get_reg(0); 16]);
     }
 
     /// Test DisplaySprite execution
     #[test]
-    #[should_panic]
     fn test_display_sprite() {
         let mut e = Emulator::default();
-        e.execute_opcode(DisplaySprite {
+        e.memory[0x300..0x302].copy_from_slice(&[0b1100_0000, 0b0100_0000]);
+        e.index_register = 0x300;
+        e.set_reg(0, 10);
+        e.set_reg(1, 20);
+        let sprite = DisplaySprite {
             coord_x: 0,
-            coord_y: 0,
-            height: 0,
-        })
+            coord_y: 1,
+            height: 2,
+        };
+        e.execute_opcode(sprite).unwrap();
+        assert_eq!(e.get_reg(15), 0);
+        assert!(e.display.pixel(10, 20));
+        assert!(e.display.pixel(11, 20));
+        assert!(!e.display.pixel(10, 21));
+        assert!(e.display.pixel(11, 21));
+        assert_eq!(e.index_register, 0x300);
+        e.execute_opcode(sprite).unwrap();
+        assert_eq!(e.get_reg(15), 1);
+        assert!(e.display.pixels().iter().all(|p| *p == 0));
     }
 
     /// Test SkipNextIfRegKeyPressed execution
     #[test]
-    #[should_panic]
     fn test_skip_key() {
         let mut e = Emulator::default();
+        e.set_reg(0, 0xC);
+        e.execute_opcode(SkipNextIfRegKeyPressed { register: 0 })
+            .unwrap();
+        assert_eq!(e.program_counter, 0x200);
+        e.press_key(0xC);
         e.execute_opcode(SkipNextIfRegKeyPressed { register: 0 })
+            .unwrap();
+        assert_eq!(e.program_counter, 0x202);
     }
 
     /// Test SkipNextIfRegKeyNotPressed execution
     #[test]
-    #[should_panic]
     fn test_skip_not_key() {
         let mut e = Emulator::default();
+        e.set_reg(0, 0xC);
         e.execute_opcode(SkipNextIfRegKeyNotPressed { register: 0 })
+            .unwrap();
+        assert_eq!(e.program_counter, 0x202);
+        e.press_key(0xC);
+        e.execute_opcode(SkipNextIfRegKeyNotPressed { register: 0 })
+            .unwrap();
+        assert_eq!(e.program_counter, 0x202);
     }
 
     /// Test SetRegToDelayTimer execution
     #[test]
     fn test_reg2delay() {
-        let mut e = Emulator::default();
-        e.delay_timer = 3;
-        e.execute_opcode(SetRegToDelayTimer { register: 0 });
+        let mut e = Emulator {
+            delay_timer: 3,
+            ..Emulator::default()
+        };
+        e.execute_opcode(SetRegToDelayTimer { register: 0 })
+            .unwrap();
         assert_eq!(e.get_reg(0), 3);
     }
 
     /// Test SetRegToKeyPressed execution
     #[test]
-    fn test_key2reg() {}
+    fn test_key2reg() {
+        let mut e = Emulator::default();
+        e.execute_opcode(SetRegToKeyPressed { register: 2 })
+            .unwrap();
+        assert_eq!(e.state, CpuState::WaitingForKey { register: 2 });
+        e.press_key(0x9);
+        assert_eq!(e.state, CpuState::Running);
+        assert_eq!(e.get_reg(2), 0x9);
+    }
 
     /// Test SetDelayTimerToReg execution
     #[test]
     fn test_delay2reg() {
         let mut e = Emulator::default();
         e.set_reg(0, 45);
-        e.execute_opcode(SetDelayTimerToReg { register: 0 });
+        e.execute_opcode(SetDelayTimerToReg { register: 0 })
+            .unwrap();
         assert_eq!(e.delay_timer, 45);
     }
 
@@ -512,7 +966,8 @@ pub mod tests {
     fn test_sound2reg() {
         let mut e = Emulator::default();
         e.set_reg(0, 45);
-        e.execute_opcode(SetSoundTimerToReg { register: 0 });
+        e.execute_opcode(SetSoundTimerToReg { register: 0 })
+            .unwrap();
         assert_eq!(e.sound_timer, 45);
     }
 
@@ -522,24 +977,56 @@ pub mod tests {
         let mut e = Emulator::default();
         e.set_reg(0, 45);
         e.index_register = 5;
-        e.execute_opcode(MemAddReg { register: 0 });
+        e.execute_opcode(MemAddReg { register: 0 }).unwrap();
         assert_eq!(e.index_register, 50);
     }
 
     /// Test MemMoveToCharReg execution
     #[test]
-    #[should_panic]
     fn test_mem_move_char() {
         let mut e = Emulator::default();
-        e.execute_opcode(MemMoveToRegChar { register: 0 })
+        e.set_reg(0, 0x1A);
+        e.execute_opcode(MemMoveToRegChar { register: 0 }).unwrap();
+        assert_eq!(e.index_register, e.font_address + 0xA * 5);
+        let i = e.index_register as usize;
+        assert_eq!(e.memory[i..i + 5], [0xF0, 0x90, 0xF0, 0x90, 0x90]);
     }
 
     /// Test StoreBCD execution
     #[test]
-    #[should_panic]
     fn test_store_bcd() {
-        let mut e = Emulator::default();
-        e.execute_opcode(StoreBCD { register: 0 })
+        let mut e = Emulator {
+            index_register: 0x300,
+            ..Emulator::default()
+        };
+        for (value, digits) in [
+            (0, [0, 0, 0]),
+            (9, [0, 0, 9]),
+            (99, [0, 9, 9]),
+            (100, [1, 0, 0]),
+            (255, [2, 5, 5]),
+        ]
+        .iter()
+        {
+            e.set_reg(4, *value);
+            e.execute_opcode(StoreBCD { register: 4 }).unwrap();
+            assert_eq!(e.memory[0x300..0x303], *digits);
+            assert_eq!(e.index_register, 0x300);
+        }
+    }
+
+    /// Test StoreBCD execution at the top of memory
+    #[test]
+    fn test_store_bcd_out_of_bounds() {
+        let mut e = Emulator {
+            index_register: 0xFFE,
+            ..Emulator::default()
+        };
+        assert_eq!(
+            e.execute_opcode(StoreBCD { register: 0 }),
+            Err(EmulatorError::MemoryOutOfBounds { address: 0x1000 })
+        );
+        assert_eq!(e.memory[0xFFE..], [0, 0]);
     }
 
     /// Test RegDump execution
@@ -549,7 +1036,7 @@ pub mod tests {
         e.set_reg(0, 1);
         e.set_reg(1, 2);
         e.set_reg(2, 3);
-        e.execute_opcode(RegDump { register: 2 });
+        e.execute_opcode(RegDump { register: 2 }).unwrap();
         assert_eq!(e.memory[0..3], [1, 2, 3])
     }
 
@@ -560,7 +1047,357 @@ pub mod tests {
         e.memory[0] = 1;
         e.memory[1] = 2;
         e.memory[2] = 3;
-        e.execute_opcode(RegLoad { register: 2 });
+        e.execute_opcode(RegLoad { register: 2 }).unwrap();
         assert_eq!(e.registers[0..3], [1, 2, 3]);
     }
+
+    /// Test RegDump execution at the top of memory
+    #[test]
+    fn test_reg_dump_out_of_bounds() {
+        let mut e = Emulator {
+            index_register: 0xFFF,
+            ..Emulator::default()
+        };
+        e.execute_opcode(RegDump { register: 0 }).unwrap();
+        assert_eq!(
+            e.execute_opcode(RegDump { register: 1 }),
+            Err(EmulatorError::MemoryOutOfBounds { address: 0x1000 })
+        );
+    }
+
+    /// Test RegLoad execution at the top of memory
+    #[test]
+    fn test_reg_load_out_of_bounds() {
+        let mut e = Emulator {
+            index_register: 0xFFF,
+            ..Emulator::default()
+        };
+        assert_eq!(
+            e.execute_opcode(RegLoad { register: 0xF }),
+            Err(EmulatorError::MemoryOutOfBounds { address: 0x1000 })
+        );
+    }
+
+    /// Test shifts with the shift_uses_vy quirk
+    #[test]
+    fn test_quirk_shift_uses_vy() {
+        let mut e = Emulator::default();
+        e.quirks.shift_uses_vy = true;
+        e.set_reg(1, 0b0000_0011);
+        e.execute_opcode(RegRightShift {
+            register_x: 0,
+            register_y: 1,
+        })
+        .unwrap();
+        assert_eq!(e.get_reg(0), 0b0000_0001);
+        assert_eq!(e.get_reg(15), 1);
+        e.set_reg(1, 0b0100_0001);
+        e.execute_opcode(RegLeftShift {
+            register_x: 0,
+            register_y: 1,
+        })
+        .unwrap();
+        assert_eq!(e.get_reg(0), 0b1000_0010);
+        assert_eq!(e.get_reg(15), 0);
+        assert_eq!(e.get_reg(1), 0b0100_0001);
+    }
+
+    /// Test RegDump and RegLoad with each index_increment quirk
+    #[test]
+    fn test_quirk_index_increment() {
+        for (increment, expected) in [
+            (IndexIncrement::None, 0x300),
+            (IndexIncrement::ByX, 0x302),
+            (IndexIncrement::ByXPlusOne, 0x303),
+        ]
+        .iter()
+        {
+            let mut e = Emulator::default();
+            e.quirks.index_increment = *increment;
+            e.index_register = 0x300;
+            e.execute_opcode(RegDump { register: 2 }).unwrap();
+            assert_eq!(e.index_register, *expected);
+            e.index_register = 0x300;
+            e.execute_opcode(RegLoad { register: 2 }).unwrap();
+            assert_eq!(e.index_register, *expected);
+        }
+    }
+
+    /// Test JumpRegZero with the jump_uses_vx quirk
+    #[test]
+    fn test_quirk_jump_uses_vx() {
+        let mut e = Emulator::default();
+        e.quirks.jump_uses_vx = true;
+        e.set_reg(0, 1);
+        e.set_reg(3, 2);
+        e.execute_opcode(JumpRegZero { target: 0x310 }).unwrap();
+        assert_eq!(e.program_counter, 0x312);
+    }
+
+    /// Test logic operations with the logic_resets_vf quirk
+    #[test]
+    fn test_quirk_logic_resets_vf() {
+        let mut e = Emulator::default();
+        let x = 0;
+        let y = 1;
+        for opcode in [
+            RegBitwiseOr {
+                register_x: x,
+                register_y: y,
+            },
+            RegBitwiseAnd {
+                register_x: x,
+                register_y: y,
+            },
+            RegBitwiseXor {
+                register_x: x,
+                register_y: y,
+            },
+        ]
+        .iter()
+        {
+            e.quirks.logic_resets_vf = false;
+            e.set_reg(15, 1);
+            e.execute_opcode(*opcode).unwrap();
+            assert_eq!(e.get_reg(15), 1);
+            e.quirks.logic_resets_vf = true;
+            e.execute_opcode(*opcode).unwrap();
+            assert_eq!(e.get_reg(15), 0);
+        }
+    }
+
+    /// Test DisplaySprite with the sprite_wrap quirk
+    #[test]
+    fn test_quirk_sprite_wrap() {
+        let mut e = Emulator::default();
+        e.quirks.sprite_wrap = true;
+        e.index_register = e.char_address(0);
+        e.set_reg(0, 62);
+        e.set_reg(1, 30);
+        e.execute_opcode(DisplaySprite {
+            coord_x: 0,
+            coord_y: 1,
+            height: 5,
+        })
+        .unwrap();
+        assert!(e.display.pixel(62, 30));
+        assert!(e.display.pixel(1, 30));
+        assert!(e.display.pixel(1, 2));
+    }
+
+    /// Test DisplaySprite with the display_wait quirk
+    #[test]
+    fn test_quirk_display_wait() {
+        let mut e = Emulator::default();
+        e.quirks.display_wait = true;
+        e.execute_opcode(DisplaySprite {
+            coord_x: 0,
+            coord_y: 0,
+            height: 1,
+        })
+        .unwrap();
+        assert_eq!(e.state, CpuState::WaitingForVBlank);
+        e.tick_timers();
+        assert_eq!(e.state, CpuState::Running);
+    }
+
+    /// Test ScrollDown, ScrollRight and ScrollLeft
+    #[test]
+    fn test_scroll() {
+        let mut e = Emulator::default();
+        e.display.draw_sprite(8, 8, &[0x80], false);
+        e.execute_opcode(ScrollDown { rows: 3 }).unwrap();
+        assert!(e.display.pixel(8, 11));
+        e.execute_opcode(ScrollRight).unwrap();
+        assert!(e.display.pixel(12, 11));
+        e.execute_opcode(ScrollLeft).unwrap();
+        e.execute_opcode(ScrollLeft).unwrap();
+        assert!(e.display.pixel(4, 11));
+        assert_eq!(e.display.pixels().iter().filter(|p| **p != 0).count(), 1);
+    }
+
+    /// Test Exit
+    #[test]
+    fn test_exit() {
+        let mut e = Emulator::default();
+        e.execute_opcode(Exit).unwrap();
+        assert_eq!(e.state, CpuState::Halted);
+    }
+
+    /// Test LowRes and HighRes
+    #[test]
+    fn test_resolution() {
+        let mut e = Emulator::default();
+        e.display.draw_sprite(0, 0, &[0xFF], false);
+        e.execute_opcode(HighRes).unwrap();
+        assert!(e.display.is_hires());
+        assert_eq!(e.display.width(), 128);
+        assert!(e.display.pixels().iter().all(|p| *p == 0));
+        e.execute_opcode(LowRes).unwrap();
+        assert!(!e.display.is_hires());
+        assert_eq!(e.display.width(), 64);
+    }
+
+    /// Test DisplaySprite with a height of 0 drawing a 16x16 sprite, or nothing on CHIP-8
+    #[test]
+    fn test_display_large_sprite() {
+        for variant in [Variant::Chip8, Variant::CosmacVip].iter() {
+            let mut e = Emulator::new(*variant);
+            e.memory[0x300..0x320].copy_from_slice(&[0xFF; 32]);
+            e.index_register = 0x300;
+            e.set_reg(15, 1);
+            e.execute_opcode(DisplaySprite {
+                coord_x: 0,
+                coord_y: 1,
+                height: 0,
+            })
+            .unwrap();
+            assert_eq!(e.get_reg(15), 0);
+            assert!(e.display.pixels().iter().all(|p| *p == 0));
+        }
+
+        let mut e = Emulator::new(Variant::SuperChip);
+        e.execute_opcode(HighRes).unwrap();
+        e.memory[0x300..0x320].copy_from_slice(&[0xFF; 32]);
+        e.index_register = 0x300;
+        e.set_reg(0, 100);
+        e.set_reg(1, 40);
+        e.execute_opcode(DisplaySprite {
+            coord_x: 0,
+            coord_y: 1,
+            height: 0,
+        })
+        .unwrap();
+        assert_eq!(e.get_reg(15), 0);
+        assert!(e.display.pixel(100, 40));
+        assert!(e.display.pixel(115, 55));
+        assert_eq!(e.display.pixels().iter().filter(|p| **p != 0).count(), 256);
+        e.execute_opcode(DisplaySprite {
+            coord_x: 0,
+            coord_y: 1,
+            height: 0,
+        })
+        .unwrap();
+        assert_eq!(e.get_reg(15), 1);
+        assert!(e.display.pixels().iter().all(|p| *p == 0));
+    }
+
+    /// Test MemMoveToRegLargeChar
+    #[test]
+    fn test_mem_move_large_char() {
+        let mut e = Emulator::default();
+        e.set_reg(2, 0x9);
+        e.execute_opcode(MemMoveToRegLargeChar { register: 2 })
+            .unwrap();
+        assert_eq!(e.index_register, e.large_char_address(0x9));
+    }
+
+    /// Test StoreFlags and LoadFlags
+    #[test]
+    fn test_flags() {
+        let mut e = Emulator::default();
+        for i in 0..16 {
+            e.set_reg(i, i + 1);
+        }
+        e.execute_opcode(StoreFlags { register: 7 }).unwrap();
+        assert_eq!(e.rpl_flags[..8], [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(e.rpl_flags[8], 0);
+        e.load_rom(&[]).unwrap();
+        e.execute_opcode(LoadFlags { register: 3 }).unwrap();
+        assert_eq!(e.registers[..5], [1, 2, 3, 4, 0]);
+    }
+
+    /// Test RegRangeDump and RegRangeLoad in both directions
+    #[test]
+    fn test_reg_range() {
+        let mut e = Emulator::default();
+        for i in 0..16 {
+            e.set_reg(i, i * 2);
+        }
+        e.index_register = 0x300;
+        e.execute_opcode(RegRangeDump {
+            register_x: 2,
+            register_y: 4,
+        })
+        .unwrap();
+        assert_eq!(e.memory[0x300..0x304], [4, 6, 8, 0]);
+        e.execute_opcode(RegRangeDump {
+            register_x: 4,
+            register_y: 2,
+        })
+        .unwrap();
+        assert_eq!(e.memory[0x300..0x303], [8, 6, 4]);
+        assert_eq!(e.index_register, 0x300);
+        e.execute_opcode(RegRangeLoad {
+            register_x: 10,
+            register_y: 12,
+        })
+        .unwrap();
+        assert_eq!(e.registers[9..14], [18, 8, 6, 4, 26]);
+        e.execute_opcode(RegRangeLoad {
+            register_x: 12,
+            register_y: 10,
+        })
+        .unwrap();
+        assert_eq!(e.registers[10..13], [4, 6, 8]);
+    }
+
+    /// Test MemLong with addresses past 4 KiB on XO-CHIP
+    #[test]
+    fn test_mem_long() {
+        let mut e = Emulator::new(Variant::XoChip);
+        assert_eq!(e.memory.len(), 0x10000);
+        e.execute_opcode(MemLong { target: 0xFFF0 }).unwrap();
+        assert_eq!(e.index_register, 0xFFF0);
+        e.set_reg(0, 123);
+        e.execute_opcode(StoreBCD { register: 0 }).unwrap();
+        assert_eq!(e.memory[0xFFF0..0xFFF3], [1, 2, 3]);
+    }
+
+    /// Test that skips jump over a whole MemLong
+    #[test]
+    fn test_skip_mem_long() {
+        let mut e = Emulator::new(Variant::XoChip);
+        e.load_rom(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x60, 0x01])
+            .unwrap();
+        e.step().unwrap();
+        assert_eq!(e.program_counter, 0x206);
+        e.program_counter = 0x202;
+        e.step().unwrap();
+        assert_eq!(e.index_register, 0x1234);
+        assert_eq!(e.program_counter, 0x206);
+    }
+
+    /// Test DisplaySprite and ClearScreen with both planes selected
+    #[test]
+    fn test_display_planes() {
+        let mut e = Emulator::new(Variant::XoChip);
+        e.execute_opcode(SelectPlanes { planes: 0b11 }).unwrap();
+        e.memory[0x300..0x302].copy_from_slice(&[0x80, 0xC0]);
+        e.index_register = 0x300;
+        e.execute_opcode(DisplaySprite {
+            coord_x: 0,
+            coord_y: 0,
+            height: 1,
+        })
+        .unwrap();
+        assert_eq!(e.display.color(0, 0), 0b11);
+        assert_eq!(e.display.color(1, 0), 0b10);
+        e.execute_opcode(SelectPlanes { planes: 0b01 }).unwrap();
+        e.execute_opcode(ClearScreen).unwrap();
+        assert_eq!(e.display.color(0, 0), 0b10);
+    }
+
+    /// Test LoadAudioPattern and SetPitchToReg
+    #[test]
+    fn test_audio() {
+        let mut e = Emulator::new(Variant::XoChip);
+        e.memory[0x300..0x310].copy_from_slice(&[0xAA; 16]);
+        e.index_register = 0x300;
+        e.execute_opcode(LoadAudioPattern).unwrap();
+        assert_eq!(e.audio.pattern, [0xAA; 16]);
+        e.set_reg(3, 112);
+        e.execute_opcode(SetPitchToReg { register: 3 }).unwrap();
+        assert_eq!(e.audio.pitch, 112);
+    }
 }
@@ -0,0 +1,251 @@
+use crate::emulator::error::EmulatorError;
+use crate::emulator::opcode::OpCode;
+use crate::emulator::Emulator;
+use std::convert::TryFrom;
+
+/// State of the emulated processor
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum CpuState {
+    /// Processor fetches and executes instructions
+    Running,
+    /// Processor is blocked until a key is pressed.
+    /// The key will be stored in `VX`, where
+    /// - `X` is `register`
+    WaitingForKey { register: u8 },
+    /// Processor is blocked until the next timer tick (vertical blank interrupt)
+    WaitingForVBlank,
+    /// Processor has stopped and won't execute any more instructions
+    Halted,
+}
+
+/// Result of a single processor cycle
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum StepResult {
+    /// An instruction was fetched, decoded and executed
+    Executed(OpCode),
+    /// Processor is halted, nothing was executed
+    Halted,
+    /// Processor awaits a key press, nothing was executed
+    WaitingForKey,
+    /// Processor awaits the next timer tick, nothing was executed
+    WaitingForVBlank,
+}
+
+/// Result of running several processor cycles
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct RunResult {
+    /// Number of executed instructions
+    pub executed: usize,
+    /// State of the processor after the run
+    pub state: CpuState,
+}
+
+impl Emulator {
+    /// Fetch the instruction at `PC` (2 bytes, or 4 for `MemLong`), decode it and execute it.
+    ///
+    /// `PC` is advanced before the execution, so jumps and subroutine calls work as expected.
+    ///
+    /// # Errors:
+    /// Fails if the instruction can't be fetched or decoded, `PC` is left pointing at it.
+    /// Fails if the instruction can't be executed, `PC` points past it.
+    pub fn step(&mut self) -> Result<StepResult, EmulatorError> {
+        match self.state {
+            CpuState::Running => {}
+            CpuState::WaitingForKey { .. } => return Ok(StepResult::WaitingForKey),
+            CpuState::WaitingForVBlank => return Ok(StepResult::WaitingForVBlank),
+            CpuState::Halted => return Ok(StepResult::Halted),
+        }
+        let address = self.program_counter;
+        let prefix = self.read_memory(address, 2)?;
+        let size = OpCode::encoded_size(prefix[0], prefix[1]);
+        let bytes = self.read_memory(address, size as usize)?;
+        let opcode = OpCode::try_from(bytes).map_err(|error| EmulatorError::UnknownOpcode {
+            address,
+            opcode: error.0,
+        })?;
+        self.program_counter = self.program_counter.wrapping_add(size);
+        self.execute_opcode(opcode)?;
+        Ok(StepResult::Executed(opcode))
+    }
+
+    /// Execute up to `cycles` instructions.
+    ///
+    /// Stops early if the processor halts or starts waiting for a key or a timer tick.
+    /// Stops at the first error.
+    pub fn run_cycles(&mut self, cycles: usize) -> Result<RunResult, EmulatorError> {
+        let mut executed = 0;
+        while executed < cycles {
+            match self.step()? {
+                StepResult::Executed(_) => executed += 1,
+                _ => break,
+            }
+        }
+        Ok(RunResult {
+            executed,
+            state: self.state,
+        })
+    }
+
+    /// Execute instructions until the processor halts or starts waiting for a key or a timer tick.
+    ///
+    /// Never returns for programs that loop forever, use `run_cycles` for those.
+    /// Stops at the first error.
+    pub fn run(&mut self) -> Result<RunResult, EmulatorError> {
+        let mut executed = 0;
+        while let StepResult::Executed(_) = self.step()? {
+            executed += 1;
+        }
+        Ok(RunResult {
+            executed,
+            state: self.state,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::cycle::{CpuState, RunResult, StepResult};
+    use crate::emulator::error::EmulatorError;
+    use crate::emulator::opcode::OpCode::*;
+    use crate::emulator::Emulator;
+
+    /// Test fetching, decoding and executing a single instruction
+    #[test]
+    fn test_step() {
+        let mut e = Emulator::default();
+        e.load_rom(&[0x61, 0x23]).unwrap();
+        assert_eq!(
+            e.step(),
+            Ok(StepResult::Executed(RegSetConst {
+                register: 1,
+                constant: 0x23
+            }))
+        );
+        assert_eq!(e.get_reg(1), 0x23);
+        assert_eq!(e.program_counter, 0x202);
+    }
+
+    /// Test that subroutines return after the calling instruction
+    #[test]
+    fn test_step_subroutine() {
+        let mut e = Emulator::default();
+        e.load_rom(&[0x22, 0x10]).unwrap();
+        e.memory[0x210..0x212].copy_from_slice(&[0x00, 0xEE]);
+        e.step().unwrap();
+        assert_eq!(e.program_counter, 0x210);
+        e.step().unwrap();
+        assert_eq!(e.program_counter, 0x202);
+    }
+
+    /// Test that a halted processor doesn't execute anything
+    #[test]
+    fn test_step_halted() {
+        let mut e = Emulator {
+            state: CpuState::Halted,
+            ..Emulator::default()
+        };
+        assert_eq!(e.step(), Ok(StepResult::Halted));
+        assert_eq!(e.program_counter, 0x200);
+    }
+
+    /// Test fetching past the end of memory
+    #[test]
+    fn test_step_out_of_bounds() {
+        let mut e = Emulator {
+            program_counter: 0xFFF,
+            ..Emulator::default()
+        };
+        assert_eq!(
+            e.step(),
+            Err(EmulatorError::MemoryOutOfBounds { address: 0x1000 })
+        );
+        assert_eq!(e.program_counter, 0xFFF);
+    }
+
+    /// Test decoding an unknown opcode
+    #[test]
+    fn test_step_unknown_opcode() {
+        let mut e = Emulator::default();
+        e.memory[0x300..0x302].copy_from_slice(&[0xE1, 0x23]);
+        e.program_counter = 0x300;
+        assert_eq!(
+            e.step(),
+            Err(EmulatorError::UnknownOpcode {
+                address: 0x300,
+                opcode: 0xE123
+            })
+        );
+        assert_eq!(e.program_counter, 0x300);
+    }
+
+    /// Test that a waiting processor doesn't execute anything
+    #[test]
+    fn test_step_waiting_for_key() {
+        let mut e = Emulator {
+            state: CpuState::WaitingForKey { register: 0 },
+            ..Emulator::default()
+        };
+        assert_eq!(e.step(), Ok(StepResult::WaitingForKey));
+        assert_eq!(e.program_counter, 0x200);
+    }
+
+    /// Test running several cycles
+    #[test]
+    fn test_run_cycles() {
+        let mut e = Emulator::default();
+        e.load_rom(&[0x70, 0x01, 0x70, 0x01, 0x12, 0x00]).unwrap();
+        assert_eq!(
+            e.run_cycles(3),
+            Ok(RunResult {
+                executed: 3,
+                state: CpuState::Running
+            })
+        );
+        assert_eq!(e.get_reg(0), 2);
+        assert_eq!(e.program_counter, 0x200);
+    }
+
+    /// Test that FX0A blocks the processor until a key is pressed
+    #[test]
+    fn test_run_cycles_waiting_for_key() {
+        let mut e = Emulator::default();
+        e.load_rom(&[0xF1, 0x0A, 0x70, 0x01]).unwrap();
+        assert_eq!(
+            e.run_cycles(10),
+            Ok(RunResult {
+                executed: 1,
+                state: CpuState::WaitingForKey { register: 1 }
+            })
+        );
+        assert_eq!(e.program_counter, 0x202);
+        e.press_key(0x4);
+        e.step().unwrap();
+        assert_eq!(e.get_reg(1), 0x4);
+        assert_eq!(e.get_reg(0), 1);
+    }
+
+    /// Test running until the processor blocks
+    #[test]
+    fn test_run() {
+        let mut e = Emulator::default();
+        e.load_rom(&[0x1F, 0xFC]).unwrap();
+        e.memory[0xFFC..0x1000].copy_from_slice(&[0x60, 0x05, 0xF1, 0x0A]);
+        assert_eq!(
+            e.run(),
+            Ok(RunResult {
+                executed: 3,
+                state: CpuState::WaitingForKey { register: 1 }
+            })
+        );
+        assert_eq!(e.get_reg(0), 5);
+    }
+
+    /// Test that running stops at the first error
+    #[test]
+    fn test_run_error() {
+        let mut e = Emulator::default();
+        e.load_rom(&[0x70, 0x01, 0x00, 0xEE]).unwrap();
+        assert_eq!(e.run(), Err(EmulatorError::StackUnderflow));
+        assert_eq!(e.get_reg(0), 1);
+    }
+}
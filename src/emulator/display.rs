@@ -0,0 +1,372 @@
+/// Display width in pixels
+pub const WIDTH: usize = 64;
+/// Display height in pixels
+pub const HEIGHT: usize = 32;
+/// Display width in pixels in the SUPER-CHIP high resolution mode
+pub const HIRES_WIDTH: usize = 128;
+/// Display height in pixels in the SUPER-CHIP high resolution mode
+pub const HIRES_HEIGHT: usize = 64;
+
+/// Number of XO-CHIP bitplanes
+pub const PLANES: usize = 2;
+
+/// CHIP-8 display with XO-CHIP bitplanes
+///
+/// Pixels are stored row by row, starting from the top left corner.
+/// Each pixel is a bit mask of the planes it's lit in, so there are 4 colours.
+/// Drawing, scrolling and clearing only affect the selected planes, plane 1 by default.
+/// The display starts in the 64x32 low resolution mode and can be switched to 128x64.
+#[derive(Clone)]
+pub struct Display {
+    pixels: [u8; HIRES_WIDTH * HIRES_HEIGHT],
+    hires: bool,
+    planes: u8,
+}
+
+impl Display {
+    /// Display width in pixels
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            WIDTH
+        }
+    }
+
+    /// Display height in pixels
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            HEIGHT
+        }
+    }
+
+    /// Check if the display is in the high resolution mode
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Switch between the low and the high resolution modes.
+    ///
+    /// The display is cleared.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.clear();
+    }
+
+    /// Bit mask of the selected planes
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    /// Number of the selected planes
+    pub fn plane_count(&self) -> usize {
+        self.planes.count_ones() as usize
+    }
+
+    /// Select the planes affected by drawing, scrolling and clearing.
+    ///
+    /// Bit 0 of `planes` is plane 1, bit 1 is plane 2, other bits are ignored.
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ((1 << PLANES) - 1);
+    }
+
+    /// Check if pixel at (`x`, `y`) is lit in any plane
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.color(x, y) != 0
+    }
+
+    /// Colour of pixel at (`x`, `y`): a bit mask of the planes it's lit in
+    pub fn color(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width() + x]
+    }
+
+    /// Set colour of pixel at (`x`, `y`) to a bit mask of planes, regardless of the selected ones
+    pub fn set_color(&mut self, x: usize, y: usize, color: u8) {
+        let width = self.width();
+        self.pixels[y * width + x] = color & ((1 << PLANES) - 1);
+    }
+
+    /// Colours of all pixels, row by row
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels[..self.width() * self.height()]
+    }
+
+    /// Turn every pixel off in every plane
+    pub fn clear(&mut self) {
+        self.pixels = [0; HIRES_WIDTH * HIRES_HEIGHT];
+    }
+
+    /// Turn every pixel off in the selected planes
+    pub fn clear_planes(&mut self) {
+        let planes = self.planes;
+        for pixel in self.pixels.iter_mut() {
+            *pixel &= !planes;
+        }
+    }
+
+    /// XOR a sprite onto the selected planes.
+    ///
+    /// Each byte of `sprite` is a row of 8 pixels, most significant bit first.
+    /// With several planes selected, `sprite` holds the rows for each plane one after another,
+    /// starting with plane 1.
+    /// The starting coordinate wraps around the display.
+    /// The sprite itself is clipped at the edges, or wraps around them if `wrap` is set.
+    ///
+    /// Returns `true` if any pixel was turned off.
+    pub fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8], wrap: bool) -> bool {
+        let mut collision = false;
+        for (plane, rows) in self.plane_chunks(sprite, 1) {
+            let rows = rows.iter().map(|byte| (*byte as u16) << 8);
+            collision |= self.draw(x, y, rows, plane, wrap);
+        }
+        collision
+    }
+
+    /// XOR a 16 pixels wide sprite onto the selected planes.
+    ///
+    /// Each pair of bytes of `sprite` is a row, most significant bit of the first byte first.
+    /// Otherwise works like `draw_sprite`.
+    pub fn draw_large_sprite(&mut self, x: u8, y: u8, sprite: &[u8], wrap: bool) -> bool {
+        let mut collision = false;
+        for (plane, rows) in self.plane_chunks(sprite, 2) {
+            let rows = rows
+                .chunks(2)
+                .map(|row| (row[0] as u16) << 8 | *row.get(1).unwrap_or(&0) as u16);
+            collision |= self.draw(x, y, rows, plane, wrap);
+        }
+        collision
+    }
+
+    /// Split `sprite` between the selected planes, rows being `row_size` bytes long
+    fn plane_chunks<'a>(
+        &self,
+        sprite: &'a [u8],
+        row_size: usize,
+    ) -> impl Iterator<Item = (u8, &'a [u8])> + 'a {
+        let planes = self.planes;
+        let size = sprite.len() / self.plane_count().max(1) / row_size * row_size;
+        (0..PLANES)
+            .map(|plane| 1 << plane)
+            .filter(move |plane| planes & plane != 0)
+            .zip(sprite.chunks(size.max(1)))
+    }
+
+    /// XOR rows of up to 16 pixels onto `plane`, most significant bit first
+    fn draw<I: Iterator<Item = u16>>(
+        &mut self,
+        x: u8,
+        y: u8,
+        rows: I,
+        plane: u8,
+        wrap: bool,
+    ) -> bool {
+        let width = self.width();
+        let height = self.height();
+        let x = x as usize % width;
+        let y = y as usize % height;
+        let mut collision = false;
+        for (row, bits) in rows.enumerate() {
+            let mut py = y + row;
+            if py >= height {
+                if !wrap {
+                    break;
+                }
+                py %= height;
+            }
+            for bit in 0..16 {
+                let mut px = x + bit;
+                if px >= width {
+                    if !wrap {
+                        break;
+                    }
+                    px %= width;
+                }
+                if bits & (0x8000 >> bit) != 0 {
+                    let pixel = &mut self.pixels[py * width + px];
+                    collision |= *pixel & plane != 0;
+                    *pixel ^= plane;
+                }
+            }
+        }
+        collision
+    }
+
+    /// Move the picture in the selected planes `rows` pixels down, the top rows are turned off
+    pub fn scroll_down(&mut self, rows: usize) {
+        self.scroll(0, rows as isize);
+    }
+
+    /// Move the picture in the selected planes `columns` pixels left, the rightmost columns are turned off
+    pub fn scroll_left(&mut self, columns: usize) {
+        self.scroll(-(columns as isize), 0);
+    }
+
+    /// Move the picture in the selected planes `columns` pixels right, the leftmost columns are turned off
+    pub fn scroll_right(&mut self, columns: usize) {
+        self.scroll(columns as isize, 0);
+    }
+
+    /// Move the picture in the selected planes by (`dx`, `dy`), pixels moved off the display are lost
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let width = self.width() as isize;
+        let height = self.height() as isize;
+        let planes = self.planes;
+        let old = self.pixels;
+        self.clear_planes();
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+                if sx >= 0 && sx < width && sy >= 0 && sy < height {
+                    self.pixels[(y * width + x) as usize] |=
+                        old[(sy * width + sx) as usize] & planes;
+                }
+            }
+        }
+    }
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self {
+            pixels: [0; HIRES_WIDTH * HIRES_HEIGHT],
+            hires: false,
+            planes: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::display::Display;
+
+    /// Test drawing and erasing a sprite
+    #[test]
+    fn test_draw_sprite() {
+        let mut d = Display::default();
+        assert!(!d.draw_sprite(2, 3, &[0b1010_0000], false));
+        assert!(d.pixel(2, 3));
+        assert!(!d.pixel(3, 3));
+        assert!(d.pixel(4, 3));
+        assert!(d.draw_sprite(2, 3, &[0b1000_0000], false));
+        assert!(!d.pixel(2, 3));
+        assert!(d.pixel(4, 3));
+    }
+
+    /// Test that sprites are clipped at the edges and their position wraps
+    #[test]
+    fn test_draw_sprite_edges() {
+        let mut d = Display::default();
+        d.draw_sprite(62, 31, &[0xFF, 0xFF], false);
+        assert_eq!(d.pixels().iter().filter(|p| **p != 0).count(), 2);
+        assert!(d.pixel(62, 31));
+        assert!(d.pixel(63, 31));
+        d.clear();
+        d.draw_sprite(64 + 1, 32 + 1, &[0x80], false);
+        assert!(d.pixel(1, 1));
+    }
+
+    /// Test that sprites wrap around the edges when asked to
+    #[test]
+    fn test_draw_sprite_wrap() {
+        let mut d = Display::default();
+        d.draw_sprite(63, 31, &[0xC0, 0xC0], true);
+        assert!(d.pixel(63, 31));
+        assert!(d.pixel(0, 31));
+        assert!(d.pixel(63, 0));
+        assert!(d.pixel(0, 0));
+        assert_eq!(d.pixels().iter().filter(|p| **p != 0).count(), 4);
+    }
+
+    /// Test drawing a 16x16 sprite in the high resolution mode
+    #[test]
+    fn test_draw_large_sprite() {
+        let mut d = Display::default();
+        d.set_hires(true);
+        let sprite = [0x80, 0x01].repeat(16);
+        assert!(!d.draw_large_sprite(100, 40, &sprite, false));
+        assert!(d.pixel(100, 40));
+        assert!(d.pixel(115, 55));
+        assert!(!d.pixel(101, 40));
+        assert_eq!(d.pixels().iter().filter(|p| **p != 0).count(), 32);
+        assert!(d.draw_large_sprite(115, 40, &sprite, false));
+        assert!(!d.pixel(115, 40));
+        assert_eq!(d.pixels().iter().filter(|p| **p != 0).count(), 16);
+    }
+
+    /// Test switching resolution
+    #[test]
+    fn test_set_hires() {
+        let mut d = Display::default();
+        d.draw_sprite(0, 0, &[0xFF], false);
+        d.set_hires(true);
+        assert_eq!((d.width(), d.height()), (128, 64));
+        assert_eq!(d.pixels().len(), 128 * 64);
+        assert!(d.pixels().iter().all(|p| *p == 0));
+        d.draw_sprite(127, 63, &[0x80], false);
+        assert!(d.pixel(127, 63));
+        d.set_hires(false);
+        assert_eq!((d.width(), d.height()), (64, 32));
+        assert!(d.pixels().iter().all(|p| *p == 0));
+    }
+
+    /// Test scrolling in every direction
+    #[test]
+    fn test_scroll() {
+        let mut d = Display::default();
+        d.draw_sprite(0, 0, &[0x80], false);
+        d.draw_sprite(63, 31, &[0x80], false);
+        d.scroll_down(2);
+        assert!(d.pixel(0, 2));
+        assert_eq!(d.pixels().iter().filter(|p| **p != 0).count(), 1);
+        d.scroll_right(4);
+        assert!(d.pixel(4, 2));
+        d.scroll_left(4);
+        assert!(d.pixel(0, 2));
+        d.scroll_left(4);
+        assert!(d.pixels().iter().all(|p| *p == 0));
+    }
+
+    /// Test clearing the display
+    #[test]
+    fn test_clear() {
+        let mut d = Display::default();
+        d.draw_sprite(0, 0, &[0xFF], false);
+        d.clear();
+        assert!(d.pixels().iter().all(|p| *p == 0));
+    }
+
+    /// Test drawing on both planes
+    #[test]
+    fn test_planes() {
+        let mut d = Display::default();
+        d.select_planes(0b11);
+        assert_eq!(d.plane_count(), 2);
+        assert!(!d.draw_sprite(0, 0, &[0xC0, 0x80], false));
+        assert_eq!(d.color(0, 0), 0b11);
+        assert_eq!(d.color(1, 0), 0b01);
+        d.select_planes(0b10);
+        assert!(d.draw_sprite(0, 0, &[0x80], false));
+        assert_eq!(d.color(0, 0), 0b01);
+        d.draw_sprite(1, 0, &[0x80], false);
+        d.clear_planes();
+        assert_eq!(d.color(0, 0), 0b01);
+        assert_eq!(d.color(1, 0), 0b01);
+        d.select_planes(0);
+        assert!(!d.draw_sprite(0, 0, &[0x80], false));
+        assert_eq!(d.color(0, 0), 0b01);
+    }
+
+    /// Test scrolling a single plane
+    #[test]
+    fn test_scroll_plane() {
+        let mut d = Display::default();
+        d.select_planes(0b11);
+        d.draw_sprite(0, 0, &[0x80, 0x80], false);
+        d.select_planes(0b10);
+        d.scroll_down(1);
+        assert_eq!(d.color(0, 0), 0b01);
+        assert_eq!(d.color(0, 1), 0b10);
+    }
+}
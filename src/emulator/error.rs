@@ -0,0 +1,37 @@
+use std::error::Error;
+use std::fmt;
+
+/// Error raised by a misbehaving program
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum EmulatorError {
+    /// Bytes at `address` don't form a known opcode
+    UnknownOpcode { address: u16, opcode: u16 },
+    /// `Return` was executed with an empty stack
+    StackUnderflow,
+    /// `Subroutine` was executed with a full stack
+    StackOverflow,
+    /// Program accessed memory past its end, `address` is the first byte that doesn't exist
+    MemoryOutOfBounds { address: usize },
+    /// Program tried to run machine code at `target` (`0x0NNN`)
+    NativeCall { target: u16 },
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmulatorError::UnknownOpcode { address, opcode } => {
+                write!(f, "unknown opcode {:04X} at {:#05X}", opcode, address)
+            }
+            EmulatorError::StackUnderflow => write!(f, "return with an empty stack"),
+            EmulatorError::StackOverflow => write!(f, "subroutine call with a full stack"),
+            EmulatorError::MemoryOutOfBounds { address } => {
+                write!(f, "memory access at {:#05X} is out of bounds", address)
+            }
+            EmulatorError::NativeCall { target } => {
+                write!(f, "machine code call to {:#05X} is not supported", target)
+            }
+        }
+    }
+}
+
+impl Error for EmulatorError {}
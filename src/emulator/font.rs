@@ -0,0 +1,268 @@
+use crate::emulator::error::EmulatorError;
+use crate::emulator::Emulator;
+
+/// Default address of the font in interpreter memory
+pub const FONT_ADDRESS: u16 = 0x050;
+/// Default address of the large font in interpreter memory, right after the font
+pub const LARGE_FONT_ADDRESS: u16 = 0x0A0;
+
+/// Hexadecimal font: 16 glyphs for characters `0x0..=0xF`, 4 pixels wide and 5 pixels high.
+///
+/// Each byte is a row of a glyph, its 4 most significant bits are the pixels.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Font {
+    /// Glyph rows, 5 bytes per character
+    pub glyphs: [u8; 16 * Font::GLYPH_SIZE],
+}
+
+impl Font {
+    /// Size of a single glyph in bytes
+    pub const GLYPH_SIZE: usize = 5;
+
+    /// Font used by most modern interpreters
+    pub const CHIP8: Font = Font {
+        glyphs: [
+            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+            0x20, 0x60, 0x20, 0x20, 0x70, // 1
+            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+            0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+        ],
+    };
+
+    /// Font of the original COSMAC VIP interpreter
+    pub const VIP: Font = Font {
+        glyphs: [
+            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+            0x60, 0x20, 0x20, 0x20, 0x70, // 1
+            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+            0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+            0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+            0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+            0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+            0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+        ],
+    };
+
+    /// Font of the DREAM 6800 interpreter (3 pixels wide)
+    pub const DREAM_6800: Font = Font {
+        glyphs: [
+            0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+            0x40, 0x40, 0x40, 0x40, 0x40, // 1
+            0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+            0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+            0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+            0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+            0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+            0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+            0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+            0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+            0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+            0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+            0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+            0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+            0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+            0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+        ],
+    };
+
+    /// Font of the ETI-660 interpreter (3 pixels wide)
+    pub const ETI_660: Font = Font {
+        glyphs: [
+            0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+            0x20, 0x20, 0x20, 0x20, 0x20, // 1
+            0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+            0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+            0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+            0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+            0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+            0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+            0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+            0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+            0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+            0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+            0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+            0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+            0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+            0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+        ],
+    };
+
+    /// Rows of the glyph for the low nibble of `character`
+    pub fn glyph(&self, character: u8) -> &[u8] {
+        let start = (character & 0xF) as usize * Self::GLYPH_SIZE;
+        &self.glyphs[start..start + Self::GLYPH_SIZE]
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self::CHIP8
+    }
+}
+
+/// Large hexadecimal font used by SUPER-CHIP `FX30`: 16 glyphs, 8 pixels wide and 10 pixels high.
+///
+/// Each byte is a row of a glyph.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct LargeFont {
+    /// Glyph rows, 10 bytes per character
+    pub glyphs: [u8; 16 * LargeFont::GLYPH_SIZE],
+}
+
+impl LargeFont {
+    /// Size of a single glyph in bytes
+    pub const GLYPH_SIZE: usize = 10;
+
+    /// Digits of the SUPER-CHIP 1.1 interpreter, which has no letters, and letters of Octo
+    pub const SCHIP: LargeFont = LargeFont {
+        glyphs: [
+            0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+            0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+            0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+            0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+            0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+            0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+            0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+            0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+            0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+            0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+            0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+            0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+            0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+        ],
+    };
+
+    /// Rows of the glyph for the low nibble of `character`
+    pub fn glyph(&self, character: u8) -> &[u8] {
+        let start = (character & 0xF) as usize * Self::GLYPH_SIZE;
+        &self.glyphs[start..start + Self::GLYPH_SIZE]
+    }
+}
+
+impl Default for LargeFont {
+    fn default() -> Self {
+        Self::SCHIP
+    }
+}
+
+impl Emulator {
+    /// Copy `font` into memory at `address` and use it for `FX29`
+    ///
+    /// Fails if the font doesn't fit into memory, nothing is changed in that case
+    pub fn load_font(&mut self, font: &Font, address: u16) -> Result<(), EmulatorError> {
+        self.write_memory(address, &font.glyphs)?;
+        self.font = *font;
+        self.font_address = address;
+        Ok(())
+    }
+
+    /// Address of the glyph for the low nibble of `character`
+    pub fn char_address(&self, character: u8) -> u16 {
+        self.font_address
+            .wrapping_add((character & 0xF) as u16 * Font::GLYPH_SIZE as u16)
+    }
+
+    /// Copy `font` into memory at `address` and use it for `FX30`
+    ///
+    /// Fails if the font doesn't fit into memory, nothing is changed in that case
+    pub fn load_large_font(&mut self, font: &LargeFont, address: u16) -> Result<(), EmulatorError> {
+        self.write_memory(address, &font.glyphs)?;
+        self.large_font = *font;
+        self.large_font_address = address;
+        Ok(())
+    }
+
+    /// Address of the large glyph for the low nibble of `character`
+    pub fn large_char_address(&self, character: u8) -> u16 {
+        self.large_font_address
+            .wrapping_add((character & 0xF) as u16 * LargeFont::GLYPH_SIZE as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::error::EmulatorError;
+    use crate::emulator::font::{Font, LargeFont, FONT_ADDRESS, LARGE_FONT_ADDRESS};
+    use crate::emulator::Emulator;
+
+    /// Test that the default font is loaded on creation
+    #[test]
+    fn test_default_font() {
+        let e = Emulator::default();
+        assert_eq!(e.font_address, FONT_ADDRESS);
+        let start = FONT_ADDRESS as usize;
+        assert_eq!(e.memory[start..start + 80], Font::CHIP8.glyphs[..]);
+        assert_eq!(e.large_font_address, LARGE_FONT_ADDRESS);
+        let start = LARGE_FONT_ADDRESS as usize;
+        assert_eq!(e.memory[start..start + 160], LargeFont::SCHIP.glyphs[..]);
+    }
+
+    /// Test loading an alternate font at a custom address
+    #[test]
+    fn test_load_font() {
+        let mut e = Emulator::default();
+        e.load_font(&Font::ETI_660, 0x100).unwrap();
+        assert_eq!(e.char_address(0xB), 0x100 + 0xB * 5);
+        assert_eq!(e.memory[0x137..0x13C], *Font::ETI_660.glyph(0xB));
+    }
+
+    /// Test rejecting fonts that don't fit into memory
+    #[test]
+    fn test_load_font_out_of_bounds() {
+        let mut e = Emulator::default();
+        assert_eq!(
+            e.load_font(&Font::ETI_660, 0xFFB0),
+            Err(EmulatorError::MemoryOutOfBounds { address: 0xFFB0 })
+        );
+        assert_eq!(
+            e.load_large_font(&LargeFont::SCHIP, 0x0F80),
+            Err(EmulatorError::MemoryOutOfBounds { address: 0x1000 })
+        );
+        assert_eq!(e.font, Font::CHIP8);
+        assert_eq!(e.font_address, FONT_ADDRESS);
+        assert_eq!(e.large_font_address, LARGE_FONT_ADDRESS);
+        assert!(e.memory[0x0F80..].iter().all(|byte| *byte == 0));
+    }
+
+    /// Test large glyph lookup
+    #[test]
+    fn test_large_char_address() {
+        let e = Emulator::default();
+        let address = e.large_char_address(0x7) as usize;
+        assert_eq!(address, 0x0A0 + 0x7 * 10);
+        assert_eq!(
+            e.memory[address..address + 10],
+            *LargeFont::SCHIP.glyph(0x7)
+        );
+    }
+
+    /// Test glyph lookup
+    #[test]
+    fn test_glyph() {
+        assert_eq!(Font::CHIP8.glyph(0x1), [0x20, 0x60, 0x20, 0x20, 0x70]);
+        assert_eq!(Font::CHIP8.glyph(0x21), Font::CHIP8.glyph(0x1));
+    }
+}
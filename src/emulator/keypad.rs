@@ -0,0 +1,107 @@
+use crate::emulator::cycle::CpuState;
+use crate::emulator::Emulator;
+
+/// Hexadecimal keypad with keys `0x0..=0xF`
+///
+/// Only the low nibble of a key is used, like on the original hardware.
+#[derive(Clone, Default)]
+pub struct Keypad {
+    keys: [bool; 16],
+}
+
+impl Keypad {
+    /// Check if `key` is held down
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.keys[(key & 0xF) as usize]
+    }
+
+    /// Hold `key` down
+    pub fn press(&mut self, key: u8) {
+        self.keys[(key & 0xF) as usize] = true;
+    }
+
+    /// Let `key` go
+    pub fn release(&mut self, key: u8) {
+        self.keys[(key & 0xF) as usize] = false;
+    }
+
+    /// Let every key go
+    pub fn release_all(&mut self) {
+        self.keys = [false; 16];
+    }
+}
+
+impl Emulator {
+    /// Press `key` on the keypad.
+    ///
+    /// Resumes the processor if it awaits a key press.
+    pub fn press_key(&mut self, key: u8) {
+        self.keypad.press(key);
+        if !self.quirks.key_latch_on_release {
+            self.latch_key(key);
+        }
+    }
+
+    /// Release `key` on the keypad.
+    ///
+    /// Resumes the processor if it awaits a key and the `key_latch_on_release` quirk is on.
+    pub fn release_key(&mut self, key: u8) {
+        let was_pressed = self.keypad.is_pressed(key);
+        self.keypad.release(key);
+        if self.quirks.key_latch_on_release && was_pressed {
+            self.latch_key(key);
+        }
+    }
+
+    /// Store `key` into the awaiting register and resume the processor
+    fn latch_key(&mut self, key: u8) {
+        if let CpuState::WaitingForKey { register } = self.state {
+            self.set_reg(register, key & 0xF);
+            self.state = CpuState::Running;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::cycle::CpuState;
+    use crate::emulator::Emulator;
+
+    /// Test pressing and releasing keys
+    #[test]
+    fn test_press_release() {
+        let mut e = Emulator::default();
+        e.press_key(0xA);
+        assert!(e.keypad.is_pressed(0xA));
+        assert!(!e.keypad.is_pressed(0xB));
+        e.release_key(0xA);
+        assert!(!e.keypad.is_pressed(0xA));
+    }
+
+    /// Test latching the awaited key on press
+    #[test]
+    fn test_latch_on_press() {
+        let mut e = Emulator {
+            state: CpuState::WaitingForKey { register: 3 },
+            ..Emulator::default()
+        };
+        e.press_key(0x7);
+        assert_eq!(e.state, CpuState::Running);
+        assert_eq!(e.get_reg(3), 0x7);
+    }
+
+    /// Test latching the awaited key on release
+    #[test]
+    fn test_latch_on_release() {
+        let mut e = Emulator::default();
+        e.quirks.key_latch_on_release = true;
+        e.state = CpuState::WaitingForKey { register: 3 };
+        e.press_key(0x7);
+        assert_eq!(e.state, CpuState::WaitingForKey { register: 3 });
+        e.release_key(0x8);
+        assert_eq!(e.state, CpuState::WaitingForKey { register: 3 });
+        e.release_key(0x7);
+        assert_eq!(e.state, CpuState::Running);
+        assert_eq!(e.get_reg(3), 0x7);
+    }
+}
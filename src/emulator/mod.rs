@@ -1,20 +1,57 @@
-use crate::emulator::random::RNG;
+use crate::emulator::audio::Audio;
+use crate::emulator::cycle::CpuState;
+use crate::emulator::display::Display;
+use crate::emulator::font::{Font, LargeFont, FONT_ADDRESS, LARGE_FONT_ADDRESS};
+use crate::emulator::keypad::Keypad;
+use crate::emulator::quirks::Quirks;
+use crate::emulator::random::{RandomSource, RNG};
+use crate::emulator::stack::Stack;
+use crate::emulator::timers::TimerClock;
+use crate::emulator::variant::Variant;
 
+/// Contains XO-CHIP audio
+pub mod audio;
 /// Contains opcode execution logic
 pub mod command_execution;
+/// Contains fetch-decode-execute loop
+pub mod cycle;
+/// Contains display logic
+pub mod display;
+/// Contains emulation errors
+pub mod error;
+/// Contains hexadecimal font
+pub mod font;
+/// Contains keypad logic
+pub mod keypad;
+/// Contains input recording and playback
+pub mod movie;
 /// Contains CHIP-8 Opcodes
 pub mod opcode;
+/// Contains interpreter quirks
+pub mod quirks;
 /// Contains RNG logic
 pub mod random;
 /// Contains register operation logic
 pub mod reg_ops;
+/// Contains rewind history
+pub mod rewind;
+/// Contains ROM loading logic
+pub mod rom;
+/// Contains save states
+pub mod save_state;
+/// Contains call stack
+pub mod stack;
+/// Contains timer logic
+pub mod timers;
+/// Contains machine variants
+pub mod variant;
 /// CHIP-8 Emulator
 pub struct Emulator {
-    /// Represents CHIP-8 memory: 4096 bytes
+    /// Represents CHIP-8 memory: 4096 bytes, 65536 bytes on XO-CHIP
     ///
     /// Bytes 0x000-0x200 - Interpreter space
-    /// Bytes 0x200-0xFFF - Working ROM and RAM
-    pub memory: [u8; 4096],
+    /// Bytes 0x200-0xFFF - Working ROM and RAM (up to 0xFFFF on XO-CHIP)
+    pub memory: Vec<u8>,
     /// Represents CHIP-8 registers
     ///
     /// There are 16 registers
@@ -28,26 +65,81 @@ pub struct Emulator {
     /// Points to a current instruction
     pub program_counter: u16,
     /// Stack for basic subroutine calls
-    pub stack: Vec<u16>,
+    pub stack: Stack,
     /// Delay Timer
     pub delay_timer: u8,
     /// Sound timer
     pub sound_timer: u8,
-    /// RNG
-    pub rng: RNG,
+    /// Timer subsystem state
+    pub timer_clock: TimerClock,
+    /// Source of random bytes for `CXNN`, seeded from system entropy by default
+    pub rng: Box<dyn RandomSource + Send>,
+    /// Processor state
+    pub state: CpuState,
+    /// Display
+    pub display: Display,
+    /// Keypad
+    pub keypad: Keypad,
+    /// XO-CHIP audio pattern and pitch
+    pub audio: Audio,
+    /// Hexadecimal font, reloaded on reset
+    pub font: Font,
+    /// Address of the hexadecimal font used by `FX29`
+    pub font_address: u16,
+    /// Large hexadecimal font, reloaded on reset
+    pub large_font: LargeFont,
+    /// Address of the large hexadecimal font used by `FX30`
+    pub large_font_address: u16,
+    /// SUPER-CHIP user flags (HP-48 RPL flags) used by `FX75`/`FX85`
+    ///
+    /// They outlive the program, so they are kept on reset.
+    pub rpl_flags: [u8; 16],
+    /// Address where programs are loaded and start executing
+    pub load_address: u16,
+    /// Emulated machine variant
+    pub variant: Variant,
+    /// Behaviour of ambiguous instructions
+    pub quirks: Quirks,
 }
 
-impl Default for Emulator {
-    fn default() -> Self {
-        Self {
-            memory: [0; 4096],
+impl Emulator {
+    /// Make a machine of the given `variant`
+    pub fn new(variant: Variant) -> Self {
+        let mut emulator = Self {
+            memory: vec![0; variant.memory_size()],
             registers: [0; 16],
             index_register: 0,
-            program_counter: 0,
-            stack: Vec::with_capacity(12),
+            program_counter: variant.load_address(),
+            stack: Stack::new(variant.stack_depth()),
             delay_timer: 0,
             sound_timer: 0,
-            rng: RNG::default(),
-        }
+            timer_clock: TimerClock::default(),
+            rng: Box::new(RNG::default()),
+            state: CpuState::Running,
+            display: Display::default(),
+            keypad: Keypad::default(),
+            audio: Audio::default(),
+            font: Font::default(),
+            font_address: FONT_ADDRESS,
+            large_font: LargeFont::default(),
+            large_font_address: LARGE_FONT_ADDRESS,
+            rpl_flags: [0; 16],
+            load_address: variant.load_address(),
+            variant,
+            quirks: variant.quirks(),
+        };
+        emulator
+            .load_font(&Font::default(), FONT_ADDRESS)
+            .expect("the font fits into every memory size");
+        emulator
+            .load_large_font(&LargeFont::default(), LARGE_FONT_ADDRESS)
+            .expect("the large font fits into every memory size");
+        emulator
+    }
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new(Variant::default())
     }
 }
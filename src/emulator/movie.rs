@@ -0,0 +1,518 @@
+use crate::emulator::error::EmulatorError;
+use crate::emulator::quirks::Quirks;
+use crate::emulator::random::{RandomSource, VipRNG};
+use crate::emulator::variant::Variant;
+use crate::emulator::Emulator;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// First bytes of every movie
+pub const MAGIC: [u8; 4] = *b"C8MV";
+
+/// Version of the format written by `Movie::to_bytes`
+pub const VERSION: u16 = 2;
+
+/// 64-bit FNV-1a hash of `rom`, which identifies the ROM a movie was recorded with
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+/// Error raised when a movie can't be read or played
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum MovieError {
+    /// Data doesn't start with `MAGIC`
+    BadMagic,
+    /// Data was written by a newer, incompatible version of the format
+    UnsupportedVersion { version: u16 },
+    /// Data ends too early
+    Truncated,
+    /// Data holds a value that doesn't make sense
+    Invalid,
+    /// Movie was recorded with another ROM
+    RomMismatch { expected: u64, actual: u64 },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::BadMagic => write!(f, "not a movie"),
+            MovieError::UnsupportedVersion { version } => {
+                write!(f, "movie version {} is not supported", version)
+            }
+            MovieError::Truncated => write!(f, "movie is truncated"),
+            MovieError::Invalid => write!(f, "movie is invalid"),
+            MovieError::RomMismatch { expected, actual } => write!(
+                f,
+                "movie was recorded with ROM {:016X}, not {:016X}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+/// Change of a key's state at the start of a frame
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct InputEvent {
+    /// Number of the frame, starting from 0
+    pub frame: u64,
+    /// Key that changed
+    pub key: u8,
+    /// `true` if the key was pressed, `false` if it was released
+    pub pressed: bool,
+}
+
+/// Recording of a run: everything needed to reproduce it from power-on.
+///
+/// A frame consists of applying the frame's input events through the keypad API,
+/// running `cycles_per_frame` cycles and ticking the timers once.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Movie {
+    /// Hash of the ROM, see `rom_hash`
+    pub rom_hash: u64,
+    /// Machine variant
+    pub variant: Variant,
+    /// Quirk profile
+    pub quirks: Quirks,
+    /// Seed of the default random source
+    pub seed: u64,
+    /// COSMAC VIP random source, used instead of the default one if set
+    pub vip_rng: Option<VipRNG>,
+    /// Cycles run per frame
+    pub cycles_per_frame: usize,
+    /// Number of recorded frames
+    pub frames: u64,
+    /// Key state changes, ordered by frame
+    pub events: Vec<InputEvent>,
+}
+
+impl Movie {
+    /// Make an empty movie of `rom` running on `variant` with `quirks` and random `seed`
+    pub fn new(
+        rom: &[u8],
+        variant: Variant,
+        quirks: Quirks,
+        seed: u64,
+        cycles_per_frame: usize,
+    ) -> Self {
+        Self {
+            rom_hash: rom_hash(rom),
+            variant,
+            quirks,
+            seed,
+            vip_rng: None,
+            cycles_per_frame,
+            frames: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Make a machine in the power-on state of the movie with `rom` loaded
+    ///
+    /// # Errors:
+    /// Fails with `RomMismatch` if `rom` isn't the one the movie was recorded with,
+    /// or with `Invalid` if it doesn't fit into memory.
+    pub fn start(&self, rom: &[u8]) -> Result<Emulator, MovieError> {
+        let actual = rom_hash(rom);
+        if actual != self.rom_hash {
+            return Err(MovieError::RomMismatch {
+                expected: self.rom_hash,
+                actual,
+            });
+        }
+        let mut emulator = Emulator::new(self.variant);
+        emulator.quirks = self.quirks;
+        match self.vip_rng {
+            Some(vip_rng) => emulator.rng = Box::new(vip_rng),
+            None => emulator.seed_rng(self.seed),
+        }
+        emulator.load_rom(rom).map_err(|_| MovieError::Invalid)?;
+        Ok(emulator)
+    }
+
+    /// Serialize the movie.
+    ///
+    /// Integers are little endian: `MAGIC`, `u16` version, `u64` ROM hash, `u8` variant code,
+    /// quirks, `u64` seed, `u8` flag followed by the `VipRNG` state if it's set,
+    /// `u32` cycles per frame, `u64` frame count, `u32` event count,
+    /// and every event as `u64` frame, `u8` key, `u8` pressed flag.
+    ///
+    /// # Errors:
+    /// Fails with `Invalid` if the cycles per frame or the event count don't fit into `u32`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MovieError> {
+        let cycles_per_frame =
+            u32::try_from(self.cycles_per_frame).map_err(|_| MovieError::Invalid)?;
+        let count = u32::try_from(self.events.len()).map_err(|_| MovieError::Invalid)?;
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.rom_hash.to_le_bytes());
+        out.push(self.variant.code());
+        out.extend_from_slice(&self.quirks.to_bytes());
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        match self.vip_rng {
+            Some(vip_rng) => {
+                out.push(1);
+                out.extend_from_slice(&vip_rng.save());
+            }
+            None => out.push(0),
+        }
+        out.extend_from_slice(&cycles_per_frame.to_le_bytes());
+        out.extend_from_slice(&self.frames.to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        for event in &self.events {
+            out.extend_from_slice(&event.frame.to_le_bytes());
+            out.push(event.key);
+            out.push(event.pressed as u8);
+        }
+        Ok(out)
+    }
+
+    /// Read a movie serialized by `to_bytes`
+    ///
+    /// # Errors:
+    /// Fails with `Invalid` if the events aren't ordered by frame.
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, MovieError> {
+        let mut cursor = Cursor(data);
+        if cursor.bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(MovieError::BadMagic);
+        }
+        let version = cursor.uint(2)? as u16;
+        if version > VERSION {
+            return Err(MovieError::UnsupportedVersion { version });
+        }
+        let rom_hash = cursor.uint(8)?;
+        let variant = Variant::from_code(cursor.uint(1)? as u8).ok_or(MovieError::Invalid)?;
+        let mut quirks = [0; Quirks::SIZE];
+        quirks.copy_from_slice(cursor.bytes(Quirks::SIZE)?);
+        let quirks = Quirks::from_bytes(quirks).ok_or(MovieError::Invalid)?;
+        let seed = cursor.uint(8)?;
+        let has_vip_rng = if version >= 2 { cursor.uint(1)? } else { 0 };
+        let vip_rng = match has_vip_rng {
+            0 => None,
+            1 => {
+                let mut vip_rng = VipRNG::new([0; 256], 0);
+                vip_rng.restore(cursor.bytes(256 + 2)?);
+                Some(vip_rng)
+            }
+            _ => return Err(MovieError::Invalid),
+        };
+        let cycles_per_frame = cursor.uint(4)? as usize;
+        let frames = cursor.uint(8)?;
+        let count = cursor.uint(4)?;
+        let mut events: Vec<InputEvent> = Vec::new();
+        for _ in 0..count {
+            let frame = cursor.uint(8)?;
+            let key = cursor.uint(1)? as u8;
+            let pressed = match cursor.uint(1)? {
+                0 => false,
+                1 => true,
+                _ => return Err(MovieError::Invalid),
+            };
+            let previous = events.last().map_or(0, |event| event.frame);
+            if key > 0xF || frame >= frames || frame < previous {
+                return Err(MovieError::Invalid);
+            }
+            events.push(InputEvent {
+                frame,
+                key,
+                pressed,
+            });
+        }
+        Ok(Movie {
+            rom_hash,
+            variant,
+            quirks,
+            seed,
+            vip_rng,
+            cycles_per_frame,
+            frames,
+            events,
+        })
+    }
+}
+
+/// Cursor over serialized movie data
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], MovieError> {
+        if self.0.len() < len {
+            return Err(MovieError::Truncated);
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    /// Little endian unsigned integer of `len` bytes, up to 8
+    fn uint(&mut self, len: usize) -> Result<u64, MovieError> {
+        let mut bytes = [0; 8];
+        bytes[..len].copy_from_slice(self.bytes(len)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+/// Run a single movie frame: `cycles` cycles, then a timer tick
+fn run_frame(emulator: &mut Emulator, cycles: usize) -> Result<(), EmulatorError> {
+    emulator.run_cycles(cycles)?;
+    emulator.tick_timers();
+    Ok(())
+}
+
+/// Records the key presses of a run into a movie
+#[derive(Clone, Debug)]
+pub struct Recorder {
+    movie: Movie,
+    pending: Vec<(u8, bool)>,
+}
+
+impl Recorder {
+    /// Start recording into `movie`, whose machine is made by `Movie::start`
+    pub fn new(movie: Movie) -> Self {
+        Self {
+            movie,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Press `key` at the start of the next frame
+    pub fn press_key(&mut self, key: u8) {
+        self.pending.push((key & 0xF, true));
+    }
+
+    /// Release `key` at the start of the next frame
+    pub fn release_key(&mut self, key: u8) {
+        self.pending.push((key & 0xF, false));
+    }
+
+    /// Apply the pending key changes to `emulator` and run a frame, recording both.
+    ///
+    /// Only changes of the key state are recorded.
+    pub fn run_frame(&mut self, emulator: &mut Emulator) -> Result<(), EmulatorError> {
+        let frame = self.movie.frames;
+        for (key, pressed) in self.pending.drain(..) {
+            if emulator.keypad.is_pressed(key) == pressed {
+                continue;
+            }
+            if pressed {
+                emulator.press_key(key);
+            } else {
+                emulator.release_key(key);
+            }
+            self.movie.events.push(InputEvent {
+                frame,
+                key,
+                pressed,
+            });
+        }
+        self.movie.frames += 1;
+        run_frame(emulator, self.movie.cycles_per_frame)
+    }
+
+    /// Stop recording
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+/// Plays a movie back by feeding its inputs to a machine
+#[derive(Clone, Debug)]
+pub struct Player<'a> {
+    movie: &'a Movie,
+    frame: u64,
+    next_event: usize,
+}
+
+impl<'a> Player<'a> {
+    /// Start playing `movie` on a machine made by `Movie::start`
+    pub fn new(movie: &'a Movie) -> Self {
+        Self {
+            movie,
+            frame: 0,
+            next_event: 0,
+        }
+    }
+
+    /// Number of the next frame to play
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Check if every frame of the movie was played
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames
+    }
+
+    /// Apply the inputs of the next frame to `emulator` and run it.
+    ///
+    /// Events left over from earlier frames, which only occur if `events` isn't ordered,
+    /// are applied as well.
+    /// Returns `false` without running anything if the movie is over.
+    pub fn run_frame(&mut self, emulator: &mut Emulator) -> Result<bool, EmulatorError> {
+        if self.is_finished() {
+            return Ok(false);
+        }
+        while let Some(event) = self.movie.events.get(self.next_event) {
+            if event.frame > self.frame {
+                break;
+            }
+            if event.pressed {
+                emulator.press_key(event.key);
+            } else {
+                emulator.release_key(event.key);
+            }
+            self.next_event += 1;
+        }
+        self.frame += 1;
+        run_frame(emulator, self.movie.cycles_per_frame)?;
+        Ok(true)
+    }
+
+    /// Play every remaining frame
+    pub fn run_to_end(&mut self, emulator: &mut Emulator) -> Result<(), EmulatorError> {
+        while self.run_frame(emulator)? {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::movie::{rom_hash, Movie, MovieError, Player, Recorder, MAGIC};
+    use crate::emulator::quirks::Quirks;
+    use crate::emulator::random::{RandomSource, VipRNG};
+    use crate::emulator::variant::Variant;
+    use std::convert::TryFrom;
+
+    /// Program that waits for a key, then draws a random sprite at a random position forever
+    const ROM: [u8; 14] = [
+        0xF0, 0x0A, 0xC1, 0x3F, 0xC2, 0x1F, 0xF0, 0x29, 0xD1, 0x25, 0x70, 0x01, 0x12, 0x02,
+    ];
+
+    /// Record a short run
+    fn record() -> (Movie, Vec<u8>) {
+        record_movie(Movie::new(
+            &ROM,
+            Variant::CosmacVip,
+            Quirks::COSMAC_VIP,
+            99,
+            10,
+        ))
+    }
+
+    /// Record a short run into `movie`
+    fn record_movie(movie: Movie) -> (Movie, Vec<u8>) {
+        let mut e = movie.start(&ROM).unwrap();
+        let mut r = Recorder::new(movie);
+        for frame in 0..30 {
+            if frame == 5 {
+                r.press_key(0x3);
+                r.press_key(0x3);
+            }
+            if frame == 7 {
+                r.release_key(0x3);
+            }
+            r.run_frame(&mut e).unwrap();
+        }
+        (r.finish(), e.save_state())
+    }
+
+    /// Test that playing a movie reproduces the recorded run exactly
+    #[test]
+    fn test_playback() {
+        let (movie, recorded) = record();
+        assert_eq!(movie.frames, 30);
+        assert_eq!(movie.events.len(), 2);
+        let mut e = movie.start(&ROM).unwrap();
+        let mut p = Player::new(&movie);
+        p.run_to_end(&mut e).unwrap();
+        assert!(p.is_finished());
+        assert_eq!(p.run_frame(&mut e), Ok(false));
+        assert_eq!(e.save_state(), recorded);
+    }
+
+    /// Test recording and playing with the COSMAC VIP random source
+    #[test]
+    fn test_vip_rng() {
+        let mut page = [0; 256];
+        for (i, byte) in page.iter_mut().enumerate() {
+            *byte = (i * 37) as u8;
+        }
+        let vip_rng = VipRNG::new(page, 0x1234);
+        let mut movie = Movie::new(&ROM, Variant::CosmacVip, Quirks::COSMAC_VIP, 99, 10);
+        movie.vip_rng = Some(vip_rng);
+        let (movie, recorded) = record_movie(movie);
+        let movie = Movie::from_bytes(&movie.to_bytes().unwrap()).unwrap();
+        assert_eq!(movie.vip_rng, Some(vip_rng));
+        let mut e = movie.start(&ROM).unwrap();
+        assert_eq!(e.rng.save(), vip_rng.save());
+        Player::new(&movie).run_to_end(&mut e).unwrap();
+        assert_eq!(e.save_state(), recorded);
+    }
+
+    /// Test reading a version 1 movie, which has no random source flag
+    #[test]
+    fn test_version_1() {
+        let (movie, _) = record();
+        let mut bytes = movie.to_bytes().unwrap();
+        bytes[4] = 1;
+        let flag = MAGIC.len() + 2 + 8 + 1 + Quirks::SIZE + 8;
+        bytes.remove(flag);
+        assert_eq!(Movie::from_bytes(&bytes), Ok(movie));
+    }
+
+    /// Test serializing a movie
+    #[test]
+    fn test_bytes() {
+        let (movie, _) = record();
+        let bytes = movie.to_bytes().unwrap();
+        assert_eq!(Movie::from_bytes(&bytes), Ok(movie.clone()));
+        assert_eq!(
+            Movie::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(MovieError::Truncated)
+        );
+        assert_eq!(Movie::from_bytes(b"C8SS"), Err(MovieError::BadMagic));
+        if let Ok(cycles_per_frame) = usize::try_from(u64::from(u32::MAX) + 1) {
+            let long = Movie {
+                cycles_per_frame,
+                ..movie
+            };
+            assert_eq!(long.to_bytes(), Err(MovieError::Invalid));
+        }
+    }
+
+    /// Test rejecting events that aren't ordered by frame
+    #[test]
+    fn test_event_order() {
+        let (movie, _) = record();
+        let mut bytes = movie.to_bytes().unwrap();
+        let events = bytes.len() - 20;
+        bytes[events..].rotate_left(10);
+        assert_eq!(Movie::from_bytes(&bytes), Err(MovieError::Invalid));
+
+        let mut unordered = movie.clone();
+        unordered.events.swap(0, 1);
+        let mut e = unordered.start(&ROM).unwrap();
+        let mut p = Player::new(&unordered);
+        p.run_to_end(&mut e).unwrap();
+        assert!(e.keypad.is_pressed(0x3));
+    }
+
+    /// Test refusing to play a movie with another ROM
+    #[test]
+    fn test_rom_mismatch() {
+        let (movie, _) = record();
+        assert_eq!(
+            movie.start(&[0x12, 0x00]).err(),
+            Some(MovieError::RomMismatch {
+                expected: rom_hash(&ROM),
+                actual: rom_hash(&[0x12, 0x00])
+            })
+        );
+        assert_eq!(rom_hash(&[]), 0xCBF2_9CE4_8422_2325);
+        assert_eq!(rom_hash(b"a"), 0xAF63_DC4C_8601_EC8C);
+    }
+}
@@ -1,10 +1,14 @@
 use crate::emulator::opcode::OpCode::*;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
 
 /// Represents a processor command.
 ///
 /// Exists for purposes of pattern matching.
 ///
 /// Internal symbols are:
+/// - `NNNN`: 16-bit address, represented by type `u16`
 /// - `NNN`: address, represented by type `u16`
 /// - `NN`: 8-bit constant, represented by type `u8`
 /// - `N`: 4-bit constant, represented by type `u8`
@@ -27,6 +31,31 @@ pub enum OpCode {
     ///
     /// Returns from a subroutine
     Return,
+    /// `0x00CN`, where
+    /// - `N` is `rows`
+    ///
+    /// Scrolls the display `N` pixels down (SUPER-CHIP)
+    ScrollDown { rows: u8 },
+    /// `0x00FB`
+    ///
+    /// Scrolls the display 4 pixels right (SUPER-CHIP)
+    ScrollRight,
+    /// `0x00FC`
+    ///
+    /// Scrolls the display 4 pixels left (SUPER-CHIP)
+    ScrollLeft,
+    /// `0x00FD`
+    ///
+    /// Halts the processor (SUPER-CHIP)
+    Exit,
+    /// `0x00FE`
+    ///
+    /// Switches the display to the 64x32 low resolution mode and clears it (SUPER-CHIP)
+    LowRes,
+    /// `0x00FF`
+    ///
+    /// Switches the display to the 128x64 high resolution mode and clears it (SUPER-CHIP)
+    HighRes,
     /// `0x1NNN`, where
     /// - `NNN` is `target`
     ///
@@ -58,6 +87,22 @@ pub enum OpCode {
     /// Skips the next instruction if `VX` equals `VY`.
     /// (Usually the next instruction is a jump to skip a code block)
     SkipNextIfRegEqualToReg { register_x: u8, register_y: u8 },
+    /// `0x5XY2`, where
+    /// - `X` is `register_x`
+    /// - `Y` is `register_y`
+    ///
+    /// Stores `VX` to `VY` (including both) in memory starting at address `I`,
+    /// in reverse order if `X` is greater than `Y`.
+    /// `I` isn't changed (XO-CHIP).
+    RegRangeDump { register_x: u8, register_y: u8 },
+    /// `0x5XY3`, where
+    /// - `X` is `register_x`
+    /// - `Y` is `register_y`
+    ///
+    /// Fills `VX` to `VY` (including both) with values from memory starting at address `I`,
+    /// in reverse order if `X` is greater than `Y`.
+    /// `I` isn't changed (XO-CHIP).
+    RegRangeLoad { register_x: u8, register_y: u8 },
     /// `0x6XNN`, where
     /// - `X` is `register`
     /// - `Y` is `constant`
@@ -68,7 +113,8 @@ pub enum OpCode {
     /// - `X` is `register`
     /// - `Y` is `constant`
     ///
-    /// Adds `NN` to `VX`
+    /// Adds `NN` to `VX`, wrapping around on overflow.
+    /// `VF` isn't changed.
     RegAddConst { register: u8, constant: u8 },
     /// `0x8XY0`, where
     /// - `X` is `register_x`
@@ -82,6 +128,7 @@ pub enum OpCode {
     ///
     /// Sets `VX` to a `VX | VY`
     /// (Bitwise Or)
+    /// `VF` is reset to 0 with the `logic_resets_vf` quirk.
     RegBitwiseOr { register_x: u8, register_y: u8 },
     /// `0x8XY2`, where
     /// - `X` is `register_x`
@@ -89,48 +136,55 @@ pub enum OpCode {
     ///
     /// Sets `VX` to a `VX & VY`
     /// (Bitwise And)
+    /// `VF` is reset to 0 with the `logic_resets_vf` quirk.
     RegBitwiseAnd { register_x: u8, register_y: u8 },
     /// `0x8XY3`, where
     /// - `X` is `register_x`
     /// - `Y` is `register_y`
     ///
     /// Sets `VX` to a `VX ^ VY`
+    /// `VF` is reset to 0 with the `logic_resets_vf` quirk.
     RegBitwiseXor { register_x: u8, register_y: u8 },
     /// `0x8XY4`, where
     /// - `X` is `register_x`
     /// - `Y` is `register_y`
     ///
-    /// Sets `VX` to a `VX + VY`
-    /// `VF` is set to 1 when there's a carry, and to 0 when there isn't. TODO: Try to understand what this means
+    /// Sets `VX` to a `VX + VY`, wrapping around on overflow.
+    /// `VF` is set to 1 when the sum exceeds 255 (there's a carry), and to 0 when it doesn't.
+    /// `VF` is written after `VX`, so the flag wins when `X` is `F`.
     RegAdd { register_x: u8, register_y: u8 },
     /// `0x8XY5`, where
     /// - `X` is `register_x`
     /// - `Y` is `register_y`
     ///
-    /// Sets `VX` to a `VX - VY`
-    /// `VF` is set to 0 when there's a borrow, and to 1 when there isn't. TODO: Try to understand what this means
+    /// Sets `VX` to a `VX - VY`, wrapping around on underflow.
+    /// `VF` is set to 0 when `VY` is greater than `VX` (there's a borrow), and to 1 when it isn't.
+    /// `VF` is written after `VX`, so the flag wins when `X` is `F`.
     RegSub { register_x: u8, register_y: u8 },
     /// `0x8XY6`, where
-    /// - `X` is `register`
-    /// - `Y` is not used
+    /// - `X` is `register_x`
+    /// - `Y` is `register_y`
     ///
-    /// Sets `VX` to a `VX >> 1`
-    /// `VF` is set to `VX`'s least significant bit. TODO: Try to understand what this means
-    RegRightShift { register: u8 },
+    /// Sets `VX` to a `VX >> 1`, or to a `VY >> 1` with the `shift_uses_vy` quirk.
+    /// `VF` is set to the shifted value's least significant bit, the bit that is shifted out.
+    /// `VF` is written after `VX`, so the flag wins when `X` is `F`.
+    RegRightShift { register_x: u8, register_y: u8 },
     /// `0x8XY7`, where
     /// - `X` is `register_x`
     /// - `Y` is `register_y`
     ///
-    /// Sets `VX` to a `VX - VY`
-    /// `VF` is set to 0 when there's a borrow, and to 1 when there isn't. TODO: Try to understand what this means
+    /// Sets `VX` to a `VY - VX`, wrapping around on underflow.
+    /// `VF` is set to 0 when `VX` is greater than `VY` (there's a borrow), and to 1 when it isn't.
+    /// `VF` is written after `VX`, so the flag wins when `X` is `F`.
     RegReverseSub { register_x: u8, register_y: u8 },
     /// `0x8XYE`, where
-    /// - `X` is `register`
-    /// - `Y` is not used
+    /// - `X` is `register_x`
+    /// - `Y` is `register_y`
     ///
-    /// Sets `VX` to a `VX << 1`
-    /// `VF` is set to `VX`'s most significant bit. TODO: Try to understand what this means
-    RegLeftShift { register: u8 },
+    /// Sets `VX` to a `VX << 1`, or to a `VY << 1` with the `shift_uses_vy` quirk.
+    /// `VF` is set to the shifted value's most significant bit, the bit that is shifted out.
+    /// `VF` is written after `VX`, so the flag wins when `X` is `F`.
+    RegLeftShift { register_x: u8, register_y: u8 },
     /// `0x9XY0`, where
     /// - `X` is `register_x`
     /// - `Y` is `register_y`
@@ -146,7 +200,8 @@ pub enum OpCode {
     /// `0xBNNN`, where
     /// - `NNN` is `target`
     ///
-    /// Jumps to the address `NNN + V0`
+    /// Jumps to the address `NNN + V0`.
+    /// With the `jump_uses_vx` quirk jumps to `NNN + VX`, where `X` is the highest nibble of `NNN`.
     JumpRegZero { target: u16 },
     /// `0xCXNN`, where
     /// - `X` is `register`
@@ -163,7 +218,12 @@ pub enum OpCode {
     /// Each row of 8 pixels is read as bit-coded starting from memory location I;
     /// I value doesn’t change after the execution of this instruction.
     /// VF is set to 1 if any screen pixels are flipped from set, to unset when the sprite is drawn,
-    /// and to 0 if that doesn’t happen
+    /// and to 0 if that doesn’t happen.
+    /// Sprites are clipped at the display edges, or wrap around with the `sprite_wrap` quirk.
+    /// With the `display_wait` quirk the processor then waits for the next timer tick.
+    ///
+    /// When `N` is 0 a 16x16 sprite is drawn instead on SUPER-CHIP and XO-CHIP, its rows are
+    /// pairs of bytes; other variants draw nothing.
     DisplaySprite {
         coord_x: u8,
         coord_y: u8,
@@ -181,6 +241,21 @@ pub enum OpCode {
     /// Skips the next instruction if the key stored in `VX` isn't pressed.
     /// (Usually the next instruction is a jump to skip a code block)
     SkipNextIfRegKeyNotPressed { register: u8 },
+    /// `0xF000 0xNNNN`, where
+    /// - `NNNN` is `target`
+    ///
+    /// Sets `I` to the address `NNNN`.
+    /// The only opcode that is 4 bytes long (XO-CHIP).
+    MemLong { target: u16 },
+    /// `0xFN01`, where
+    /// - `N` is `planes`
+    ///
+    /// Selects the bitplanes affected by drawing, scrolling and clearing (XO-CHIP).
+    SelectPlanes { planes: u8 },
+    /// `0xF002`
+    ///
+    /// Loads the 16 bytes at `I` into the audio pattern buffer (XO-CHIP).
+    LoadAudioPattern,
     /// `0xFX07`, where
     /// - `X` is `register`
     ///
@@ -215,6 +290,17 @@ pub enum OpCode {
     /// Sets `I` to the location of the sprite for the character in `VX`.
     /// Characters 0-F (in hexadecimal) are represented by a 4x5 font.
     MemMoveToRegChar { register: u8 },
+    /// `0xFX30`, where
+    /// - `X` is `register`
+    ///
+    /// Sets `I` to the location of the large sprite for the character in `VX`.
+    /// Characters 0-F (in hexadecimal) are represented by a 8x10 font (SUPER-CHIP).
+    MemMoveToRegLargeChar { register: u8 },
+    /// `0xFX3A`, where
+    /// - `X` is `register`
+    ///
+    /// Sets the audio pattern playback pitch to `VX` (XO-CHIP).
+    SetPitchToReg { register: u8 },
     /// `0xFX33`, where
     /// - `X` is `register`
     ///
@@ -223,38 +309,307 @@ pub enum OpCode {
     /// the middle digit at I plus 1, and the least significant digit at I plus 2.
     /// (In other words, take the decimal representation of VX,
     /// place the hundreds digit in memory at location in I, the tens digit at location I+1,
-    /// and the ones digit at location I+2.) TODO: Understand how this works
+    /// and the ones digit at location I+2.)
     StoreBCD { register: u8 },
     /// `0xFX55`, where
     /// - `X` is `register`
     ///
     /// Stores `V0` to `VX` (including `VX`) in memory starting at address `I`.
-    /// The offset from `I` is increased by 1 for each value written, but `I` itself is left unmodified.
+    /// The offset from `I` is increased by 1 for each value written.
+    /// `I` itself is changed according to the `index_increment` quirk.
     RegDump { register: u8 },
     /// `0xFX65`, where
     /// - `X` is `register`
     ///
     /// Fills `V0` to `VX` (including `VX`) with values from memory starting at address I.
-    /// The offset from `I` is increased by 1 for each value written, but `I` itself is left unmodified.
+    /// The offset from `I` is increased by 1 for each value written.
+    /// `I` itself is changed according to the `index_increment` quirk.
     RegLoad { register: u8 },
+    /// `0xFX75`, where
+    /// - `X` is `register`
+    ///
+    /// Stores `V0` to `VX` (including `VX`) in the user flags (SUPER-CHIP)
+    StoreFlags { register: u8 },
+    /// `0xFX85`, where
+    /// - `X` is `register`
+    ///
+    /// Fills `V0` to `VX` (including `VX`) with values from the user flags (SUPER-CHIP)
+    LoadFlags { register: u8 },
+}
+
+/// Error returned when bytes don't form a known opcode.
+///
+/// Contains the offending bytes.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct UnknownOpcode(pub u16);
+
+impl fmt::Display for UnknownOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown opcode {:04X}", self.0)
+    }
+}
+
+impl Error for UnknownOpcode {}
+
+/// Error returned when an opcode can't be encoded because an operand is out of range.
+///
+/// Contains the opcode and the name of the offending operand.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct InvalidOperand {
+    /// Opcode that was being encoded
+    pub opcode: OpCode,
+    /// Name of the field that is out of range
+    pub operand: &'static str,
+}
+
+impl fmt::Display for InvalidOperand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "operand `{}` out of range in {:?}",
+            self.operand, self.opcode
+        )
+    }
+}
+
+impl Error for InvalidOperand {}
+
+/// Bytes of an opcode encoded by `OpCode::encode`
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Encoded {
+    bytes: [u8; 4],
+    len: usize,
+}
+
+impl Encoded {
+    /// Encoded bytes: 2 of them, or 4 for `MemLong`
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
 }
 
 fn combine(first_byte: u8, second_byte: u8) -> u16 {
     ((first_byte as u16) << 8) | second_byte as u16
 }
 
-impl From<(u8, u8)> for OpCode {
+impl OpCode {
+    /// Size of the opcode in bytes
+    pub fn size(&self) -> u16 {
+        match self {
+            MemLong { .. } => 4,
+            _ => 2,
+        }
+    }
+
+    /// Size in bytes of the opcode starting with `first_byte` and `second_byte`
+    pub fn encoded_size(first_byte: u8, second_byte: u8) -> u16 {
+        if (first_byte, second_byte) == (0xF0, 0x00) {
+            4
+        } else {
+            2
+        }
+    }
+
+    /// Encode the opcode into its bytes: 2 of them, or 4 for `MemLong`.
+    ///
+    /// Fails with `InvalidOperand` if an operand doesn't fit its field:
+    /// addresses are 12 bits (16 for `MemLong`), registers and other nibbles are 4 bits.
+    /// A `_NativeCall` whose target would decode as another `0x0NNN` opcode is rejected as well.
+    pub fn encode(&self) -> Result<Encoded, InvalidOperand> {
+        let invalid = |operand| InvalidOperand {
+            opcode: *self,
+            operand,
+        };
+        let address = |target: u16| -> Result<u16, InvalidOperand> {
+            if target <= 0xFFF {
+                Ok(target)
+            } else {
+                Err(invalid("target"))
+            }
+        };
+        let nibble = |value: u8, operand| -> Result<u16, InvalidOperand> {
+            if value <= 0xF {
+                Ok(value as u16)
+            } else {
+                Err(invalid(operand))
+            }
+        };
+        let x_nn = |base: u16, register, constant: u8| -> Result<u16, InvalidOperand> {
+            Ok(base | nibble(register, "register")? << 8 | constant as u16)
+        };
+        let x_y = |base: u16, register_x, register_y| -> Result<u16, InvalidOperand> {
+            Ok(base
+                | nibble(register_x, "register_x")? << 8
+                | nibble(register_y, "register_y")? << 4)
+        };
+        let x = |base: u16, register| -> Result<u16, InvalidOperand> {
+            Ok(base | nibble(register, "register")? << 8)
+        };
+        let word = match *self {
+            _NativeCall { target } => {
+                let word = address(target)?;
+                match OpCode::try_from(((word >> 8) as u8, word as u8)) {
+                    Ok(_NativeCall { .. }) => word,
+                    _ => return Err(invalid("target")),
+                }
+            }
+            ClearScreen => 0x00E0,
+            Return => 0x00EE,
+            ScrollDown { rows } => 0x00C0 | nibble(rows, "rows")?,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            LowRes => 0x00FE,
+            HighRes => 0x00FF,
+            Goto { target } => 0x1000 | address(target)?,
+            Subroutine { target } => 0x2000 | address(target)?,
+            SkipNextIfRegEqualToConst { register, constant } => x_nn(0x3000, register, constant)?,
+            SkipNextIfRegNotEqualToConst { register, constant } => {
+                x_nn(0x4000, register, constant)?
+            }
+            SkipNextIfRegEqualToReg {
+                register_x,
+                register_y,
+            } => x_y(0x5000, register_x, register_y)?,
+            RegRangeDump {
+                register_x,
+                register_y,
+            } => x_y(0x5002, register_x, register_y)?,
+            RegRangeLoad {
+                register_x,
+                register_y,
+            } => x_y(0x5003, register_x, register_y)?,
+            RegSetConst { register, constant } => x_nn(0x6000, register, constant)?,
+            RegAddConst { register, constant } => x_nn(0x7000, register, constant)?,
+            RegMov {
+                register_x,
+                register_y,
+            } => x_y(0x8000, register_x, register_y)?,
+            RegBitwiseOr {
+                register_x,
+                register_y,
+            } => x_y(0x8001, register_x, register_y)?,
+            RegBitwiseAnd {
+                register_x,
+                register_y,
+            } => x_y(0x8002, register_x, register_y)?,
+            RegBitwiseXor {
+                register_x,
+                register_y,
+            } => x_y(0x8003, register_x, register_y)?,
+            RegAdd {
+                register_x,
+                register_y,
+            } => x_y(0x8004, register_x, register_y)?,
+            RegSub {
+                register_x,
+                register_y,
+            } => x_y(0x8005, register_x, register_y)?,
+            RegRightShift {
+                register_x,
+                register_y,
+            } => x_y(0x8006, register_x, register_y)?,
+            RegReverseSub {
+                register_x,
+                register_y,
+            } => x_y(0x8007, register_x, register_y)?,
+            RegLeftShift {
+                register_x,
+                register_y,
+            } => x_y(0x800E, register_x, register_y)?,
+            SkipNextIfRegNotEqualToReg {
+                register_x,
+                register_y,
+            } => x_y(0x9000, register_x, register_y)?,
+            Mem { target } => 0xA000 | address(target)?,
+            JumpRegZero { target } => 0xB000 | address(target)?,
+            RandToReg { register, constant } => x_nn(0xC000, register, constant)?,
+            DisplaySprite {
+                coord_x,
+                coord_y,
+                height,
+            } => {
+                0xD000
+                    | nibble(coord_x, "coord_x")? << 8
+                    | nibble(coord_y, "coord_y")? << 4
+                    | nibble(height, "height")?
+            }
+            SkipNextIfRegKeyPressed { register } => x(0xE09E, register)?,
+            SkipNextIfRegKeyNotPressed { register } => x(0xE0A1, register)?,
+            MemLong { target } => {
+                return Ok(Encoded {
+                    bytes: [0xF0, 0x00, (target >> 8) as u8, target as u8],
+                    len: 4,
+                })
+            }
+            SelectPlanes { planes } => 0xF001 | nibble(planes, "planes")? << 8,
+            LoadAudioPattern => 0xF002,
+            SetRegToDelayTimer { register } => x(0xF007, register)?,
+            SetRegToKeyPressed { register } => x(0xF00A, register)?,
+            SetDelayTimerToReg { register } => x(0xF015, register)?,
+            SetSoundTimerToReg { register } => x(0xF018, register)?,
+            MemAddReg { register } => x(0xF01E, register)?,
+            MemMoveToRegChar { register } => x(0xF029, register)?,
+            MemMoveToRegLargeChar { register } => x(0xF030, register)?,
+            SetPitchToReg { register } => x(0xF03A, register)?,
+            StoreBCD { register } => x(0xF033, register)?,
+            RegDump { register } => x(0xF055, register)?,
+            RegLoad { register } => x(0xF065, register)?,
+            StoreFlags { register } => x(0xF075, register)?,
+            LoadFlags { register } => x(0xF085, register)?,
+        };
+        Ok(Encoded {
+            bytes: [(word >> 8) as u8, word as u8, 0, 0],
+            len: 2,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for OpCode {
+    type Error = UnknownOpcode;
+
+    /// Makes an OpCode object from the bytes at the start of `bytes`, including 4 bytes long ones.
+    /// Fails with `UnknownOpcode` if they don't form a known opcode or `bytes` is too short.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let byte = |index: usize| bytes.get(index).copied().unwrap_or(0);
+        let (first_byte, second_byte) = (byte(0), byte(1));
+        let size = OpCode::encoded_size(first_byte, second_byte) as usize;
+        if bytes.len() < size {
+            return Err(UnknownOpcode(combine(first_byte, second_byte)));
+        }
+        if size == 4 {
+            return Ok(MemLong {
+                target: combine(byte(2), byte(3)),
+            });
+        }
+        OpCode::try_from((first_byte, second_byte))
+    }
+}
+
+impl TryFrom<(u8, u8)> for OpCode {
+    type Error = UnknownOpcode;
+
     /// Makes an OpCode object from two (consequent) bytes.
+    /// Fails with `UnknownOpcode` if they don't form a known opcode,
+    /// or start the 4 bytes long `MemLong`, which is decoded from a slice instead.
     ///
     /// Implemented:
     /// - [x] _NativeCall
     /// - [x] ClearScreen
     /// - [x] Return
+    /// - [x] ScrollDown
+    /// - [x] ScrollRight
+    /// - [x] ScrollLeft
+    /// - [x] Exit
+    /// - [x] LowRes
+    /// - [x] HighRes
     /// - [x] Goto
     /// - [x] Subroutine
     /// - [x] SkipNextIfRegEqualToConst
     /// - [x] SkipNextIfRegNotEqualToConst
     /// - [x] SkipNextIfRegEqualToReg
+    /// - [x] RegRangeDump
+    /// - [x] RegRangeLoad
     /// - [x] RegSetConst
     /// - [x] RegAddConst
     /// - [x] RegMov
@@ -271,6 +626,8 @@ impl From<(u8, u8)> for OpCode {
     /// - [x] JumpRegZero
     /// - [x] RandToReg
     /// - [x] DisplaySprite
+    /// - [x] SelectPlanes
+    /// - [x] LoadAudioPattern
     /// - [x] SkipNextIfRegKeyPressed
     /// - [x] SkipNextIfRegKeyNotPressed
     /// - [x] SetRegToDelayTimer
@@ -279,21 +636,32 @@ impl From<(u8, u8)> for OpCode {
     /// - [x] SetSoundTimerToReg
     /// - [x] MemAddReg
     /// - [x] MemMoveToCharReg
+    /// - [x] MemMoveToRegLargeChar
+    /// - [x] SetPitchToReg
     /// - [x] StoreBCD
     /// - [x] RegDump
     /// - [x] RegLoad
-    fn from((first_byte, second_byte): (u8, u8)) -> Self {
+    /// - [x] StoreFlags
+    /// - [x] LoadFlags
+    fn try_from((first_byte, second_byte): (u8, u8)) -> Result<Self, Self::Error> {
         let full_repr = combine(first_byte, second_byte);
         let first_digit = first_byte >> 4;
         let second_digit = first_byte % (1 << 4);
         let third_digit = second_byte >> 4;
         let fourth_digit = second_byte % (1 << 4);
         let target = combine(second_digit, second_byte);
-        match first_digit {
-            // ClearScreen, Return, _NativeCall
-            0x0 => match second_byte {
-                0xE0 => ClearScreen,
-                0xEE => Return,
+        let opcode = match first_digit {
+            // ClearScreen, Return, ScrollDown, ScrollRight, ScrollLeft, Exit, LowRes, HighRes,
+            // _NativeCall
+            0x0 => match (second_digit, second_byte) {
+                (0x0, 0xE0) => ClearScreen,
+                (0x0, 0xEE) => Return,
+                (0x0, 0xC0..=0xCF) => ScrollDown { rows: fourth_digit },
+                (0x0, 0xFB) => ScrollRight,
+                (0x0, 0xFC) => ScrollLeft,
+                (0x0, 0xFD) => Exit,
+                (0x0, 0xFE) => LowRes,
+                (0x0, 0xFF) => HighRes,
                 _ => _NativeCall { target },
             },
             // Goto
@@ -310,10 +678,21 @@ impl From<(u8, u8)> for OpCode {
                 register: second_digit,
                 constant: second_byte,
             },
-            // SkipNextIfRegEqualToReg
-            0x5 => SkipNextIfRegEqualToReg {
-                register_x: second_digit,
-                register_y: third_digit,
+            // SkipNextIfRegEqualToReg, RegRangeDump, RegRangeLoad
+            0x5 => match fourth_digit {
+                0x0 => SkipNextIfRegEqualToReg {
+                    register_x: second_digit,
+                    register_y: third_digit,
+                },
+                0x2 => RegRangeDump {
+                    register_x: second_digit,
+                    register_y: third_digit,
+                },
+                0x3 => RegRangeLoad {
+                    register_x: second_digit,
+                    register_y: third_digit,
+                },
+                _ => return Err(UnknownOpcode(full_repr)),
             },
             // RegSetConst
             0x6 => RegSetConst {
@@ -353,16 +732,18 @@ impl From<(u8, u8)> for OpCode {
                     register_y: third_digit,
                 },
                 0x6 => RegRightShift {
-                    register: second_digit,
+                    register_x: second_digit,
+                    register_y: third_digit,
                 },
                 0x7 => RegReverseSub {
                     register_x: second_digit,
                     register_y: third_digit,
                 },
                 0xE => RegLeftShift {
-                    register: second_digit,
+                    register_x: second_digit,
+                    register_y: third_digit,
                 },
-                _ => panic!("Opcode {} not found", full_repr),
+                _ => return Err(UnknownOpcode(full_repr)),
             },
             // SkipNextIfRegNotEqualToReg
             0x9 => SkipNextIfRegNotEqualToReg {
@@ -392,11 +773,16 @@ impl From<(u8, u8)> for OpCode {
                 0xA1 => SkipNextIfRegKeyNotPressed {
                     register: second_digit,
                 },
-                _ => panic!("Opcode {} not found", full_repr),
+                _ => return Err(UnknownOpcode(full_repr)),
             },
             // SetRegToDelayTimer, SetRegToKeyPressed, SetDelayTimerToReg, SetSoundTimerToReg, MemAddReg,
-            // MemMoveToRegChar, StoreBCD, RegDump, RegLoad
+            // MemMoveToRegChar, StoreBCD, RegDump, RegLoad, SelectPlanes, LoadAudioPattern,
+            // MemMoveToRegLargeChar, SetPitchToReg, StoreFlags, LoadFlags
             0xF => match second_byte {
+                0x01 => SelectPlanes {
+                    planes: second_digit,
+                },
+                0x02 if second_digit == 0 => LoadAudioPattern,
                 0x07 => SetRegToDelayTimer {
                     register: second_digit,
                 },
@@ -415,6 +801,12 @@ impl From<(u8, u8)> for OpCode {
                 0x29 => MemMoveToRegChar {
                     register: second_digit,
                 },
+                0x30 => MemMoveToRegLargeChar {
+                    register: second_digit,
+                },
+                0x3A => SetPitchToReg {
+                    register: second_digit,
+                },
                 0x33 => StoreBCD {
                     register: second_digit,
                 },
@@ -424,13 +816,17 @@ impl From<(u8, u8)> for OpCode {
                 0x65 => RegLoad {
                     register: second_digit,
                 },
-                _ => panic!("Opcode {} not found", full_repr),
+                0x75 => StoreFlags {
+                    register: second_digit,
+                },
+                0x85 => LoadFlags {
+                    register: second_digit,
+                },
+                _ => return Err(UnknownOpcode(full_repr)),
             },
-            _ => panic!(
-                "First digit has a value of {}, while only 0x0..0xF are accepted",
-                first_byte
-            ),
-        }
+            _ => unreachable!("First digit is a nibble, so only 0x0..0xF are possible"),
+        };
+        Ok(opcode)
     }
 }
 
@@ -445,11 +841,19 @@ fn split_bytes(b: u16) -> (u8, u8) {
 /// - [x] _NativeCall
 /// - [x] ClearScreen
 /// - [x] Return
+/// - [x] ScrollDown
+/// - [x] ScrollRight
+/// - [x] ScrollLeft
+/// - [x] Exit
+/// - [x] LowRes
+/// - [x] HighRes
 /// - [x] Goto
 /// - [x] Subroutine
 /// - [x] SkipNextIfRegEqualToConst
 /// - [x] SkipNextIfRegNotEqualToConst
 /// - [x] SkipNextIfRegEqualToReg
+/// - [x] RegRangeDump
+/// - [x] RegRangeLoad
 /// - [x] RegSetConst
 /// - [x] RegAddConst
 /// - [x] RegMov
@@ -466,6 +870,9 @@ fn split_bytes(b: u16) -> (u8, u8) {
 /// - [x] JumpRegZero
 /// - [x] RandToReg
 /// - [x] DisplaySprite
+/// - [x] MemLong
+/// - [x] SelectPlanes
+/// - [x] LoadAudioPattern
 /// - [x] SkipNextIfRegKeyPressed
 /// - [x] SkipNextIfRegKeyNotPressed
 /// - [x] SetRegToDelayTimer
@@ -474,18 +881,231 @@ fn split_bytes(b: u16) -> (u8, u8) {
 /// - [x] SetSoundTimerToReg
 /// - [x] MemAddReg
 /// - [x] MemMoveToCharReg
+/// - [x] MemMoveToRegLargeChar
+/// - [x] SetPitchToReg
 /// - [x] StoreBCD
 /// - [x] RegDump
 /// - [x] RegLoad
+/// - [x] StoreFlags
+/// - [x] LoadFlags
 #[cfg(test)]
 mod tests {
     use crate::emulator::opcode::OpCode::*;
-    use crate::emulator::opcode::{split_bytes, OpCode};
+    use crate::emulator::opcode::{split_bytes, InvalidOperand, OpCode, UnknownOpcode};
+    use std::convert::TryFrom;
 
     fn assert_code(code: u16, opcode: OpCode) {
         let n = code;
-        let o = OpCode::from(split_bytes(n));
-        assert_eq!(o, opcode)
+        let o = OpCode::try_from(split_bytes(n));
+        assert_eq!(o, Ok(opcode))
+    }
+
+    fn assert_unknown(code: u16) {
+        assert_eq!(
+            OpCode::try_from(split_bytes(code)),
+            Err(UnknownOpcode(code))
+        )
+    }
+
+    /// Test that unknown opcodes are rejected
+    #[test]
+    fn test_unknown() {
+        assert_unknown(0x5121);
+        assert_unknown(0x8128);
+        assert_unknown(0x812F);
+        assert_unknown(0xE100);
+        assert_unknown(0xF1FF);
+        assert_unknown(0xF102);
+        assert_unknown(0xF000);
+    }
+
+    /// Every opcode with every combination of valid operands
+    fn all_opcodes() -> Vec<OpCode> {
+        let nibbles = || 0..=0xFu8;
+        let bytes = || 0..=0xFFu8;
+        let addresses = || 0..=0xFFFu16;
+        let mut opcodes = vec![
+            ClearScreen,
+            Return,
+            ScrollRight,
+            ScrollLeft,
+            Exit,
+            LowRes,
+            HighRes,
+            LoadAudioPattern,
+        ];
+        for target in addresses() {
+            if !(0x0C0..=0x0CF).contains(&target)
+                && ![0x0E0, 0x0EE, 0x0FB, 0x0FC, 0x0FD, 0x0FE, 0x0FF].contains(&target)
+            {
+                opcodes.push(_NativeCall { target });
+            }
+            opcodes.extend(vec![
+                Goto { target },
+                Subroutine { target },
+                Mem { target },
+                JumpRegZero { target },
+            ]);
+        }
+        for target in 0..=0xFFFFu16 {
+            opcodes.push(MemLong { target });
+        }
+        for n in nibbles() {
+            opcodes.extend(vec![ScrollDown { rows: n }, SelectPlanes { planes: n }]);
+        }
+        for register in nibbles() {
+            opcodes.extend(vec![
+                SkipNextIfRegKeyPressed { register },
+                SkipNextIfRegKeyNotPressed { register },
+                SetRegToDelayTimer { register },
+                SetRegToKeyPressed { register },
+                SetDelayTimerToReg { register },
+                SetSoundTimerToReg { register },
+                MemAddReg { register },
+                MemMoveToRegChar { register },
+                MemMoveToRegLargeChar { register },
+                SetPitchToReg { register },
+                StoreBCD { register },
+                RegDump { register },
+                RegLoad { register },
+                StoreFlags { register },
+                LoadFlags { register },
+            ]);
+            for constant in bytes() {
+                opcodes.extend(vec![
+                    SkipNextIfRegEqualToConst { register, constant },
+                    SkipNextIfRegNotEqualToConst { register, constant },
+                    RegSetConst { register, constant },
+                    RegAddConst { register, constant },
+                    RandToReg { register, constant },
+                ]);
+            }
+        }
+        for register_x in nibbles() {
+            for register_y in nibbles() {
+                opcodes.extend(vec![
+                    SkipNextIfRegEqualToReg {
+                        register_x,
+                        register_y,
+                    },
+                    RegRangeDump {
+                        register_x,
+                        register_y,
+                    },
+                    RegRangeLoad {
+                        register_x,
+                        register_y,
+                    },
+                    RegMov {
+                        register_x,
+                        register_y,
+                    },
+                    RegBitwiseOr {
+                        register_x,
+                        register_y,
+                    },
+                    RegBitwiseAnd {
+                        register_x,
+                        register_y,
+                    },
+                    RegBitwiseXor {
+                        register_x,
+                        register_y,
+                    },
+                    RegAdd {
+                        register_x,
+                        register_y,
+                    },
+                    RegSub {
+                        register_x,
+                        register_y,
+                    },
+                    RegRightShift {
+                        register_x,
+                        register_y,
+                    },
+                    RegReverseSub {
+                        register_x,
+                        register_y,
+                    },
+                    RegLeftShift {
+                        register_x,
+                        register_y,
+                    },
+                    SkipNextIfRegNotEqualToReg {
+                        register_x,
+                        register_y,
+                    },
+                ]);
+                for height in nibbles() {
+                    opcodes.push(DisplaySprite {
+                        coord_x: register_x,
+                        coord_y: register_y,
+                        height,
+                    });
+                }
+            }
+        }
+        opcodes
+    }
+
+    /// Test that decoding an encoded opcode gives it back, for every valid operand combination
+    #[test]
+    fn test_encode_round_trip() {
+        for opcode in all_opcodes() {
+            let encoded = opcode.encode().unwrap();
+            assert_eq!(encoded.as_slice().len(), opcode.size() as usize);
+            assert_eq!(OpCode::try_from(encoded.as_slice()), Ok(opcode));
+        }
+    }
+
+    /// Test that encoding a decoded opcode gives back its bytes, for every 2 bytes long opcode.
+    /// `0x9XYN` is decoded regardless of `N`, so it's encoded back as `0x9XY0`.
+    #[test]
+    fn test_decode_round_trip() {
+        for code in 0..=0xFFFFu16 {
+            let (first_byte, second_byte) = split_bytes(code);
+            if let Ok(opcode) = OpCode::try_from((first_byte, second_byte)) {
+                let canonical = if first_byte >> 4 == 0x9 {
+                    second_byte & 0xF0
+                } else {
+                    second_byte
+                };
+                assert_eq!(opcode.encode().unwrap().as_slice(), [first_byte, canonical]);
+            }
+        }
+    }
+
+    /// Test that out of range operands are rejected
+    #[test]
+    fn test_encode_invalid() {
+        let invalid = |opcode, operand| Err(InvalidOperand { opcode, operand });
+        let goto = Goto { target: 0x1000 };
+        assert_eq!(goto.encode(), invalid(goto, "target"));
+        let set = RegSetConst {
+            register: 0x10,
+            constant: 0,
+        };
+        assert_eq!(set.encode(), invalid(set, "register"));
+        let add = RegAdd {
+            register_x: 0,
+            register_y: 0x10,
+        };
+        assert_eq!(add.encode(), invalid(add, "register_y"));
+        let sprite = DisplaySprite {
+            coord_x: 0,
+            coord_y: 0,
+            height: 0x10,
+        };
+        assert_eq!(sprite.encode(), invalid(sprite, "height"));
+        let planes = SelectPlanes { planes: 0x10 };
+        assert_eq!(planes.encode(), invalid(planes, "planes"));
+        let native = _NativeCall { target: 0x0E0 };
+        assert_eq!(native.encode(), invalid(native, "target"));
+        assert_eq!(
+            MemLong { target: 0xFFFF }.encode().unwrap().as_slice(),
+            [0xF0, 0x00, 0xFF, 0xFF]
+        );
     }
 
     /// Test _NativeCall generation
@@ -506,6 +1126,23 @@ mod tests {
         assert_code(0x00EE, Return);
     }
 
+    /// Test ScrollDown generation
+    #[test]
+    fn test_scroll_down() {
+        assert_code(0x00C5, ScrollDown { rows: 0x5 });
+    }
+
+    /// Test ScrollRight, ScrollLeft, Exit, LowRes and HighRes generation
+    #[test]
+    fn test_schip_control() {
+        assert_code(0x00FB, ScrollRight);
+        assert_code(0x00FC, ScrollLeft);
+        assert_code(0x00FD, Exit);
+        assert_code(0x00FE, LowRes);
+        assert_code(0x00FF, HighRes);
+        assert_code(0x01FF, _NativeCall { target: 0x1FF });
+    }
+
     /// Test Goto generation
     #[test]
     fn test_goto() {
@@ -554,6 +1191,30 @@ mod tests {
         );
     }
 
+    /// Test RegRangeDump generation
+    #[test]
+    fn test_reg_range_dump() {
+        assert_code(
+            0x5122,
+            RegRangeDump {
+                register_x: 0x1,
+                register_y: 0x2,
+            },
+        );
+    }
+
+    /// Test RegRangeLoad generation
+    #[test]
+    fn test_reg_range_load() {
+        assert_code(
+            0x5213,
+            RegRangeLoad {
+                register_x: 0x2,
+                register_y: 0x1,
+            },
+        );
+    }
+
     /// Test RegSetConst generation
     #[test]
     fn test_reg_set_const() {
@@ -653,7 +1314,13 @@ mod tests {
     /// Test RegRightShift generation
     #[test]
     fn test_reg_rshift() {
-        assert_code(0x8126, RegRightShift { register: 0x1 });
+        assert_code(
+            0x8126,
+            RegRightShift {
+                register_x: 0x1,
+                register_y: 0x2,
+            },
+        );
     }
 
     /// Test RegReverseRub generation
@@ -671,7 +1338,13 @@ mod tests {
     /// Test RegLeftShift generation
     #[test]
     fn test_reg_lshift() {
-        assert_code(0x812E, RegLeftShift { register: 0x1 })
+        assert_code(
+            0x812E,
+            RegLeftShift {
+                register_x: 0x1,
+                register_y: 0x2,
+            },
+        )
     }
 
     /// Test SkipNextIfRegNotEqualToReg generation
@@ -723,6 +1396,35 @@ mod tests {
         )
     }
 
+    /// Test MemLong generation from a slice
+    #[test]
+    fn test_mem_long() {
+        let bytes: &[u8] = &[0xF0, 0x00, 0x12, 0x34];
+        assert_eq!(OpCode::try_from(bytes), Ok(MemLong { target: 0x1234 }));
+        assert_eq!(MemLong { target: 0x1234 }.size(), 4);
+        assert_eq!(OpCode::try_from(&bytes[..3]), Err(UnknownOpcode(0xF000)));
+        let bytes: &[u8] = &[0x61, 0x23, 0xF0];
+        assert_eq!(
+            OpCode::try_from(bytes),
+            Ok(RegSetConst {
+                register: 0x1,
+                constant: 0x23
+            })
+        );
+    }
+
+    /// Test SelectPlanes generation
+    #[test]
+    fn test_select_planes() {
+        assert_code(0xF301, SelectPlanes { planes: 0x3 })
+    }
+
+    /// Test LoadAudioPattern generation
+    #[test]
+    fn test_load_audio_pattern() {
+        assert_code(0xF002, LoadAudioPattern)
+    }
+
     /// Test SkipNextIfRegKeyPressed generation
     #[test]
     fn test_skip_key() {
@@ -771,6 +1473,18 @@ mod tests {
         assert_code(0xF129, MemMoveToRegChar { register: 0x1 })
     }
 
+    /// Test MemMoveToRegLargeChar generation
+    #[test]
+    fn test_mem_move_large_char() {
+        assert_code(0xF130, MemMoveToRegLargeChar { register: 0x1 })
+    }
+
+    /// Test SetPitchToReg generation
+    #[test]
+    fn test_set_pitch() {
+        assert_code(0xF13A, SetPitchToReg { register: 0x1 })
+    }
+
     /// Test StoreBCD generation
     #[test]
     fn test_store_bcd() {
@@ -788,4 +1502,16 @@ mod tests {
     fn test_reg_load() {
         assert_code(0xF165, RegLoad { register: 0x1 })
     }
+
+    /// Test StoreFlags generation
+    #[test]
+    fn test_store_flags() {
+        assert_code(0xF175, StoreFlags { register: 0x1 })
+    }
+
+    /// Test LoadFlags generation
+    #[test]
+    fn test_load_flags() {
+        assert_code(0xF185, LoadFlags { register: 0x1 })
+    }
 }
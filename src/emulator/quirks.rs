@@ -0,0 +1,171 @@
+/// How `FX55` and `FX65` change `I`
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum IndexIncrement {
+    /// `I` is left unmodified
+    None,
+    /// `I` is increased by `X` (CHIP-48 behaviour)
+    ByX,
+    /// `I` is increased by `X + 1`, pointing past the last accessed byte (COSMAC VIP behaviour)
+    ByXPlusOne,
+}
+
+/// Behaviours that differ between CHIP-8 interpreters
+///
+/// `Quirks::default()` matches what most modern CHIP-8 emulators do.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` shift `VY` and store the result in `VX`, instead of shifting `VX` in place
+    pub shift_uses_vy: bool,
+    /// How `FX55`/`FX65` change `I`
+    pub index_increment: IndexIncrement,
+    /// `BNNN` jumps to `NNN + VX` (`X` being the highest nibble of `NNN`), instead of `NNN + V0`
+    pub jump_uses_vx: bool,
+    /// `8XY1`/`8XY2`/`8XY3` reset `VF` to 0
+    pub logic_resets_vf: bool,
+    /// Sprites wrap around the display edges, instead of being clipped
+    pub sprite_wrap: bool,
+    /// `DXYN` blocks the processor until the next timer tick (vertical blank interrupt)
+    pub display_wait: bool,
+    /// `FX0A` latches the key when it's released, instead of when it's pressed
+    pub key_latch_on_release: bool,
+}
+
+impl Quirks {
+    /// Original COSMAC VIP interpreter
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        index_increment: IndexIncrement::ByXPlusOne,
+        jump_uses_vx: false,
+        logic_resets_vf: true,
+        sprite_wrap: false,
+        display_wait: true,
+        key_latch_on_release: true,
+    };
+
+    /// CHIP-48 for HP-48 calculators
+    pub const CHIP_48: Quirks = Quirks {
+        shift_uses_vy: false,
+        index_increment: IndexIncrement::ByX,
+        jump_uses_vx: true,
+        logic_resets_vf: false,
+        sprite_wrap: false,
+        display_wait: false,
+        key_latch_on_release: false,
+    };
+
+    /// SUPER-CHIP 1.1 for HP-48 calculators
+    pub const SCHIP_11: Quirks = Quirks {
+        shift_uses_vy: false,
+        index_increment: IndexIncrement::None,
+        jump_uses_vx: true,
+        logic_resets_vf: false,
+        sprite_wrap: false,
+        display_wait: true,
+        key_latch_on_release: false,
+    };
+
+    /// SUPER-CHIP as implemented by modern interpreters such as Octo
+    pub const MODERN_SCHIP: Quirks = Quirks {
+        shift_uses_vy: false,
+        index_increment: IndexIncrement::None,
+        jump_uses_vx: true,
+        logic_resets_vf: false,
+        sprite_wrap: false,
+        display_wait: false,
+        key_latch_on_release: false,
+    };
+
+    /// XO-CHIP, as defined by Octo
+    pub const XO_CHIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        index_increment: IndexIncrement::ByXPlusOne,
+        jump_uses_vx: false,
+        logic_resets_vf: false,
+        sprite_wrap: true,
+        display_wait: false,
+        key_latch_on_release: false,
+    };
+
+    /// Size of the binary form produced by `to_bytes`
+    pub const SIZE: usize = 7;
+
+    /// Binary form for save states and movies: one byte per field, in declaration order
+    pub fn to_bytes(&self) -> [u8; Quirks::SIZE] {
+        let index_increment = match self.index_increment {
+            IndexIncrement::None => 0,
+            IndexIncrement::ByX => 1,
+            IndexIncrement::ByXPlusOne => 2,
+        };
+        [
+            self.shift_uses_vy as u8,
+            index_increment,
+            self.jump_uses_vx as u8,
+            self.logic_resets_vf as u8,
+            self.sprite_wrap as u8,
+            self.display_wait as u8,
+            self.key_latch_on_release as u8,
+        ]
+    }
+
+    /// Read the binary form produced by `to_bytes`, `None` if a byte is out of range
+    pub fn from_bytes(bytes: [u8; Quirks::SIZE]) -> Option<Quirks> {
+        let flag = |byte: u8| match byte {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        };
+        let index_increment = match bytes[1] {
+            0 => IndexIncrement::None,
+            1 => IndexIncrement::ByX,
+            2 => IndexIncrement::ByXPlusOne,
+            _ => return None,
+        };
+        Some(Quirks {
+            shift_uses_vy: flag(bytes[0])?,
+            index_increment,
+            jump_uses_vx: flag(bytes[2])?,
+            logic_resets_vf: flag(bytes[3])?,
+            sprite_wrap: flag(bytes[4])?,
+            display_wait: flag(bytes[5])?,
+            key_latch_on_release: flag(bytes[6])?,
+        })
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift_uses_vy: false,
+            index_increment: IndexIncrement::None,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            sprite_wrap: false,
+            display_wait: false,
+            key_latch_on_release: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::quirks::Quirks;
+
+    /// Test the binary form of every preset
+    #[test]
+    fn test_bytes() {
+        for quirks in [
+            Quirks::default(),
+            Quirks::COSMAC_VIP,
+            Quirks::CHIP_48,
+            Quirks::SCHIP_11,
+            Quirks::MODERN_SCHIP,
+            Quirks::XO_CHIP,
+        ]
+        .iter()
+        {
+            assert_eq!(Quirks::from_bytes(quirks.to_bytes()), Some(*quirks));
+        }
+        assert_eq!(Quirks::from_bytes([0, 3, 0, 0, 0, 0, 0]), None);
+        assert_eq!(Quirks::from_bytes([2, 0, 0, 0, 0, 0, 0]), None);
+    }
+}
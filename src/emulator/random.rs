@@ -1,16 +1,84 @@
-use rand::Rng;
+use crate::emulator::variant::Variant;
+use crate::emulator::Emulator;
+use std::convert::TryFrom;
 
-#[derive(Copy, Clone)]
-pub struct RNG(rand::rngs::ThreadRng);
+/// Source of the random bytes used by `CXNN`
+///
+/// Implementations must be deterministic for a given initial state,
+/// so that runs can be replayed.
+pub trait RandomSource {
+    /// Generate a random number in `0..256`
+    fn rand(&mut self) -> u8;
+
+    /// Internal state for save states, empty if it can't be saved
+    fn save(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restore internal state produced by `save`.
+    ///
+    /// Returns `false` if `state` isn't understood, the source isn't changed in that case.
+    fn restore(&mut self, _state: &[u8]) -> bool {
+        false
+    }
+}
+
+/// Default random source: a seedable SplitMix64 generator.
+///
+/// The same seed yields the same sequence on every platform.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct RNG {
+    state: u64,
+}
 
 impl RNG {
+    /// Make a generator seeded from system entropy
     pub fn new() -> Self {
-        Self(rand::thread_rng())
+        Self::from_seed(rand::random())
     }
 
-    /// Generate a random number in `0..256`
-    pub fn rand(&mut self) -> u8 {
-        self.0.gen_range(0, 256u16) as u8
+    /// Make a generator that produces the sequence determined by `seed`
+    pub fn from_seed(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Internal state, which can be restored with `set_state` to replay the sequence
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /// Restore internal state saved by `state`
+    pub fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
+
+    /// Generate the next 64 random bits
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl RandomSource for RNG {
+    fn rand(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.state.to_le_bytes().to_vec()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> bool {
+        match <[u8; 8]>::try_from(state) {
+            Ok(bytes) => {
+                self.state = u64::from_le_bytes(bytes);
+                true
+            }
+            Err(_) => false,
+        }
     }
 }
 
@@ -19,3 +87,168 @@ impl Default for RNG {
         Self::new()
     }
 }
+
+/// Random source reproducing the routine of the original COSMAC VIP interpreter.
+///
+/// The interpreter keeps a 16-bit seed in register `R9` of the 1802.
+/// For every `CXNN` it increments the seed, reads the byte of its own code page (`0x01XX`)
+/// addressed by the seed's low byte, adds the seed's high byte to it,
+/// and stores the sum back as the high byte. The sum is the random byte.
+///
+/// The interpreter isn't distributed with this crate, so its second page
+/// (256 bytes at `0x0100`) has to be supplied, as well as the power-on value of `R9`.
+/// Only changes made to `R9` by the `CXNN` routine itself are modelled.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct VipRNG {
+    page: [u8; 256],
+    seed: u16,
+}
+
+impl VipRNG {
+    /// Make a generator from the interpreter's page at `0x0100` and the initial value of `R9`
+    pub fn new(page: [u8; 256], seed: u16) -> Self {
+        Self { page, seed }
+    }
+
+    /// Current value of `R9`
+    pub fn seed(&self) -> u16 {
+        self.seed
+    }
+
+    /// Set the value of `R9`
+    pub fn set_seed(&mut self, seed: u16) {
+        self.seed = seed;
+    }
+}
+
+impl RandomSource for VipRNG {
+    fn rand(&mut self) -> u8 {
+        self.seed = self.seed.wrapping_add(1);
+        let [high, low] = self.seed.to_be_bytes();
+        let value = self.page[low as usize].wrapping_add(high);
+        self.seed = u16::from_be_bytes([value, low]);
+        value
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut state = self.page.to_vec();
+        state.extend_from_slice(&self.seed.to_le_bytes());
+        state
+    }
+
+    fn restore(&mut self, state: &[u8]) -> bool {
+        if state.len() != 256 + 2 {
+            return false;
+        }
+        self.page.copy_from_slice(&state[..256]);
+        self.seed = u16::from_le_bytes([state[256], state[257]]);
+        true
+    }
+}
+
+impl Emulator {
+    /// Replace the random source with the default generator seeded with `seed`
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Box::new(RNG::from_seed(seed));
+    }
+
+    /// Make a COSMAC VIP machine whose `CXNN` reproduces the original interpreter.
+    ///
+    /// `page` and `seed` are the interpreter's page at `0x0100` and the power-on value of `R9`,
+    /// see `VipRNG`.
+    pub fn with_vip_rng(page: [u8; 256], seed: u16) -> Self {
+        let mut emulator = Emulator::new(Variant::CosmacVip);
+        emulator.rng = Box::new(VipRNG::new(page, seed));
+        emulator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::random::{RandomSource, VipRNG, RNG};
+    use crate::emulator::Emulator;
+
+    /// Test that equal seeds produce equal sequences
+    #[test]
+    fn test_seed() {
+        let mut a = RNG::from_seed(42);
+        let mut b = RNG::from_seed(42);
+        let mut c = RNG::from_seed(43);
+        let a: Vec<u8> = (0..32).map(|_| a.rand()).collect();
+        let b: Vec<u8> = (0..32).map(|_| b.rand()).collect();
+        let c: Vec<u8> = (0..32).map(|_| c.rand()).collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    /// Test replaying a sequence from a saved state
+    #[test]
+    fn test_state() {
+        let mut r = RNG::from_seed(7);
+        r.rand();
+        let state = r.state();
+        let first = r.rand();
+        r.set_state(state);
+        assert_eq!(r.rand(), first);
+    }
+
+    /// Test saving and restoring generator state through the trait
+    #[test]
+    fn test_save_restore() {
+        let mut a = RNG::from_seed(3);
+        let mut b = RNG::from_seed(4);
+        assert!(b.restore(&a.save()));
+        assert_eq!(a.rand(), b.rand());
+        assert!(!b.restore(&[1, 2, 3]));
+        let mut v = VipRNG::new([5; 256], 0x1234);
+        let mut w = VipRNG::new([0; 256], 0);
+        assert!(w.restore(&v.save()));
+        assert_eq!(v, w);
+        assert_eq!(v.rand(), w.rand());
+        assert!(!w.restore(&a.save()));
+    }
+
+    /// Test the COSMAC VIP routine
+    #[test]
+    fn test_vip() {
+        let mut page = [0; 256];
+        for (i, byte) in page.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let mut r = VipRNG::new(page, 0x0000);
+        assert_eq!(r.rand(), 1);
+        assert_eq!(r.seed(), 0x0101);
+        assert_eq!(r.rand(), 3);
+        assert_eq!(r.rand(), 6);
+        assert_eq!(r.seed(), 0x0603);
+        r.set_seed(0xFFFF);
+        assert_eq!(r.rand(), 0);
+        assert_eq!(r.seed(), 0x0000);
+    }
+
+    /// Test `CXNN` on a VIP machine against a hand trace of the interpreter routine
+    #[test]
+    fn test_vip_machine() {
+        let mut page = [0; 256];
+        page[0xFF] = 0x0F;
+        page[0x00] = 0xF0;
+        page[0x01] = 0x5A;
+        page[0x02] = 0x3C;
+        let mut e = Emulator::with_vip_rng(page, 0x00FE);
+        e.load_rom(&[0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF, 0xC3, 0xF0])
+            .unwrap();
+        for _ in 0..4 {
+            e.step().unwrap();
+        }
+        // R9: 00FF -> 0FFF, 1000 -> 0000 (F0 + 10 wraps), 0001 -> 5A01, 5A02 -> 9602
+        assert_eq!(e.registers[..4], [0x0F, 0x00, 0x5A, 0x90]);
+        assert_eq!(e.rng.save()[256..], [0x02, 0x96]);
+    }
+
+    /// Test that the emulator can be sent to another thread
+    #[test]
+    fn test_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Emulator>();
+    }
+}
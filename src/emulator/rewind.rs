@@ -0,0 +1,255 @@
+use crate::emulator::Emulator;
+use std::collections::VecDeque;
+
+/// Changes that turn a save state back into an older one
+#[derive(Clone, Debug)]
+enum Delta {
+    /// Older bytes of every changed run, with the offset of the run
+    Runs(Vec<(usize, Vec<u8>)>),
+    /// The whole older state, used when the sizes differ
+    Full(Vec<u8>),
+}
+
+impl Delta {
+    /// Delta that turns `newer` into `older`
+    fn between(newer: &[u8], older: &[u8]) -> Self {
+        if newer.len() != older.len() {
+            return Delta::Full(older.to_vec());
+        }
+        let mut runs = Vec::new();
+        let mut i = 0;
+        while i < older.len() {
+            if older[i] == newer[i] {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < older.len() && older[i] != newer[i] {
+                i += 1;
+            }
+            runs.push((start, older[start..i].to_vec()));
+        }
+        Delta::Runs(runs)
+    }
+
+    /// Turn `state` into the older state
+    fn apply(&self, state: &mut Vec<u8>) {
+        match self {
+            Delta::Runs(runs) => {
+                for (offset, bytes) in runs {
+                    state[*offset..offset + bytes.len()].copy_from_slice(bytes);
+                }
+            }
+            Delta::Full(older) => *state = older.clone(),
+        }
+    }
+
+    /// Approximate number of bytes used
+    fn size(&self) -> usize {
+        match self {
+            Delta::Runs(runs) => runs
+                .iter()
+                .map(|(_, bytes)| std::mem::size_of::<(usize, Vec<u8>)>() + bytes.len())
+                .sum(),
+            Delta::Full(older) => older.len(),
+        }
+    }
+}
+
+/// Snapshot of the machine taken at `frame`
+#[derive(Clone, Debug)]
+struct Snapshot {
+    /// Number of the frame after which the snapshot was taken
+    frame: u64,
+    /// Turns the state of the next snapshot into the state of this one, empty for the newest
+    delta: Delta,
+}
+
+/// Bounded history of the machine state for stepping back in time.
+///
+/// A snapshot is taken every `interval` frames and the oldest ones are dropped past `capacity`.
+/// Only the newest snapshot is stored whole, as a save state.
+/// Older ones are stored as the bytes that differ from the next snapshot,
+/// so long histories of programs that change little per frame stay small.
+#[derive(Clone, Debug)]
+pub struct Rewind {
+    interval: u64,
+    capacity: usize,
+    frame: u64,
+    snapshots: VecDeque<Snapshot>,
+    latest: Vec<u8>,
+}
+
+impl Rewind {
+    /// Make an empty history with a snapshot every `interval` frames, keeping up to `capacity` of them
+    ///
+    /// # Panics:
+    /// Panics if `interval` or `capacity` is 0.
+    pub fn new(interval: u64, capacity: usize) -> Self {
+        assert!(interval > 0, "rewind interval must be positive");
+        assert!(capacity > 0, "rewind capacity must be positive");
+        Self {
+            interval,
+            capacity,
+            frame: 0,
+            snapshots: VecDeque::with_capacity(capacity),
+            latest: Vec::new(),
+        }
+    }
+
+    /// Number of frames recorded so far, minus the rewound ones
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Number of stored snapshots
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Check if there are no snapshots to rewind to
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Approximate number of bytes used by the snapshots
+    pub fn memory_usage(&self) -> usize {
+        self.latest.len()
+            + self
+                .snapshots
+                .iter()
+                .map(|snapshot| std::mem::size_of::<Snapshot>() + snapshot.delta.size())
+                .sum::<usize>()
+    }
+
+    /// Forget every snapshot and start counting frames from 0
+    pub fn clear(&mut self) {
+        self.frame = 0;
+        self.snapshots.clear();
+        self.latest.clear();
+    }
+
+    /// Count a finished frame of `emulator`, taking a snapshot if it's due
+    pub fn record_frame(&mut self, emulator: &Emulator) {
+        self.frame += 1;
+        if self.frame.is_multiple_of(self.interval) {
+            self.snapshot(emulator);
+        }
+    }
+
+    /// Take a snapshot of `emulator` now, regardless of the interval
+    pub fn snapshot(&mut self, emulator: &Emulator) {
+        let state = emulator.save_state();
+        if let Some(newest) = self.snapshots.back_mut() {
+            newest.delta = Delta::between(&state, &self.latest);
+        }
+        self.latest = state;
+        self.snapshots.push_back(Snapshot {
+            frame: self.frame,
+            delta: Delta::Runs(Vec::new()),
+        });
+        if self.snapshots.len() > self.capacity {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Step `emulator` back by at least `frames` frames.
+    ///
+    /// Restores the newest snapshot taken at or before the target frame,
+    /// or the oldest one if the history doesn't go back that far.
+    /// Snapshots newer than the restored one are dropped.
+    ///
+    /// Returns the number of frames actually rewound, or `None` if there are no snapshots.
+    pub fn rewind(&mut self, emulator: &mut Emulator, frames: u64) -> Option<u64> {
+        if self.snapshots.is_empty() {
+            return None;
+        }
+        let target = self.frame.saturating_sub(frames);
+        let index = self
+            .snapshots
+            .iter()
+            .rposition(|snapshot| snapshot.frame <= target)
+            .unwrap_or(0);
+        let mut state = std::mem::take(&mut self.latest);
+        while self.snapshots.len() > index + 1 {
+            self.snapshots.pop_back();
+            if let Some(snapshot) = self.snapshots.back() {
+                snapshot.delta.apply(&mut state);
+            }
+        }
+        let restored = self.snapshots.back_mut()?;
+        restored.delta = Delta::Runs(Vec::new());
+        emulator
+            .load_state(&state)
+            .expect("snapshots are valid save states");
+        let rewound = self.frame - restored.frame;
+        self.frame = restored.frame;
+        self.latest = state;
+        Some(rewound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::rewind::Rewind;
+    use crate::emulator::Emulator;
+
+    /// Make a machine that increments `V0` and stores it at `0x300` once per frame
+    fn counter() -> Emulator {
+        let mut e = Emulator::default();
+        e.load_rom(&[0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00])
+            .unwrap();
+        e
+    }
+
+    /// Run `frames` frames of the counter, recording them
+    fn run(e: &mut Emulator, r: &mut Rewind, frames: u64) {
+        for _ in 0..frames {
+            e.run_cycles(4).unwrap();
+            r.record_frame(e);
+        }
+    }
+
+    /// Test rewinding to an older snapshot and continuing from it
+    #[test]
+    fn test_rewind() {
+        let mut e = counter();
+        let mut r = Rewind::new(10, 100);
+        run(&mut e, &mut r, 100);
+        assert_eq!(r.len(), 10);
+        assert_eq!(e.get_reg(0), 100);
+        assert_eq!(r.rewind(&mut e, 25), Some(30));
+        assert_eq!(r.frame(), 70);
+        assert_eq!(e.get_reg(0), 70);
+        assert_eq!(e.memory[0x300], 70);
+        assert_eq!(r.len(), 7);
+        run(&mut e, &mut r, 20);
+        assert_eq!(r.len(), 9);
+        assert_eq!(r.rewind(&mut e, 10), Some(10));
+        assert_eq!(e.get_reg(0), 80);
+        assert_eq!(e.memory[0x300], 80);
+    }
+
+    /// Test that the history is bounded and rewinding stops at its start
+    #[test]
+    fn test_capacity() {
+        let mut e = counter();
+        let mut r = Rewind::new(1, 5);
+        assert_eq!(r.rewind(&mut e, 1), None);
+        run(&mut e, &mut r, 50);
+        assert_eq!(r.len(), 5);
+        assert_eq!(r.rewind(&mut e, 1000), Some(4));
+        assert_eq!(e.get_reg(0), 46);
+        assert_eq!(r.len(), 1);
+    }
+
+    /// Test that older snapshots are stored as small deltas
+    #[test]
+    fn test_memory_usage() {
+        let mut e = counter();
+        let mut r = Rewind::new(1, 1000);
+        run(&mut e, &mut r, 1000);
+        let state = e.save_state().len();
+        assert!(r.memory_usage() < state * 30);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::emulator::audio::Audio;
+use crate::emulator::cycle::CpuState;
+use crate::emulator::display::Display;
+use crate::emulator::font::{FONT_ADDRESS, LARGE_FONT_ADDRESS};
+use crate::emulator::timers::TimerClock;
+use crate::emulator::Emulator;
+use std::error::Error;
+use std::path::Path;
+use std::{fmt, fs, io};
+
+/// Error raised when a ROM can't be loaded
+#[derive(Debug)]
+pub enum RomError {
+    /// ROM of `size` bytes doesn't fit into `capacity` bytes available after the load address
+    TooLarge { size: usize, capacity: usize },
+    /// ROM file couldn't be read
+    Io(io::Error),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::TooLarge { size, capacity } => write!(
+                f,
+                "ROM is {} bytes long, but only {} bytes are available",
+                size, capacity
+            ),
+            RomError::Io(error) => write!(f, "can't read ROM: {}", error),
+        }
+    }
+}
+
+impl Error for RomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RomError::TooLarge { .. } => None,
+            RomError::Io(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(error: io::Error) -> Self {
+        RomError::Io(error)
+    }
+}
+
+impl Emulator {
+    /// Reset the machine and load `rom` at the load address, where execution starts.
+    ///
+    /// Fails if `rom` doesn't fit into memory, the machine isn't modified in that case.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        let start = self.load_address as usize;
+        let capacity = self.memory.len().saturating_sub(start);
+        if rom.len() > capacity {
+            return Err(RomError::TooLarge {
+                size: rom.len(),
+                capacity,
+            });
+        }
+        self.reset();
+        self.memory[start..start + rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+
+    /// Read a ROM from the file at `path` and load it like `load_rom` does
+    pub fn load_rom_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), RomError> {
+        let rom = fs::read(path)?;
+        self.load_rom(&rom)
+    }
+
+    /// Bring the machine to its power-on state, so another program can be run.
+    ///
+    /// Memory is cleared and the fonts are reloaded, `PC` points to the load address.
+    /// The display returns to the low resolution mode with only plane 1 selected.
+    /// Machine configuration and SUPER-CHIP user flags are kept.
+    /// Fonts whose address was set out of memory are moved back to their default address.
+    pub fn reset(&mut self) {
+        for byte in self.memory.iter_mut() {
+            *byte = 0;
+        }
+        let font = self.font;
+        if self.load_font(&font, self.font_address).is_err() {
+            self.load_font(&font, FONT_ADDRESS)
+                .expect("the font fits into every memory size");
+        }
+        let large_font = self.large_font;
+        if self
+            .load_large_font(&large_font, self.large_font_address)
+            .is_err()
+        {
+            self.load_large_font(&large_font, LARGE_FONT_ADDRESS)
+                .expect("the large font fits into every memory size");
+        }
+        self.registers = [0; 16];
+        self.index_register = 0;
+        self.program_counter = self.load_address;
+        self.stack.clear();
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.timer_clock = TimerClock::default();
+        self.state = CpuState::Running;
+        self.display = Display::default();
+        self.audio = Audio::default();
+        self.keypad.release_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::cycle::CpuState;
+    use crate::emulator::font::{FONT_ADDRESS, LARGE_FONT_ADDRESS};
+    use crate::emulator::rom::RomError;
+    use crate::emulator::variant::Variant;
+    use crate::emulator::Emulator;
+    use std::fs;
+
+    /// Test loading a ROM at the default address
+    #[test]
+    fn test_load_rom() {
+        let mut e = Emulator::default();
+        e.load_rom(&[0x12, 0x34, 0x56]).unwrap();
+        assert_eq!(e.memory[0x200..0x203], [0x12, 0x34, 0x56]);
+        assert_eq!(e.program_counter, 0x200);
+    }
+
+    /// Test loading a ROM at the ETI-660 address
+    #[test]
+    fn test_load_rom_eti_660() {
+        let mut e = Emulator::new(Variant::Eti660);
+        e.load_rom(&[0x12, 0x34]).unwrap();
+        assert_eq!(e.memory[0x600..0x602], [0x12, 0x34]);
+        assert_eq!(e.program_counter, 0x600);
+    }
+
+    /// Test rejecting a ROM that doesn't fit into memory
+    #[test]
+    fn test_load_rom_too_large() {
+        let mut e = Emulator::default();
+        e.load_rom(&[0xAA; 0xE00]).unwrap();
+        e.set_reg(0, 1);
+        match e.load_rom(&[0xBB; 0xE01]) {
+            Err(RomError::TooLarge { size, capacity }) => {
+                assert_eq!(size, 0xE01);
+                assert_eq!(capacity, 0xE00);
+            }
+            other => panic!("Expected TooLarge, got {:?}", other),
+        }
+        assert_eq!(e.get_reg(0), 1);
+        assert_eq!(e.memory[0xFFF], 0xAA);
+    }
+
+    /// Test that loading a ROM resets the machine
+    #[test]
+    fn test_load_rom_resets() {
+        let mut e = Emulator::default();
+        e.load_rom(&[0x22, 0x00, 0xAA, 0xBB]).unwrap();
+        e.run_cycles(3).unwrap();
+        e.set_reg(3, 1);
+        e.index_register = 0x300;
+        e.delay_timer = 5;
+        e.sound_timer = 5;
+        e.display.draw_sprite(0, 0, &[0xFF], false);
+        e.press_key(1);
+        e.state = CpuState::WaitingForKey { register: 0 };
+        e.load_rom(&[0x12, 0x00]).unwrap();
+        assert_eq!(e.memory[0x200..0x204], [0x12, 0x00, 0x00, 0x00]);
+        assert_eq!(e.get_reg(3), 0);
+        assert_eq!(e.index_register, 0);
+        assert!(e.stack.is_empty());
+        assert_eq!(e.delay_timer, 0);
+        assert_eq!(e.sound_timer, 0);
+        assert!(e.display.pixels().iter().all(|p| *p == 0));
+        assert!(!e.keypad.is_pressed(1));
+        assert_eq!(e.state, CpuState::Running);
+        assert_eq!(e.memory[0x50..0x55], [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+    }
+
+    /// Test moving fonts set out of memory back to their default address on reset
+    #[test]
+    fn test_reset_font_address() {
+        let mut e = Emulator {
+            font_address: 0xFFFF,
+            large_font_address: 0x0FA0,
+            ..Emulator::default()
+        };
+        e.load_rom(&[0x12, 0x00]).unwrap();
+        assert_eq!(e.font_address, FONT_ADDRESS);
+        assert_eq!(e.large_font_address, LARGE_FONT_ADDRESS);
+        assert_eq!(e.memory[0x50..0x55], [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+        assert_eq!(e.char_address(0x1), 0x55);
+    }
+
+    /// Test loading a ROM from a file
+    #[test]
+    fn test_load_rom_file() {
+        let path = std::env::temp_dir().join("my_chip_eight_test_load_rom_file.ch8");
+        fs::write(&path, [0x00, 0xE0]).unwrap();
+        let mut e = Emulator::default();
+        e.load_rom_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(e.memory[0x200..0x202], [0x00, 0xE0]);
+        match e.load_rom_file(&path) {
+            Err(RomError::Io(_)) => {}
+            other => panic!("Expected Io, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use crate::emulator::cycle::CpuState;
+use crate::emulator::quirks::Quirks;
+use crate::emulator::random::RNG;
+use crate::emulator::stack::Stack;
+use crate::emulator::timers::TimerClock;
+use crate::emulator::variant::Variant;
+use crate::emulator::Emulator;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// First bytes of every save state
+pub const MAGIC: [u8; 4] = *b"C8SS";
+
+/// Version of the format written by `save_state`
+///
+/// Adding sections doesn't change it, changing the layout of an existing section does.
+pub const VERSION: u16 = 2;
+
+/// Section tags
+const VARIANT: [u8; 4] = *b"VARI";
+const QUIRKS: [u8; 4] = *b"QRKS";
+const MEMORY: [u8; 4] = *b"MEMO";
+const REGISTERS: [u8; 4] = *b"REGS";
+const STACK: [u8; 4] = *b"STCK";
+const TIMERS: [u8; 4] = *b"TIMR";
+const CPU_STATE: [u8; 4] = *b"CPUS";
+const DISPLAY: [u8; 4] = *b"DISP";
+const KEYPAD: [u8; 4] = *b"KEYS";
+const FONTS: [u8; 4] = *b"FONT";
+const RPL_FLAGS: [u8; 4] = *b"RPLF";
+const AUDIO: [u8; 4] = *b"AUDI";
+const RANDOM: [u8; 4] = *b"RAND";
+
+/// Error raised when a save state can't be loaded
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum SaveStateError {
+    /// Data doesn't start with `MAGIC`
+    BadMagic,
+    /// Data was written by a newer, incompatible version of the format
+    UnsupportedVersion { version: u16 },
+    /// Data ends in the middle of a section
+    Truncated,
+    /// Section `tag` holds a value that doesn't make sense
+    InvalidSection { tag: [u8; 4] },
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveStateError::BadMagic => write!(f, "not a save state"),
+            SaveStateError::UnsupportedVersion { version } => {
+                write!(f, "save state version {} is not supported", version)
+            }
+            SaveStateError::Truncated => write!(f, "save state is truncated"),
+            SaveStateError::InvalidSection { tag } => write!(
+                f,
+                "save state section {} is invalid",
+                String::from_utf8_lossy(tag)
+            ),
+        }
+    }
+}
+
+impl Error for SaveStateError {}
+
+/// Cursor over the payload of a section
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        if self.bytes.len() < len {
+            return Err(SaveStateError::Truncated);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SaveStateError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, SaveStateError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, SaveStateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+/// Append section `tag` with `payload` to `out`
+fn write_section(out: &mut Vec<u8>, tag: [u8; 4], payload: &[u8]) {
+    out.extend_from_slice(&tag);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+}
+
+/// Section tag and payload
+type Section<'a> = ([u8; 4], &'a [u8]);
+
+/// Split a save state into its version and sections, checking the header
+fn read_sections(data: &[u8]) -> Result<(u16, Vec<Section<'_>>), SaveStateError> {
+    let mut reader = Reader { bytes: data };
+    if reader.bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return Err(SaveStateError::BadMagic);
+    }
+    let version = reader.u16()?;
+    if version > VERSION {
+        return Err(SaveStateError::UnsupportedVersion { version });
+    }
+    let mut sections = Vec::new();
+    while !reader.bytes.is_empty() {
+        let mut tag = [0; 4];
+        tag.copy_from_slice(reader.bytes(4)?);
+        let len = reader.u32()? as usize;
+        sections.push((tag, reader.bytes(len)?));
+    }
+    Ok((version, sections))
+}
+
+impl Emulator {
+    /// Serialize the whole machine into a versioned binary save state.
+    ///
+    /// The state starts with `MAGIC` and a little endian `VERSION`,
+    /// followed by sections made of a 4 byte tag, a little endian `u32` length and a payload.
+    /// The random source is included if it supports `RandomSource::save`.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&VERSION.to_le_bytes());
+
+        write_section(&mut out, VARIANT, &[self.variant.code()]);
+        write_section(&mut out, QUIRKS, &self.quirks.to_bytes());
+
+        write_section(&mut out, MEMORY, &self.memory);
+
+        let mut registers = self.registers.to_vec();
+        registers.extend_from_slice(&self.index_register.to_le_bytes());
+        registers.extend_from_slice(&self.program_counter.to_le_bytes());
+        registers.extend_from_slice(&self.load_address.to_le_bytes());
+        write_section(&mut out, REGISTERS, &registers);
+
+        let mut stack = (self.stack.depth() as u16).to_le_bytes().to_vec();
+        stack.extend_from_slice(&(self.stack.len() as u16).to_le_bytes());
+        for address in self.stack.entries() {
+            stack.extend_from_slice(&address.to_le_bytes());
+        }
+        write_section(&mut out, STACK, &stack);
+
+        let mut timers = vec![self.delay_timer, self.sound_timer];
+        let elapsed = self.timer_clock.elapsed().as_nanos() as u64;
+        timers.extend_from_slice(&elapsed.to_le_bytes());
+        timers.push(self.timer_clock.sound_active() as u8);
+        write_section(&mut out, TIMERS, &timers);
+
+        let cpu_state = match self.state {
+            CpuState::Running => [0, 0],
+            CpuState::WaitingForKey { register } => [1, register],
+            CpuState::WaitingForVBlank => [2, 0],
+            CpuState::Halted => [3, 0],
+        };
+        write_section(&mut out, CPU_STATE, &cpu_state);
+
+        let mut display = vec![self.display.is_hires() as u8, self.display.planes()];
+        display.extend_from_slice(self.display.pixels());
+        write_section(&mut out, DISPLAY, &display);
+
+        let keys = (0..16)
+            .filter(|key| self.keypad.is_pressed(*key))
+            .fold(0u16, |keys, key| keys | 1 << key);
+        write_section(&mut out, KEYPAD, &keys.to_le_bytes());
+
+        let mut fonts = self.font_address.to_le_bytes().to_vec();
+        fonts.extend_from_slice(&self.font.glyphs);
+        fonts.extend_from_slice(&self.large_font_address.to_le_bytes());
+        fonts.extend_from_slice(&self.large_font.glyphs);
+        write_section(&mut out, FONTS, &fonts);
+
+        write_section(&mut out, RPL_FLAGS, &self.rpl_flags);
+
+        let mut audio = self.audio.pattern.to_vec();
+        audio.push(self.audio.pitch);
+        write_section(&mut out, AUDIO, &audio);
+
+        let random = self.rng.save();
+        if !random.is_empty() {
+            write_section(&mut out, RANDOM, &random);
+        }
+
+        out
+    }
+
+    /// Restore the machine from a save state made by `save_state`.
+    ///
+    /// Sections missing from older save states keep the values of a freshly made machine
+    /// of the saved variant, unknown sections are skipped.
+    /// The random source is kept and restored from the state.
+    ///
+    /// # Errors:
+    /// Fails if `data` isn't a valid save state, or if the random source doesn't understand
+    /// the saved one, the machine isn't modified in that case.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SaveStateError> {
+        let (version, sections) = read_sections(data)?;
+        let variant = match sections.iter().find(|(tag, _)| *tag == VARIANT) {
+            Some((tag, payload)) => {
+                let code = Reader { bytes: payload }.u8()?;
+                Variant::from_code(code).ok_or(SaveStateError::InvalidSection { tag: *tag })?
+            }
+            None => self.variant,
+        };
+        let mut machine = Emulator::new(variant);
+        let mut random = None;
+        for (tag, payload) in sections {
+            let mut reader = Reader { bytes: payload };
+            let invalid = SaveStateError::InvalidSection { tag };
+            match tag {
+                QUIRKS => {
+                    let mut bytes = [0; Quirks::SIZE];
+                    bytes.copy_from_slice(reader.bytes(Quirks::SIZE)?);
+                    machine.quirks = Quirks::from_bytes(bytes).ok_or(invalid)?;
+                }
+                MEMORY => {
+                    if payload.len() != machine.memory.len() {
+                        return Err(invalid);
+                    }
+                    machine.memory.copy_from_slice(payload);
+                }
+                REGISTERS => {
+                    machine.registers.copy_from_slice(reader.bytes(16)?);
+                    machine.index_register = reader.u16()?;
+                    machine.program_counter = reader.u16()?;
+                    machine.load_address = reader.u16()?;
+                }
+                STACK => {
+                    let depth = reader.u16()? as usize;
+                    let len = if version < 2 {
+                        reader.u8()? as u16
+                    } else {
+                        reader.u16()?
+                    };
+                    let mut stack = Stack::new(depth);
+                    for _ in 0..len {
+                        stack.push(reader.u16()?).map_err(|_| invalid)?;
+                    }
+                    machine.stack = stack;
+                }
+                TIMERS => {
+                    machine.delay_timer = reader.u8()?;
+                    machine.sound_timer = reader.u8()?;
+                    let elapsed = Duration::from_nanos(reader.u64()?);
+                    machine.timer_clock = TimerClock::new(elapsed, reader.u8()? != 0);
+                }
+                CPU_STATE => {
+                    machine.state = match (reader.u8()?, reader.u8()?) {
+                        (0, _) => CpuState::Running,
+                        (1, register) if register < 16 => CpuState::WaitingForKey { register },
+                        (2, _) => CpuState::WaitingForVBlank,
+                        (3, _) => CpuState::Halted,
+                        _ => return Err(invalid),
+                    };
+                }
+                DISPLAY => {
+                    let hires = reader.u8()? != 0;
+                    machine.display.set_hires(hires);
+                    machine.display.select_planes(reader.u8()?);
+                    let (width, height) = (machine.display.width(), machine.display.height());
+                    let pixels = reader.bytes(width * height)?;
+                    for (i, color) in pixels.iter().enumerate() {
+                        machine.display.set_color(i % width, i / width, *color);
+                    }
+                }
+                KEYPAD => {
+                    let keys = reader.u16()?;
+                    for key in (0..16).filter(|key| keys & 1 << key != 0) {
+                        machine.keypad.press(key);
+                    }
+                }
+                FONTS => {
+                    machine.font_address = reader.u16()?;
+                    machine.font.glyphs.copy_from_slice(reader.bytes(80)?);
+                    machine.large_font_address = reader.u16()?;
+                    machine
+                        .large_font
+                        .glyphs
+                        .copy_from_slice(reader.bytes(160)?);
+                    let fits =
+                        |address: u16, len: usize| address as usize + len <= machine.memory.len();
+                    if !fits(machine.font_address, 80) || !fits(machine.large_font_address, 160) {
+                        return Err(invalid);
+                    }
+                }
+                RPL_FLAGS => machine.rpl_flags.copy_from_slice(reader.bytes(16)?),
+                AUDIO => {
+                    machine.audio.pattern.copy_from_slice(reader.bytes(16)?);
+                    machine.audio.pitch = reader.u8()?;
+                }
+                RANDOM => random = Some(payload),
+                _ => {}
+            }
+        }
+        if let Some(random) = random {
+            if !self.rng.restore(random) {
+                return Err(SaveStateError::InvalidSection { tag: RANDOM });
+            }
+        }
+        machine.rng = std::mem::replace(&mut self.rng, Box::new(RNG::default()));
+        *self = machine;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::cycle::CpuState;
+    use crate::emulator::quirks::Quirks;
+    use crate::emulator::save_state::{SaveStateError, MAGIC};
+    use crate::emulator::stack::Stack;
+    use crate::emulator::variant::Variant;
+    use crate::emulator::Emulator;
+
+    /// Test that a saved machine continues exactly like the original
+    #[test]
+    fn test_round_trip() {
+        let mut e = Emulator::new(Variant::SuperChip);
+        e.seed_rng(5);
+        e.load_rom(&[
+            0x00, 0xFF, 0x22, 0x06, 0x12, 0x00, 0xC0, 0xFF, 0xD0, 0x10, 0x00, 0xEE,
+        ])
+        .unwrap();
+        e.run_cycles(4).unwrap();
+        e.delay_timer = 30;
+        e.press_key(0xC);
+        e.rpl_flags[3] = 9;
+        let saved = e.save_state();
+
+        let mut l = Emulator::default();
+        l.load_state(&saved).unwrap();
+        assert_eq!(l.variant, Variant::SuperChip);
+        assert_eq!(l.quirks, Quirks::SCHIP_11);
+        assert_eq!(l.memory, e.memory);
+        assert_eq!(l.registers, e.registers);
+        assert_eq!(l.program_counter, e.program_counter);
+        assert_eq!(l.stack.entries(), e.stack.entries());
+        assert_eq!(l.delay_timer, 30);
+        assert!(l.keypad.is_pressed(0xC));
+        assert_eq!(l.rpl_flags[3], 9);
+        assert!(l.display.is_hires());
+        assert_eq!(l.display.pixels(), e.display.pixels());
+        assert_eq!(l.save_state(), saved);
+
+        e.tick_timers();
+        l.tick_timers();
+        e.run_cycles(20).unwrap();
+        l.run_cycles(20).unwrap();
+        assert_eq!(l.registers, e.registers);
+        assert_eq!(l.display.pixels(), e.display.pixels());
+    }
+
+    /// Test that unknown sections are skipped and missing ones keep their defaults
+    #[test]
+    fn test_sections() {
+        let mut e = Emulator::new(Variant::XoChip);
+        e.state = CpuState::WaitingForKey { register: 4 };
+        let mut saved = MAGIC.to_vec();
+        saved.extend_from_slice(&1u16.to_le_bytes());
+        saved.extend_from_slice(b"VARI\x01\x00\x00\x00\x00");
+        saved.extend_from_slice(b"NEWS\x02\x00\x00\x00ab");
+        e.load_state(&saved).unwrap();
+        assert_eq!(e.variant, Variant::CosmacVip);
+        assert_eq!(e.memory.len(), 0x1000);
+        assert_eq!(e.state, CpuState::Running);
+        assert_eq!(e.program_counter, 0x200);
+    }
+
+    /// Test saving deep stacks, and reading the stack of version 1 save states
+    #[test]
+    fn test_stack() {
+        let mut e = Emulator {
+            stack: Stack::new(300),
+            ..Emulator::default()
+        };
+        for address in 0..260 {
+            e.stack.push(address).unwrap();
+        }
+        let mut l = Emulator::default();
+        l.load_state(&e.save_state()).unwrap();
+        assert_eq!(l.stack.depth(), 300);
+        assert_eq!(l.stack.entries(), e.stack.entries());
+
+        let mut saved = MAGIC.to_vec();
+        saved.extend_from_slice(&1u16.to_le_bytes());
+        saved.extend_from_slice(b"STCK\x05\x00\x00\x00\x0C\x00\x01\x00\x03");
+        l.load_state(&saved).unwrap();
+        assert_eq!(l.stack.depth(), 12);
+        assert_eq!(l.stack.entries(), [0x300]);
+    }
+
+    /// Test rejecting invalid save states
+    #[test]
+    fn test_invalid() {
+        let mut e = Emulator::default();
+        e.registers[0] = 7;
+        assert_eq!(e.load_state(b"nope"), Err(SaveStateError::BadMagic));
+        let mut saved = e.save_state();
+        saved[4] = 0xFF;
+        assert_eq!(
+            e.load_state(&saved),
+            Err(SaveStateError::UnsupportedVersion { version: 0x00FF })
+        );
+        let saved = e.save_state();
+        assert_eq!(
+            e.load_state(&saved[..saved.len() - 1]),
+            Err(SaveStateError::Truncated)
+        );
+        let mut saved = MAGIC.to_vec();
+        saved.extend_from_slice(&1u16.to_le_bytes());
+        saved.extend_from_slice(b"VARI\x01\x00\x00\x00\x09");
+        assert_eq!(
+            e.load_state(&saved),
+            Err(SaveStateError::InvalidSection { tag: *b"VARI" })
+        );
+        assert_eq!(e.registers[0], 7);
+    }
+
+    /// Test rejecting the state of another kind of random source
+    #[test]
+    fn test_random_mismatch() {
+        let saved = Emulator::with_vip_rng([0; 256], 0x1234).save_state();
+        let mut e = Emulator::default();
+        e.seed_rng(1);
+        let mut expected = Emulator::default();
+        expected.seed_rng(1);
+        assert_eq!(
+            e.load_state(&saved),
+            Err(SaveStateError::InvalidSection { tag: *b"RAND" })
+        );
+        assert_eq!(e.rng.rand(), expected.rng.rand());
+    }
+
+    /// Test rejecting fonts that don't fit in memory
+    #[test]
+    fn test_invalid_font_address() {
+        let mut e = Emulator::default();
+        for (font, large_font) in [(0xFFFFu16, 0x0A0u16), (0x050, 0x0FA0)].iter() {
+            let mut saved = MAGIC.to_vec();
+            saved.extend_from_slice(&1u16.to_le_bytes());
+            saved.extend_from_slice(b"FONT");
+            saved.extend_from_slice(&244u32.to_le_bytes());
+            saved.extend_from_slice(&font.to_le_bytes());
+            saved.extend_from_slice(&[0; 80]);
+            saved.extend_from_slice(&large_font.to_le_bytes());
+            saved.extend_from_slice(&[0; 160]);
+            assert_eq!(
+                e.load_state(&saved),
+                Err(SaveStateError::InvalidSection { tag: *b"FONT" })
+            );
+        }
+        assert_eq!(e.font_address, 0x050);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::emulator::error::EmulatorError;
+
+/// Call stack with a fixed depth
+///
+/// Storage for all levels is allocated on creation, so pushing never allocates.
+#[derive(Clone, Debug)]
+pub struct Stack {
+    entries: Vec<u16>,
+    depth: usize,
+}
+
+impl Stack {
+    /// Make an empty stack that holds up to `depth` return addresses
+    pub fn new(depth: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(depth),
+            depth,
+        }
+    }
+
+    /// Maximum number of return addresses
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Number of return addresses on the stack
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the stack holds no return addresses
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return addresses, from the bottom of the stack to the top
+    pub fn entries(&self) -> &[u16] {
+        &self.entries
+    }
+
+    /// Push `address` onto the stack
+    ///
+    /// Fails with `StackOverflow` if the stack is full
+    pub fn push(&mut self, address: u16) -> Result<(), EmulatorError> {
+        if self.entries.len() == self.depth {
+            return Err(EmulatorError::StackOverflow);
+        }
+        self.entries.push(address);
+        Ok(())
+    }
+
+    /// Pop an address from the top of the stack
+    ///
+    /// Fails with `StackUnderflow` if the stack is empty
+    pub fn pop(&mut self) -> Result<u16, EmulatorError> {
+        self.entries.pop().ok_or(EmulatorError::StackUnderflow)
+    }
+
+    /// Remove every address from the stack
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::error::EmulatorError;
+    use crate::emulator::stack::Stack;
+
+    /// Test pushing and popping addresses
+    #[test]
+    fn test_push_pop() {
+        let mut s = Stack::new(2);
+        s.push(0x200).unwrap();
+        s.push(0x300).unwrap();
+        assert_eq!(s.entries(), [0x200, 0x300]);
+        assert_eq!(s.pop(), Ok(0x300));
+        assert_eq!(s.pop(), Ok(0x200));
+        assert!(s.is_empty());
+    }
+
+    /// Test pushing onto a full stack
+    #[test]
+    fn test_overflow() {
+        let mut s = Stack::new(1);
+        s.push(0x200).unwrap();
+        assert_eq!(s.push(0x300), Err(EmulatorError::StackOverflow));
+        assert_eq!(s.entries(), [0x200]);
+    }
+
+    /// Test popping from an empty stack
+    #[test]
+    fn test_underflow() {
+        let mut s = Stack::new(1);
+        assert_eq!(s.pop(), Err(EmulatorError::StackUnderflow));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::emulator::cycle::CpuState;
+use crate::emulator::Emulator;
+use std::time::Duration;
+
+/// Frequency of the delay and sound timers in Hz
+pub const TIMER_FREQUENCY: u32 = 60;
+
+/// Time between two timer ticks
+pub const TIMER_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / TIMER_FREQUENCY as u64);
+
+/// Change of the sound timer state, for audio backends to follow
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum SoundEvent {
+    /// Sound timer became non-zero, the buzzer should start
+    Started,
+    /// Sound timer reached zero, the buzzer should stop
+    Stopped,
+}
+
+/// Bookkeeping of the timer subsystem
+#[derive(Clone, Default, Debug)]
+pub struct TimerClock {
+    /// Time that passed since the last tick
+    elapsed: Duration,
+    /// Sound state that was last reported
+    sound_active: bool,
+}
+
+impl TimerClock {
+    /// Make a clock in the given state, e.g. to restore a saved one
+    pub fn new(elapsed: Duration, sound_active: bool) -> Self {
+        Self {
+            elapsed,
+            sound_active,
+        }
+    }
+
+    /// Time that passed since the last tick
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Sound state that was last reported
+    pub fn sound_active(&self) -> bool {
+        self.sound_active
+    }
+}
+
+impl Emulator {
+    /// Decrement both timers once, as it happens 60 times per second.
+    ///
+    /// For hosts that drive emulation frame by frame.
+    /// Instructions aren't executed, so any number of them may be run between ticks.
+    /// Resumes the processor if it awaits a timer tick.
+    ///
+    /// Returns a sound event if the buzzer state changed since the last report.
+    pub fn tick_timers(&mut self) -> Option<SoundEvent> {
+        self.tick();
+        self.sound_event()
+    }
+
+    /// Advance timers by `elapsed` time, ticking them once per `TIMER_PERIOD`.
+    ///
+    /// For hosts that drive emulation by a clock.
+    /// Time that doesn't add up to a full tick is carried over to the next call.
+    ///
+    /// Returns a sound event if the buzzer state changed since the last report.
+    pub fn update_timers(&mut self, elapsed: Duration) -> Option<SoundEvent> {
+        self.timer_clock.elapsed += elapsed;
+        while self.timer_clock.elapsed >= TIMER_PERIOD {
+            self.timer_clock.elapsed -= TIMER_PERIOD;
+            self.tick();
+        }
+        self.sound_event()
+    }
+
+    /// Decrement both timers and signal the vertical blank interrupt
+    fn tick(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+        if self.state == CpuState::WaitingForVBlank {
+            self.state = CpuState::Running;
+        }
+    }
+
+    /// Check if the buzzer should sound
+    pub fn is_sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Report the buzzer state if it changed since the last report
+    fn sound_event(&mut self) -> Option<SoundEvent> {
+        let active = self.is_sound_active();
+        if active == self.timer_clock.sound_active {
+            return None;
+        }
+        self.timer_clock.sound_active = active;
+        if active {
+            Some(SoundEvent::Started)
+        } else {
+            Some(SoundEvent::Stopped)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::opcode::OpCode::*;
+    use crate::emulator::timers::{SoundEvent, TIMER_PERIOD};
+    use crate::emulator::Emulator;
+    use std::time::Duration;
+
+    /// Test ticking timers once per frame
+    #[test]
+    fn test_tick_timers() {
+        let mut e = Emulator {
+            delay_timer: 2,
+            ..Emulator::default()
+        };
+        e.tick_timers();
+        assert_eq!(e.delay_timer, 1);
+        e.tick_timers();
+        e.tick_timers();
+        assert_eq!(e.delay_timer, 0);
+    }
+
+    /// Test that timers aren't affected by executed instructions
+    #[test]
+    fn test_timers_independent_of_cycles() {
+        let mut e = Emulator::default();
+        e.load_rom(&[0x12, 0x00]).unwrap();
+        e.delay_timer = 10;
+        e.run_cycles(1000).unwrap();
+        assert_eq!(e.delay_timer, 10);
+    }
+
+    /// Test ticking timers by elapsed time
+    #[test]
+    fn test_update_timers() {
+        let mut e = Emulator {
+            delay_timer: 10,
+            ..Emulator::default()
+        };
+        e.update_timers(TIMER_PERIOD / 2);
+        assert_eq!(e.delay_timer, 10);
+        e.update_timers(TIMER_PERIOD / 2 + TIMER_PERIOD * 2);
+        assert_eq!(e.delay_timer, 7);
+        e.update_timers(Duration::from_secs(1));
+        assert_eq!(e.delay_timer, 0);
+    }
+
+    /// Test sound events
+    #[test]
+    fn test_sound_events() {
+        let mut e = Emulator::default();
+        e.set_reg(0, 2);
+        e.execute_opcode(SetSoundTimerToReg { register: 0 })
+            .unwrap();
+        assert_eq!(
+            e.update_timers(Duration::from_millis(1)),
+            Some(SoundEvent::Started)
+        );
+        assert_eq!(e.tick_timers(), None);
+        assert!(e.is_sound_active());
+        assert_eq!(e.tick_timers(), Some(SoundEvent::Stopped));
+        assert_eq!(e.tick_timers(), None);
+        assert!(!e.is_sound_active());
+    }
+}
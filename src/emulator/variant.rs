@@ -0,0 +1,84 @@
+use crate::emulator::quirks::Quirks;
+
+/// Machine variant: the interpreter whose behaviour is emulated
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default)]
+pub enum Variant {
+    /// Original CHIP-8 interpreter of the COSMAC VIP
+    CosmacVip,
+    /// CHIP-8 as implemented by most modern interpreters
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1 for HP-48 calculators
+    SuperChip,
+    /// CHIP-8 interpreter of the ETI-660, which loads programs at `0x600`
+    Eti660,
+    /// XO-CHIP as defined by Octo, with 64 KiB of memory
+    XoChip,
+}
+
+impl Variant {
+    /// Number of nested subroutine calls the interpreter supports
+    pub fn stack_depth(self) -> usize {
+        match self {
+            Variant::CosmacVip => 12,
+            Variant::Chip8 | Variant::SuperChip | Variant::Eti660 | Variant::XoChip => 16,
+        }
+    }
+
+    /// Size of the memory in bytes
+    pub fn memory_size(self) -> usize {
+        match self {
+            Variant::XoChip => 0x10000,
+            _ => 0x1000,
+        }
+    }
+
+    /// Behaviour of ambiguous instructions
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Variant::CosmacVip | Variant::Eti660 => Quirks::COSMAC_VIP,
+            Variant::Chip8 => Quirks::default(),
+            Variant::SuperChip => Quirks::SCHIP_11,
+            Variant::XoChip => Quirks::XO_CHIP,
+        }
+    }
+
+    /// Check if `DXY0` draws a 16x16 sprite, instead of a sprite of 0 rows
+    pub fn has_large_sprites(self) -> bool {
+        match self {
+            Variant::SuperChip | Variant::XoChip => true,
+            Variant::CosmacVip | Variant::Chip8 | Variant::Eti660 => false,
+        }
+    }
+
+    /// Number identifying the variant in save states and movies
+    pub fn code(self) -> u8 {
+        match self {
+            Variant::CosmacVip => 0,
+            Variant::Chip8 => 1,
+            Variant::SuperChip => 2,
+            Variant::Eti660 => 3,
+            Variant::XoChip => 4,
+        }
+    }
+
+    /// Variant identified by `code`, see `Variant::code`
+    pub fn from_code(code: u8) -> Option<Variant> {
+        match code {
+            0 => Some(Variant::CosmacVip),
+            1 => Some(Variant::Chip8),
+            2 => Some(Variant::SuperChip),
+            3 => Some(Variant::Eti660),
+            4 => Some(Variant::XoChip),
+            _ => None,
+        }
+    }
+
+    /// Address where programs are loaded and start executing
+    pub fn load_address(self) -> u16 {
+        match self {
+            Variant::Eti660 => 0x600,
+            _ => 0x200,
+        }
+    }
+}
@@ -1,2 +1,12 @@
+/// Control-flow analysis of ROMs
+pub mod analysis;
+/// Assembler for opcode mnemonics
+pub mod assembler;
+/// Structured pseudo-code decompiler
+pub mod decompiler;
+/// Opcode mnemonics and ROM listings
+pub mod disassembler;
 /// Emulation structs and logic
 pub mod emulator;
+/// Octo language compiler
+pub mod octo;
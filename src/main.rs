@@ -1,5 +1,4 @@
 #[cfg_attr(tarpaulin, skip)]
-
 fn main() {
     println!("Hello, world!");
 }
@@ -0,0 +1,137 @@
+use crate::assembler::error::{AssemblyError, ErrorKind};
+use crate::octo::Compiler;
+use std::f64::consts;
+
+impl Compiler {
+    /// Evaluate a `{ expression }` block.
+    ///
+    /// Like in Octo, operators have no precedence and are evaluated from right to left,
+    /// so `2 * 3 + 1` is 8. Parentheses group terms.
+    pub fn calc_block(&mut self) -> Result<f64, AssemblyError> {
+        self.expect("{")?;
+        let value = self.calc_expression()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+
+    /// Evaluate `term [operator expression]`
+    fn calc_expression(&mut self) -> Result<f64, AssemblyError> {
+        let left = self.calc_term()?;
+        match self.peek() {
+            Some(")") | Some("}") | None => return Ok(left),
+            _ => {}
+        }
+        let operator = self.next()?;
+        let right = self.calc_expression()?;
+        let int = |value: f64| value as i64;
+        let bool = |value: bool| if value { 1.0 } else { 0.0 };
+        let value = match operator.text.as_str() {
+            "-" => left - right,
+            "+" => left + right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "&" => (int(left) & int(right)) as f64,
+            "|" => (int(left) | int(right)) as f64,
+            "^" => (int(left) ^ int(right)) as f64,
+            "<<" => int(left).wrapping_shl(int(right) as u32) as f64,
+            ">>" => int(left).wrapping_shr(int(right) as u32) as f64,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "<" => bool(left < right),
+            "<=" => bool(left <= right),
+            "==" => bool(left == right),
+            "!=" => bool(left != right),
+            ">=" => bool(left >= right),
+            ">" => bool(left > right),
+            found => {
+                return Err(self.error(ErrorKind::Expected {
+                    expected: "operator".to_string(),
+                    found: found.to_string(),
+                }))
+            }
+        };
+        Ok(value)
+    }
+
+    /// Evaluate a number, a name, a parenthesized expression or a unary operator applied to a term
+    fn calc_term(&mut self) -> Result<f64, AssemblyError> {
+        let token = self.next()?;
+        let unary: Option<fn(f64) -> f64> = match token.text.as_str() {
+            "-" => Some(|value| -value),
+            "~" => Some(|value| !(value as i64) as f64),
+            "!" => Some(|value| if value == 0.0 { 1.0 } else { 0.0 }),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "exp" => Some(f64::exp),
+            "log" => Some(f64::ln),
+            "abs" => Some(f64::abs),
+            "sqrt" => Some(f64::sqrt),
+            "sign" => Some(|value| if value == 0.0 { 0.0 } else { value.signum() }),
+            "ceil" => Some(f64::ceil),
+            "floor" => Some(f64::floor),
+            _ => None,
+        };
+        if let Some(unary) = unary {
+            return Ok(unary(self.calc_term()?));
+        }
+        match token.text.as_str() {
+            "(" => {
+                let value = self.calc_expression()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            "@" => {
+                let address = self.calc_term()? as i64;
+                self.main_first = false;
+                Ok(self.rom_byte(address) as f64)
+            }
+            "HERE" => {
+                self.main_first = false;
+                Ok(self.here as f64)
+            }
+            "PI" => Ok(consts::PI),
+            "E" => Ok(consts::E),
+            text => self.known_value(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::octo::compile;
+
+    /// Value of the `:calc` expression `expression`
+    fn calc(expression: &str) -> f64 {
+        let source = format!(": main :calc x {{ {} }}", expression);
+        compile("test.8o", &source).unwrap().constants["x"]
+    }
+
+    /// Test operators and their right to left evaluation
+    #[test]
+    fn test_calc() {
+        assert_eq!(calc("2 * 3 + 1"), 8.0);
+        assert_eq!(calc("( 2 * 3 ) + 1"), 7.0);
+        assert_eq!(calc("3 / 2"), 1.5);
+        assert_eq!(calc("1 << 4 | 1"), 32.0);
+        assert_eq!(calc("- 3 + 5"), 2.0);
+        assert_eq!(calc("0xFF & ~ 0x0F"), 240.0);
+        assert_eq!(calc("2 pow 3 max 1"), 8.0);
+        assert_eq!(calc("! 0 == 0"), 0.0);
+        assert_eq!(calc("floor 2.5"), 2.0);
+        assert_eq!(calc("HERE + 2"), 0x202 as f64);
+    }
+
+    /// Test reading back compiled bytes
+    #[test]
+    fn test_calc_rom() {
+        let output = compile(
+            "test.8o",
+            ": main 0x12 0x34 : after :calc second { @ ( after - 1 ) }",
+        )
+        .unwrap();
+        assert_eq!(output.constants["second"], 0x34 as f64);
+    }
+}
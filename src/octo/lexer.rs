@@ -0,0 +1,55 @@
+/// Word of the source, with the line it's on
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Token {
+    /// Text of the word
+    pub text: String,
+    /// Line number, starting from 1
+    pub line: usize,
+    /// Number of nested macro expansions that produced the token, 0 in the source
+    pub depth: usize,
+}
+
+/// Split `source` into whitespace separated tokens, dropping `#` comments
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        for word in line.split_whitespace() {
+            if word.starts_with('#') {
+                break;
+            }
+            tokens.push(Token {
+                text: word.to_string(),
+                line: index + 1,
+                depth: 0,
+            });
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::octo::lexer::{tokenize, Token};
+
+    /// Test splitting source into tokens
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize(": main # entry point\n\tv0 := 0x1F\n#done\n;");
+        let token = |text: &str, line| Token {
+            text: text.to_string(),
+            line,
+            depth: 0,
+        };
+        assert_eq!(
+            tokens,
+            vec![
+                token(":", 1),
+                token("main", 1),
+                token("v0", 2),
+                token(":=", 2),
+                token("0x1F", 2),
+                token(";", 4),
+            ]
+        );
+    }
+}
@@ -0,0 +1,732 @@
+use crate::assembler::error::{AssemblyError, ErrorKind, Location};
+use crate::emulator::opcode::OpCode;
+use crate::octo::lexer::{tokenize, Token};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Contains `:calc` expressions
+pub mod calc;
+/// Contains source tokenizing
+pub mod lexer;
+/// Contains statement compilation
+pub mod statement;
+
+/// Address programs are loaded at
+const START: usize = 0x200;
+
+/// Deepest nesting of macro expansions
+const MAX_DEPTH: usize = 64;
+
+/// Compiled program with its debugging information
+#[derive(PartialEq, Clone, Debug)]
+pub struct Output {
+    /// ROM bytes, to be loaded at `0x200`
+    pub rom: Vec<u8>,
+    /// Addresses of the labels
+    pub labels: BTreeMap<String, u16>,
+    /// Values of the `:const` and `:calc` constants
+    pub constants: BTreeMap<String, f64>,
+    /// Registers named by `:alias`
+    pub aliases: BTreeMap<String, u8>,
+    /// Names of the `:breakpoint`s, by address
+    pub breakpoints: BTreeMap<u16, String>,
+}
+
+/// Field that refers to a label defined later
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+enum FixupKind {
+    /// 12-bit address in the low bits of an opcode
+    Address,
+    /// 16-bit address in the second word of `i := long`
+    Long,
+    /// Address split between the constants of `v0 :=` and `v1 :=` by `:unpack`,
+    /// with `nibble` in the high bits, or all 16 bits for `:unpack long`
+    Unpack { nibble: Option<u8> },
+}
+
+impl FixupKind {
+    /// Number of bits the address has to fit in
+    fn bits(&self) -> u32 {
+        match self {
+            FixupKind::Address | FixupKind::Unpack { nibble: Some(_) } => 12,
+            FixupKind::Long | FixupKind::Unpack { nibble: None } => 16,
+        }
+    }
+}
+
+/// Reference to a label that isn't defined yet, patched once compilation is done
+#[derive(Clone, Debug)]
+struct Fixup {
+    /// Address of the opcode that refers to the label
+    address: usize,
+    /// Field of the opcode to patch
+    kind: FixupKind,
+    /// Name of the label
+    name: String,
+    /// Where the label is referred to
+    location: Location,
+}
+
+/// Open block
+#[derive(Clone, Debug)]
+enum Block {
+    /// `if ... begin`, with the jump over its body
+    If { jump: usize, location: Location },
+    /// `else`, with the jump over its body
+    Else { jump: usize, location: Location },
+    /// `loop`, with the jumps out of it of its `while`s
+    Loop {
+        start: usize,
+        whiles: Vec<usize>,
+        location: Location,
+    },
+}
+
+/// Macro defined by `:macro`
+#[derive(Clone, Debug)]
+struct Macro {
+    /// Names replaced by the arguments
+    parameters: Vec<String>,
+    /// Tokens between the braces
+    body: Vec<Token>,
+    /// Number of expansions so far, available as `CALLS`
+    calls: usize,
+}
+
+/// Single-pass compiler that patches forward references at the end
+struct Compiler {
+    file: String,
+    tokens: VecDeque<Token>,
+    line: usize,
+    here: usize,
+    rom: Vec<u8>,
+    written: Vec<bool>,
+    jump_to_main: bool,
+    main_first: bool,
+    labels: BTreeMap<String, u16>,
+    constants: BTreeMap<String, f64>,
+    aliases: BTreeMap<String, u8>,
+    breakpoints: BTreeMap<u16, String>,
+    macros: BTreeMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    blocks: Vec<Block>,
+}
+
+/// Compile the Octo program `source`, naming it `file` in errors.
+///
+/// Programs start at `main`: unless only `:const`, `:alias`, `:macro` and `:calc`s
+/// that don't depend on addresses come before it,
+/// `0x200` holds a jump to it, like in the reference compiler.
+/// Supported are labels, `:const`, `:alias`, `:unpack`, `:next`, `:org`, `:byte`, `:call`,
+/// `:macro`, `:calc`, `:breakpoint`, `:monitor`, `if ... then`, `if ... begin ... else ... end`,
+/// `loop ... while ... again` and the statements of CHIP-8, SUPER-CHIP and XO-CHIP
+/// that have an `OpCode`.
+pub fn compile(file: &str, source: &str) -> Result<Output, AssemblyError> {
+    let mut compiler = Compiler {
+        file: file.to_string(),
+        tokens: tokenize(source).into_iter().collect(),
+        line: 1,
+        here: START,
+        rom: Vec::new(),
+        written: Vec::new(),
+        jump_to_main: true,
+        main_first: true,
+        labels: BTreeMap::new(),
+        constants: BTreeMap::new(),
+        aliases: BTreeMap::new(),
+        breakpoints: BTreeMap::new(),
+        macros: BTreeMap::new(),
+        fixups: Vec::new(),
+        blocks: Vec::new(),
+    };
+    compiler.emit(OpCode::Goto { target: 0 })?;
+    while !compiler.tokens.is_empty() {
+        compiler.statement()?;
+    }
+    compiler.finish()
+}
+
+/// Parse a numeric literal: decimal, `0x` hexadecimal or `0b` binary, optionally negative
+fn number(text: &str) -> Option<f64> {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let lower = digits.to_ascii_lowercase();
+    let value = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()? as f64
+    } else {
+        lower.parse::<f64>().ok()?
+    };
+    Some(if digits.len() < text.len() {
+        -value
+    } else {
+        value
+    })
+}
+
+impl Compiler {
+    /// Position of the last read token
+    fn location(&self) -> Location {
+        Location {
+            file: self.file.clone(),
+            line: self.line,
+        }
+    }
+
+    /// Error at the last read token
+    fn error(&self, kind: ErrorKind) -> AssemblyError {
+        AssemblyError {
+            location: self.location(),
+            kind,
+        }
+    }
+
+    /// Read the next token
+    fn next(&mut self) -> Result<Token, AssemblyError> {
+        match self.tokens.pop_front() {
+            Some(token) => {
+                self.line = token.line;
+                Ok(token)
+            }
+            None => Err(self.error(ErrorKind::Expected {
+                expected: "more tokens".to_string(),
+                found: "end of file".to_string(),
+            })),
+        }
+    }
+
+    /// Text of the next token, without reading it
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(|token| token.text.as_str())
+    }
+
+    /// Read the next token, failing if it isn't `text`
+    fn expect(&mut self, text: &str) -> Result<(), AssemblyError> {
+        let token = self.next()?;
+        if token.text == text {
+            Ok(())
+        } else {
+            Err(self.error(ErrorKind::Expected {
+                expected: format!("`{}`", text),
+                found: token.text,
+            }))
+        }
+    }
+
+    /// Register named `text`: `v0` to `vf` or an alias
+    fn register_named(&self, text: &str) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(text) {
+            return Some(register);
+        }
+        let digit = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+        if digit.len() == 1 {
+            u8::from_str_radix(digit, 16).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Register named by the next token, without reading it
+    fn peek_register(&self) -> Option<u8> {
+        self.peek().and_then(|text| self.register_named(text))
+    }
+
+    /// Read a register
+    fn register(&mut self) -> Result<u8, AssemblyError> {
+        let token = self.next()?;
+        self.register_named(&token.text).ok_or_else(|| {
+            self.error(ErrorKind::Expected {
+                expected: "register".to_string(),
+                found: token.text,
+            })
+        })
+    }
+
+    /// Read a name for a new label, constant, alias or macro
+    fn name(&mut self) -> Result<String, AssemblyError> {
+        let token = self.next()?;
+        if number(&token.text).is_some() || self.register_named(&token.text).is_some() {
+            return Err(self.error(ErrorKind::Expected {
+                expected: "name".to_string(),
+                found: token.text,
+            }));
+        }
+        Ok(token.text)
+    }
+
+    /// Value of a number, constant or defined label
+    fn known_value(&self, text: &str) -> Result<f64, AssemblyError> {
+        if let Some(value) = number(text) {
+            return Ok(value);
+        }
+        if let Some(&value) = self.constants.get(text) {
+            return Ok(value);
+        }
+        match self.labels.get(text) {
+            Some(&address) => Ok(address as f64),
+            None => Err(self.error(ErrorKind::UndefinedLabel(text.to_string()))),
+        }
+    }
+
+    /// Read a number, constant or defined label
+    fn value(&mut self) -> Result<f64, AssemblyError> {
+        let token = self.next()?;
+        self.known_value(&token.text)
+    }
+
+    /// Check that `value` is an integer in `range`
+    fn in_range(
+        &self,
+        value: f64,
+        range: std::ops::RangeInclusive<i64>,
+    ) -> Result<i64, AssemblyError> {
+        let value = value as i64;
+        if range.contains(&value) {
+            Ok(value)
+        } else {
+            Err(self.error(ErrorKind::OutOfRange(value)))
+        }
+    }
+
+    /// Read a byte, negative values are stored in two's complement
+    fn short(&mut self) -> Result<u8, AssemblyError> {
+        let value = self.value()?;
+        Ok(self.in_range(value, -128..=255)? as u8)
+    }
+
+    /// Read a 4-bit value
+    fn nibble(&mut self) -> Result<u8, AssemblyError> {
+        let value = self.value()?;
+        Ok(self.in_range(value, 0..=0xF)? as u8)
+    }
+
+    /// Read an address for the opcode at `here`.
+    /// Labels that aren't defined yet are recorded to be patched into the `kind` field later.
+    fn address(&mut self, kind: FixupKind) -> Result<u16, AssemblyError> {
+        let token = self.next()?;
+        let known = number(&token.text).is_some()
+            || self.constants.contains_key(&token.text)
+            || self.labels.contains_key(&token.text);
+        if !known && self.register_named(&token.text).is_none() {
+            self.fixups.push(Fixup {
+                address: self.here,
+                kind,
+                name: token.text,
+                location: self.location(),
+            });
+            return Ok(0);
+        }
+        let value = self.known_value(&token.text)?;
+        Ok(self.in_range(value, 0..=(1 << kind.bits()) - 1)? as u16)
+    }
+
+    /// Byte of the program at `address`, 0 if it wasn't written
+    fn rom_byte(&self, address: i64) -> u8 {
+        if address < START as i64 {
+            return 0;
+        }
+        self.rom.get(address as usize - START).copied().unwrap_or(0)
+    }
+
+    /// Write `byte` at `here` and move past it
+    fn write(&mut self, byte: u8) -> Result<(), AssemblyError> {
+        if self.here < START || self.here > 0xFFFF {
+            return Err(self.error(ErrorKind::OutOfRange(self.here as i64)));
+        }
+        let index = self.here - START;
+        if index >= self.rom.len() {
+            self.rom.resize(index + 1, 0);
+            self.written.resize(index + 1, false);
+        }
+        if self.written[index] {
+            return Err(self.error(ErrorKind::Overlap(self.here as u16)));
+        }
+        self.rom[index] = byte;
+        self.written[index] = true;
+        self.here += 1;
+        Ok(())
+    }
+
+    /// Write `opcode` at `here` and move past it
+    fn emit(&mut self, opcode: OpCode) -> Result<(), AssemblyError> {
+        let encoded = opcode
+            .encode()
+            .map_err(|error| self.error(ErrorKind::InvalidOperand(error)))?;
+        for byte in encoded.as_slice() {
+            self.write(*byte)?;
+        }
+        Ok(())
+    }
+
+    /// Set the address of the opcode at `address` to `target`
+    fn patch(&mut self, address: usize, target: usize) -> Result<(), AssemblyError> {
+        if target > 0xFFF {
+            return Err(self.error(ErrorKind::OutOfRange(target as i64)));
+        }
+        let index = address - START;
+        self.rom[index] = (self.rom[index] & 0xF0) | (target >> 8) as u8;
+        self.rom[index + 1] = target as u8;
+        Ok(())
+    }
+
+    /// Define label `name` at `address`
+    fn define_label(&mut self, name: String, address: usize) -> Result<(), AssemblyError> {
+        if self.labels.contains_key(&name) {
+            return Err(self.error(ErrorKind::DuplicateLabel(name)));
+        }
+        let address = self.in_range(address as f64, 0..=0xFFFF)? as u16;
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    /// Check the blocks, patch the forward references and the jump to `main`
+    fn finish(mut self) -> Result<Output, AssemblyError> {
+        if let Some(block) = self.blocks.pop() {
+            let (token, location) = match block {
+                Block::If { location, .. } => ("begin", location),
+                Block::Else { location, .. } => ("else", location),
+                Block::Loop { location, .. } => ("loop", location),
+            };
+            return Err(AssemblyError {
+                location,
+                kind: ErrorKind::Unbalanced(token.to_string()),
+            });
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            let error = |kind| AssemblyError {
+                location: fixup.location.clone(),
+                kind,
+            };
+            let target = match self.labels.get(&fixup.name) {
+                Some(&target) => target as usize,
+                None => return Err(error(ErrorKind::UndefinedLabel(fixup.name.clone()))),
+            };
+            if target >= 1 << fixup.kind.bits() {
+                return Err(error(ErrorKind::OutOfRange(target as i64)));
+            }
+            let index = fixup.address - START;
+            match fixup.kind {
+                FixupKind::Address => {
+                    self.rom[index] = (self.rom[index] & 0xF0) | (target >> 8) as u8;
+                    self.rom[index + 1] = target as u8;
+                }
+                FixupKind::Long => {
+                    self.rom[index + 2] = (target >> 8) as u8;
+                    self.rom[index + 3] = target as u8;
+                }
+                FixupKind::Unpack { nibble } => {
+                    self.rom[index + 1] = match nibble {
+                        Some(nibble) => nibble << 4 | (target >> 8) as u8,
+                        None => (target >> 8) as u8,
+                    };
+                    self.rom[index + 3] = target as u8;
+                }
+            }
+        }
+        if self.jump_to_main {
+            let main = match self.labels.get("main") {
+                Some(&main) => main as usize,
+                None => return Err(self.error(ErrorKind::MissingMain)),
+            };
+            self.patch(START, main)?;
+        }
+        Ok(Output {
+            rom: self.rom,
+            labels: self.labels,
+            constants: self.constants,
+            aliases: self.aliases,
+            breakpoints: self.breakpoints,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::error::{AssemblyError, ErrorKind, Location};
+    use crate::emulator::Emulator;
+    use crate::octo::compile;
+
+    /// ROM compiled from `source`
+    fn rom(source: &str) -> Vec<u8> {
+        compile("test.8o", source).unwrap().rom
+    }
+
+    /// Error of `kind` at `line` of `test.8o`
+    fn error(line: usize, kind: ErrorKind) -> AssemblyError {
+        AssemblyError {
+            location: Location {
+                file: "test.8o".to_string(),
+                line,
+            },
+            kind,
+        }
+    }
+
+    /// Test a program starting with `main`, which needs no jump
+    #[test]
+    fn test_main_first() {
+        let source = "
+            : main
+                v0 := 5
+                v1 += 2
+                i := sprite
+                sprite v0 v1 3
+                loop again
+            : sprite
+                0x80 0x40 0x20
+        ";
+        assert_eq!(
+            rom(source),
+            vec![0x60, 0x05, 0x71, 0x02, 0xA2, 0x0A, 0xD0, 0x13, 0x12, 0x08, 0x80, 0x40, 0x20]
+        );
+    }
+
+    /// Test that `main` starts at `0x200` after definitions that place nothing
+    #[test]
+    fn test_main_after_definitions() {
+        let source =
+            ":const five 5 :alias x v3 :calc six { five + 1 }\n:macro m { x := six }\n: main m";
+        assert_eq!(rom(source), vec![0x63, 0x06]);
+    }
+
+    /// Test keeping the jump to `main` when something was placed before it
+    #[test]
+    fn test_main_after_placement() {
+        let output = compile("test.8o", ": foo : main v0 := 1 jump foo").unwrap();
+        assert_eq!(output.rom, vec![0x12, 0x02, 0x60, 0x01, 0x12, 0x02]);
+        assert_eq!(output.labels["foo"], 0x202);
+        let output = compile("test.8o", ":breakpoint b : main clear").unwrap();
+        assert_eq!(output.rom, vec![0x12, 0x02, 0x00, 0xE0]);
+        assert_eq!(output.breakpoints[&0x202], "b");
+        assert_eq!(rom(":org 0x202 : main clear"), vec![0x12, 0x02, 0x00, 0xE0]);
+        assert_eq!(
+            rom(":calc end { HERE } : main i := end"),
+            vec![0x12, 0x02, 0xA2, 0x02]
+        );
+    }
+
+    /// Test the jump to `main` and calls by name
+    #[test]
+    fn test_jump_to_main() {
+        let source = "
+            : helper
+                v0 := 1
+                ;
+            : main
+                helper
+                loop again
+        ";
+        assert_eq!(
+            rom(source),
+            vec![0x12, 0x06, 0x60, 0x01, 0x00, 0xEE, 0x22, 0x02, 0x12, 0x08]
+        );
+    }
+
+    /// Test the statements of every opcode
+    #[test]
+    fn test_statements() {
+        let source = "
+            : main
+                clear return native 0x123 jump 0x234 jump0 0x345 :call 0x456
+                hires lores scroll-down 3 scroll-left scroll-right exit
+                v1 := v2 v1 := 0x12 v1 := random 0x0F v1 := key v1 := delay
+                v1 += 1 v1 += v2 v1 -= 1 v1 -= v2 v1 =- v2
+                v1 |= v2 v1 &= v2 v1 ^= v2 v1 >>= v2 v1 <<= v2
+                i := 0x123 i := long 0x1234 i := hex v1 i := bighex v1 i += v1
+                delay := v1 buzzer := v1 pitch := v1 bcd v1
+                save v1 load v1 save v1 - v2 load v1 - v2 saveflags v1 loadflags v1
+                sprite v1 v2 0 plane 3 audio
+        ";
+        assert_eq!(
+            rom(source),
+            vec![
+                0x00, 0xE0, 0x00, 0xEE, 0x01, 0x23, 0x12, 0x34, 0xB3, 0x45, 0x24, 0x56, //
+                0x00, 0xFF, 0x00, 0xFE, 0x00, 0xC3, 0x00, 0xFC, 0x00, 0xFB, 0x00, 0xFD, //
+                0x81, 0x20, 0x61, 0x12, 0xC1, 0x0F, 0xF1, 0x0A, 0xF1, 0x07, //
+                0x71, 0x01, 0x81, 0x24, 0x71, 0xFF, 0x81, 0x25, 0x81, 0x27, //
+                0x81, 0x21, 0x81, 0x22, 0x81, 0x23, 0x81, 0x26, 0x81, 0x2E, //
+                0xA1, 0x23, 0xF0, 0x00, 0x12, 0x34, 0xF1, 0x29, 0xF1, 0x30, 0xF1, 0x1E, //
+                0xF1, 0x15, 0xF1, 0x18, 0xF1, 0x3A, 0xF1, 0x33, //
+                0xF1, 0x55, 0xF1, 0x65, 0x51, 0x22, 0x51, 0x23, 0xF1, 0x75, 0xF1, 0x85, //
+                0xD1, 0x20, 0xF3, 0x01, 0xF0, 0x02,
+            ]
+        );
+    }
+
+    /// Test conditions and `if` blocks
+    #[test]
+    fn test_if() {
+        let source = "
+            : main
+                if v0 == 3 then v1 := 1
+                if v0 != v2 then v1 := 2
+                if v0 key then v1 := 3
+                if v0 > 4 then v1 := 4
+                if v0 <= v2 begin
+                    v1 := 5
+                else
+                    v1 := 6
+                end
+        ";
+        assert_eq!(
+            rom(source),
+            vec![
+                0x40, 0x03, 0x61, 0x01, 0x50, 0x20, 0x61, 0x02, 0xE0, 0xA1, 0x61, 0x03, //
+                0x6F, 0x04, 0x8F, 0x05, 0x3F, 0x01, 0x61, 0x04, //
+                0x8F, 0x20, 0x8F, 0x05, 0x3F, 0x01, 0x12, 0x20, 0x61, 0x05, //
+                0x12, 0x22, 0x61, 0x06,
+            ]
+        );
+    }
+
+    /// Test that comparisons behave as written
+    #[test]
+    fn test_comparisons() {
+        for (operator, expected) in [
+            ("<", [1, 0, 0]),
+            (">", [0, 0, 1]),
+            ("<=", [1, 1, 0]),
+            (">=", [0, 1, 1]),
+        ] {
+            for (index, value) in [4, 5, 6].iter().enumerate() {
+                let source = format!(
+                    ": main v0 := {} v1 := 0 if v0 {} 5 then v1 := 1 if v0 {} 5 begin v2 := 1 else v2 := 0 end : halt jump halt",
+                    value, operator, operator
+                );
+                let mut e = Emulator::default();
+                e.load_rom(&rom(&source)).unwrap();
+                e.run_cycles(20).unwrap();
+                assert_eq!(e.get_reg(1), expected[index], "{}", source);
+                assert_eq!(e.get_reg(2), expected[index], "{}", source);
+            }
+        }
+    }
+
+    /// Test loops with `while`
+    #[test]
+    fn test_loop() {
+        let source = "
+            : main
+                v0 := 0
+                loop
+                    v0 += 1
+                    while v0 != 10
+                again
+            : halt
+                jump halt
+        ";
+        let rom = rom(source);
+        assert_eq!(
+            rom,
+            vec![0x60, 0x00, 0x70, 0x01, 0x40, 0x0A, 0x12, 0x0A, 0x12, 0x02, 0x12, 0x0A]
+        );
+        let mut e = Emulator::default();
+        e.load_rom(&rom).unwrap();
+        e.run_cycles(100).unwrap();
+        assert_eq!(e.get_reg(0), 10);
+    }
+
+    /// Test directives, macros and debugging information
+    #[test]
+    fn test_directives() {
+        let source = "
+            :const SPEED 3
+            :alias x v4
+            :macro move reg amount { reg += amount }
+            :calc DOUBLE { SPEED * 2 + 1 }
+            : main
+                move x SPEED
+                x := DOUBLE
+                :breakpoint check
+                :unpack 0xA data
+                :next target
+                v2 := 0
+                :org 0x300
+            : data
+                :byte { 1 << 4 }
+                :byte DOUBLE
+                :monitor data 2
+        ";
+        let output = compile("test.8o", source).unwrap();
+        let mut expected = vec![0x74, 0x03, 0x64, 0x09, 0x60, 0xA3, 0x61, 0x00, 0x62, 0x00];
+        expected.resize(0x100, 0);
+        expected.extend(vec![0x10, 0x09]);
+        assert_eq!(output.rom, expected);
+        assert_eq!(output.labels["data"], 0x300);
+        assert_eq!(output.labels["target"], 0x209);
+        assert_eq!(output.constants["DOUBLE"], 9.0);
+        assert_eq!(output.aliases["x"], 4);
+        assert_eq!(output.breakpoints[&0x204], "check");
+    }
+
+    /// Test macro expansion counting and forward references of long addresses
+    #[test]
+    fn test_macro_calls() {
+        let source = "
+            :macro count { :byte CALLS }
+            : main
+                i := long data
+                count count
+            : data
+        ";
+        assert_eq!(rom(source), vec![0xF0, 0x00, 0x02, 0x06, 0x00, 0x01]);
+    }
+
+    /// Test rejecting macros that expand themselves
+    #[test]
+    fn test_recursive_macro() {
+        for source in [":macro m { m }\n: main m", ":macro m { m m }\n: main m"].iter() {
+            assert_eq!(
+                compile("test.8o", source),
+                Err(error(2, ErrorKind::Recursive("m".to_string())))
+            );
+        }
+        let nested = ":macro a { b b }\n:macro b { :byte 1 }\n: main a";
+        assert_eq!(rom(nested), vec![0x01, 0x01]);
+    }
+
+    /// Test that errors point to their line
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            compile("test.8o", ": main\njump nowhere"),
+            Err(error(2, ErrorKind::UndefinedLabel("nowhere".to_string())))
+        );
+        assert_eq!(
+            compile("test.8o", ": main\n\nend"),
+            Err(error(3, ErrorKind::Unbalanced("end".to_string())))
+        );
+        assert_eq!(
+            compile("test.8o", ": main\nloop\nv0 += 1"),
+            Err(error(2, ErrorKind::Unbalanced("loop".to_string())))
+        );
+        assert_eq!(
+            compile("test.8o", ": start\nclear"),
+            Err(error(2, ErrorKind::MissingMain))
+        );
+        assert_eq!(
+            compile("test.8o", ": main\nclear\n:org 0x200\nclear"),
+            Err(error(4, ErrorKind::Overlap(0x200)))
+        );
+        assert_eq!(
+            compile("test.8o", ": main\nscroll-up 2"),
+            Err(error(2, ErrorKind::Unsupported("scroll-up".to_string())))
+        );
+        assert_eq!(
+            compile("test.8o", ": main\nv0 := 256"),
+            Err(error(2, ErrorKind::OutOfRange(256)))
+        );
+        assert_eq!(
+            compile("test.8o", ": main\nv0 ?= 1"),
+            Err(error(
+                2,
+                ErrorKind::Expected {
+                    expected: "operator".to_string(),
+                    found: "?=".to_string()
+                }
+            ))
+        );
+    }
+}
@@ -0,0 +1,607 @@
+use crate::assembler::error::{AssemblyError, ErrorKind};
+use crate::emulator::opcode::OpCode::*;
+use crate::octo::lexer::Token;
+use crate::octo::{number, Block, Compiler, FixupKind, Macro, MAX_DEPTH, START};
+
+impl Compiler {
+    /// Compile the next statement
+    pub fn statement(&mut self) -> Result<(), AssemblyError> {
+        let token = self.next()?;
+        if ![":", ":const", ":alias", ":calc", ":macro"].contains(&token.text.as_str()) {
+            self.main_first = false;
+        }
+        match token.text.as_str() {
+            ":" => {
+                let name = self.name()?;
+                if name == "main" && self.main_first {
+                    self.jump_to_main = false;
+                    self.rom.clear();
+                    self.written.clear();
+                    self.here = START;
+                }
+                self.main_first = false;
+                self.define_label(name, self.here)?;
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            }
+            ":calc" => {
+                let name = self.name()?;
+                let value = self.calc_block()?;
+                self.constants.insert(name, value);
+            }
+            ":macro" => self.define_macro()?,
+            ":unpack" => {
+                let nibble = if self.peek() == Some("long") {
+                    self.next()?;
+                    None
+                } else {
+                    Some(self.nibble()?)
+                };
+                let target = self.address(FixupKind::Unpack { nibble })?;
+                let high = match nibble {
+                    Some(nibble) => nibble << 4 | (target >> 8) as u8,
+                    None => (target >> 8) as u8,
+                };
+                self.emit(RegSetConst {
+                    register: 0,
+                    constant: high,
+                })?;
+                self.emit(RegSetConst {
+                    register: 1,
+                    constant: target as u8,
+                })?;
+            }
+            ":next" => {
+                let name = self.name()?;
+                self.define_label(name, self.here + 1)?;
+            }
+            ":org" => {
+                let value = self.value()?;
+                self.here = self.in_range(value, START as i64..=0xFFFF)? as usize;
+            }
+            ":byte" => {
+                let value = if self.peek() == Some("{") {
+                    self.calc_block()?
+                } else {
+                    self.value()?
+                };
+                let byte = self.in_range(value, -128..=255)? as u8;
+                self.write(byte)?;
+            }
+            ":call" => {
+                let target = self.address(FixupKind::Address)?;
+                self.emit(Subroutine { target })?;
+            }
+            ":breakpoint" => {
+                let name = self.name()?;
+                self.breakpoints.insert(self.here as u16, name);
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            ";" | "return" => self.emit(Return)?,
+            "clear" => self.emit(ClearScreen)?,
+            "hires" => self.emit(HighRes)?,
+            "lores" => self.emit(LowRes)?,
+            "exit" => self.emit(Exit)?,
+            "scroll-down" => {
+                let rows = self.nibble()?;
+                self.emit(ScrollDown { rows })?;
+            }
+            "scroll-left" => self.emit(ScrollLeft)?,
+            "scroll-right" => self.emit(ScrollRight)?,
+            "audio" => self.emit(LoadAudioPattern)?,
+            "plane" => {
+                let planes = self.nibble()?;
+                self.emit(SelectPlanes { planes })?;
+            }
+            "bcd" => {
+                let register = self.register()?;
+                self.emit(StoreBCD { register })?;
+            }
+            "save" | "load" => {
+                let register_x = self.register()?;
+                let save = token.text == "save";
+                let opcode = if self.peek() == Some("-") {
+                    self.next()?;
+                    let register_y = self.register()?;
+                    if save {
+                        RegRangeDump {
+                            register_x,
+                            register_y,
+                        }
+                    } else {
+                        RegRangeLoad {
+                            register_x,
+                            register_y,
+                        }
+                    }
+                } else if save {
+                    RegDump {
+                        register: register_x,
+                    }
+                } else {
+                    RegLoad {
+                        register: register_x,
+                    }
+                };
+                self.emit(opcode)?;
+            }
+            "saveflags" => {
+                let register = self.register()?;
+                self.emit(StoreFlags { register })?;
+            }
+            "loadflags" => {
+                let register = self.register()?;
+                self.emit(LoadFlags { register })?;
+            }
+            "sprite" => {
+                let coord_x = self.register()?;
+                let coord_y = self.register()?;
+                let height = self.nibble()?;
+                self.emit(DisplaySprite {
+                    coord_x,
+                    coord_y,
+                    height,
+                })?;
+            }
+            "jump" => {
+                let target = self.address(FixupKind::Address)?;
+                self.emit(Goto { target })?;
+            }
+            "jump0" => {
+                let target = self.address(FixupKind::Address)?;
+                self.emit(JumpRegZero { target })?;
+            }
+            "native" => {
+                let target = self.address(FixupKind::Address)?;
+                self.emit(_NativeCall { target })?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let register = self.register()?;
+                self.emit(match token.text.as_str() {
+                    "delay" => SetDelayTimerToReg { register },
+                    "buzzer" => SetSoundTimerToReg { register },
+                    _ => SetPitchToReg { register },
+                })?;
+            }
+            "i" => self.index_statement()?,
+            "if" => self.if_statement()?,
+            "else" => match self.blocks.pop() {
+                Some(Block::If { jump, .. }) => {
+                    let location = self.location();
+                    let end_jump = self.here;
+                    self.emit(Goto { target: 0 })?;
+                    self.patch(jump, self.here)?;
+                    self.blocks.push(Block::Else {
+                        jump: end_jump,
+                        location,
+                    });
+                }
+                _ => return Err(self.error(ErrorKind::Unbalanced(token.text))),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. }) | Some(Block::Else { jump, .. }) => {
+                    self.patch(jump, self.here)?;
+                }
+                _ => return Err(self.error(ErrorKind::Unbalanced(token.text))),
+            },
+            "loop" => {
+                let location = self.location();
+                self.blocks.push(Block::Loop {
+                    start: self.here,
+                    whiles: Vec::new(),
+                    location,
+                });
+            }
+            "while" => {
+                if !self
+                    .blocks
+                    .iter()
+                    .any(|block| matches!(block, Block::Loop { .. }))
+                {
+                    return Err(self.error(ErrorKind::Unbalanced(token.text)));
+                }
+                self.condition(true)?;
+                let jump = self.here;
+                self.emit(Goto { target: 0 })?;
+                if let Some(Block::Loop { whiles, .. }) = self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|block| matches!(block, Block::Loop { .. }))
+                {
+                    whiles.push(jump);
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, whiles, .. }) => {
+                    self.emit(Goto {
+                        target: start as u16,
+                    })?;
+                    for jump in whiles {
+                        self.patch(jump, self.here)?;
+                    }
+                }
+                _ => return Err(self.error(ErrorKind::Unbalanced(token.text))),
+            },
+            "scroll-up" | ":stringmode" | ":assert" | ":include" | ":pointer" => {
+                return Err(self.error(ErrorKind::Unsupported(token.text)))
+            }
+            text => {
+                if let Some(register) = self.register_named(text) {
+                    self.register_statement(register)?;
+                } else if self.macros.contains_key(text) {
+                    self.expand_macro(&token.text, token.depth)?;
+                } else if let Some(value) = number(text) {
+                    let byte = self.in_range(value, -128..=255)? as u8;
+                    self.write(byte)?;
+                } else {
+                    self.tokens.push_front(token);
+                    let target = self.address(FixupKind::Address)?;
+                    self.emit(Subroutine { target })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compile `vx <operator> <operand>`
+    fn register_statement(&mut self, register_x: u8) -> Result<(), AssemblyError> {
+        let operator = self.next()?;
+        let register_y = self.peek_register();
+        if register_y.is_some() {
+            self.next()?;
+        }
+        let opcode = match (operator.text.as_str(), register_y) {
+            (":=", Some(register_y)) => RegMov {
+                register_x,
+                register_y,
+            },
+            (":=", None) => match self.peek() {
+                Some("random") => {
+                    self.next()?;
+                    RandToReg {
+                        register: register_x,
+                        constant: self.short()?,
+                    }
+                }
+                Some("key") => {
+                    self.next()?;
+                    SetRegToKeyPressed {
+                        register: register_x,
+                    }
+                }
+                Some("delay") => {
+                    self.next()?;
+                    SetRegToDelayTimer {
+                        register: register_x,
+                    }
+                }
+                _ => RegSetConst {
+                    register: register_x,
+                    constant: self.short()?,
+                },
+            },
+            ("+=", Some(register_y)) => RegAdd {
+                register_x,
+                register_y,
+            },
+            ("+=", None) => RegAddConst {
+                register: register_x,
+                constant: self.short()?,
+            },
+            ("-=", Some(register_y)) => RegSub {
+                register_x,
+                register_y,
+            },
+            ("-=", None) => RegAddConst {
+                register: register_x,
+                constant: self.short()?.wrapping_neg(),
+            },
+            ("=-", Some(register_y)) => RegReverseSub {
+                register_x,
+                register_y,
+            },
+            ("|=", Some(register_y)) => RegBitwiseOr {
+                register_x,
+                register_y,
+            },
+            ("&=", Some(register_y)) => RegBitwiseAnd {
+                register_x,
+                register_y,
+            },
+            ("^=", Some(register_y)) => RegBitwiseXor {
+                register_x,
+                register_y,
+            },
+            (">>=", Some(register_y)) => RegRightShift {
+                register_x,
+                register_y,
+            },
+            ("<<=", Some(register_y)) => RegLeftShift {
+                register_x,
+                register_y,
+            },
+            ("=-", None)
+            | ("|=", None)
+            | ("&=", None)
+            | ("^=", None)
+            | (">>=", None)
+            | ("<<=", None) => {
+                let found = self.next()?;
+                return Err(self.error(ErrorKind::Expected {
+                    expected: "register".to_string(),
+                    found: found.text,
+                }));
+            }
+            _ => {
+                return Err(self.error(ErrorKind::Expected {
+                    expected: "operator".to_string(),
+                    found: operator.text,
+                }))
+            }
+        };
+        self.emit(opcode)
+    }
+
+    /// Compile `i := <operand>` and `i += vx`
+    fn index_statement(&mut self) -> Result<(), AssemblyError> {
+        let operator = self.next()?;
+        if operator.text == "+=" {
+            let register = self.register()?;
+            return self.emit(MemAddReg { register });
+        }
+        if operator.text != ":=" {
+            return Err(self.error(ErrorKind::Expected {
+                expected: "`:=` or `+=`".to_string(),
+                found: operator.text,
+            }));
+        }
+        let opcode = match self.peek() {
+            Some("hex") => {
+                self.next()?;
+                MemMoveToRegChar {
+                    register: self.register()?,
+                }
+            }
+            Some("bighex") => {
+                self.next()?;
+                MemMoveToRegLargeChar {
+                    register: self.register()?,
+                }
+            }
+            Some("long") => {
+                self.next()?;
+                MemLong {
+                    target: self.address(FixupKind::Long)?,
+                }
+            }
+            _ => Mem {
+                target: self.address(FixupKind::Address)?,
+            },
+        };
+        self.emit(opcode)
+    }
+
+    /// Compile `if <condition> then` and `if <condition> begin`
+    fn if_statement(&mut self) -> Result<(), AssemblyError> {
+        let keyword = match self.tokens.get(1).map(|token| token.text.as_str()) {
+            Some("key") | Some("-key") => 2,
+            _ => 3,
+        };
+        let location = self.location();
+        match self.tokens.get(keyword).map(|token| token.text.as_str()) {
+            Some("then") => {
+                self.condition(false)?;
+                self.expect("then")
+            }
+            Some("begin") => {
+                self.condition(true)?;
+                self.expect("begin")?;
+                let jump = self.here;
+                self.emit(Goto { target: 0 })?;
+                self.blocks.push(Block::If { jump, location });
+                Ok(())
+            }
+            _ => {
+                let found = self
+                    .tokens
+                    .get(keyword)
+                    .map_or("end of file".to_string(), |token| token.text.clone());
+                Err(self.error(ErrorKind::Expected {
+                    expected: "`then` or `begin`".to_string(),
+                    found,
+                }))
+            }
+        }
+    }
+
+    /// Compile a condition into opcodes that skip the next one when it's false,
+    /// or when it's true if `negated`.
+    ///
+    /// Ordering comparisons subtract into the `compare-temp` alias (`vf` by default)
+    /// and test the borrow flag in `vf`.
+    fn condition(&mut self, negated: bool) -> Result<(), AssemblyError> {
+        let register = self.register()?;
+        let operator = self.next()?;
+        let mut operator = operator.text;
+        if negated {
+            let negation = match operator.as_str() {
+                "==" => "!=",
+                "!=" => "==",
+                "key" => "-key",
+                "-key" => "key",
+                "<" => ">=",
+                ">" => "<=",
+                "<=" => ">",
+                ">=" => "<",
+                other => other,
+            };
+            operator = negation.to_string();
+        }
+        let temp = self.aliases.get("compare-temp").copied().unwrap_or(0xF);
+        match operator.as_str() {
+            "==" | "!=" => {
+                let equal = operator == "==";
+                let opcode = match self.peek_register() {
+                    Some(register_y) => {
+                        self.next()?;
+                        if equal {
+                            SkipNextIfRegNotEqualToReg {
+                                register_x: register,
+                                register_y,
+                            }
+                        } else {
+                            SkipNextIfRegEqualToReg {
+                                register_x: register,
+                                register_y,
+                            }
+                        }
+                    }
+                    None => {
+                        let constant = self.short()?;
+                        if equal {
+                            SkipNextIfRegNotEqualToConst { register, constant }
+                        } else {
+                            SkipNextIfRegEqualToConst { register, constant }
+                        }
+                    }
+                };
+                self.emit(opcode)
+            }
+            "key" => self.emit(SkipNextIfRegKeyNotPressed { register }),
+            "-key" => self.emit(SkipNextIfRegKeyPressed { register }),
+            "<" | ">" | "<=" | ">=" => {
+                match self.peek_register() {
+                    Some(register_y) => {
+                        self.next()?;
+                        self.emit(RegMov {
+                            register_x: temp,
+                            register_y,
+                        })?;
+                    }
+                    None => {
+                        let constant = self.short()?;
+                        self.emit(RegSetConst {
+                            register: temp,
+                            constant,
+                        })?;
+                    }
+                }
+                // `temp - vx` doesn't borrow when `vx <= operand`, `vx - temp` when `vx >= operand`
+                self.emit(match operator.as_str() {
+                    ">" | "<=" => RegSub {
+                        register_x: temp,
+                        register_y: register,
+                    },
+                    _ => RegReverseSub {
+                        register_x: temp,
+                        register_y: register,
+                    },
+                })?;
+                self.emit(match operator.as_str() {
+                    ">" | "<" => SkipNextIfRegEqualToConst {
+                        register: 0xF,
+                        constant: 1,
+                    },
+                    _ => SkipNextIfRegNotEqualToConst {
+                        register: 0xF,
+                        constant: 1,
+                    },
+                })
+            }
+            _ => Err(self.error(ErrorKind::Expected {
+                expected: "comparison".to_string(),
+                found: operator,
+            })),
+        }
+    }
+
+    /// Compile `:macro name parameters... { body }`
+    fn define_macro(&mut self) -> Result<(), AssemblyError> {
+        let name = self.name()?;
+        let mut parameters = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token.text == "{" {
+                break;
+            }
+            parameters.push(token.text);
+        }
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            body.push(token);
+        }
+        self.macros.insert(
+            name,
+            Macro {
+                parameters,
+                body,
+                calls: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// Replace the invocation of macro `name`, made by a token of `depth`, with its body
+    fn expand_macro(&mut self, name: &str, depth: usize) -> Result<(), AssemblyError> {
+        if depth >= MAX_DEPTH {
+            return Err(self.error(ErrorKind::Recursive(name.to_string())));
+        }
+        let parameters = self.macros[name].parameters.len();
+        let mut arguments = Vec::with_capacity(parameters);
+        for _ in 0..parameters {
+            arguments.push(self.next()?.text);
+        }
+        let line = self.line;
+        let definition = self
+            .macros
+            .get_mut(name)
+            .expect("the macro was just looked up");
+        let calls = definition.calls;
+        definition.calls += 1;
+        let expansion: Vec<Token> = definition
+            .body
+            .iter()
+            .map(|token| {
+                let text = match definition.parameters.iter().position(|p| *p == token.text) {
+                    Some(index) => arguments[index].clone(),
+                    None if token.text == "CALLS" => calls.to_string(),
+                    None => token.text.clone(),
+                };
+                Token {
+                    text,
+                    line,
+                    depth: depth + 1,
+                }
+            })
+            .collect();
+        for token in expansion.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+}